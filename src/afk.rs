@@ -0,0 +1,49 @@
+use std::{sync::Arc, thread, time::Duration};
+
+use input_linux::{Key, KeyState, RelativeAxis};
+
+use crate::{
+    args::{key_from_code, ClickOptions},
+    rng::Rng,
+    OutputBackend,
+};
+
+/// Keeps a game from kicking the player for being idle, with small movements and a key tap
+/// at random times, independently of the binds
+pub struct AntiAfk {
+    /// Average time between the actions
+    interval: Duration,
+    key: Key,
+}
+
+impl AntiAfk {
+    pub fn from_options(options: &ClickOptions) -> Option<Self> {
+        options.anti_afk.then(|| Self {
+            interval: options.anti_afk_interval.0,
+            key: key_from_code(options.anti_afk_key),
+        })
+    }
+
+    pub fn spawn(self, output: Arc<dyn OutputBackend>) {
+        thread::spawn(move || {
+            let mut rng = Rng::from_time();
+            loop {
+                // Between half and one and a half times the interval
+                let pause = self.interval.mul_f64(0.5 + rng.next_f64());
+                thread::sleep(pause);
+
+                if rng.next_f64() < 0.5 {
+                    let x = rng.range_i64(-6, 6) as i32;
+                    let y = rng.range_i64(-6, 6) as i32;
+                    output.send_relative(&[(RelativeAxis::X, x), (RelativeAxis::Y, y)]);
+                    thread::sleep(Duration::from_millis(rng.range_i64(80, 250) as u64));
+                    output.send_relative(&[(RelativeAxis::X, -x), (RelativeAxis::Y, -y)]);
+                } else {
+                    output.send_key(self.key, KeyState::PRESSED);
+                    thread::sleep(Duration::from_millis(rng.range_i64(50, 150) as u64));
+                    output.send_key(self.key, KeyState::RELEASED);
+                }
+            }
+        });
+    }
+}
@@ -1,181 +1,2375 @@
-use clap::Parser;
+use clap::{parser::ValueSource, ArgMatches, Parser};
+use input_linux::Key;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{collections::{BTreeMap, HashSet}, path::PathBuf, time::Duration};
 
+use crate::{
+    config::{Config, ConfigCommand},
+    macros::Macro,
+};
+
+// Click options shared by `run` and `run-legacy`
+#[derive(clap::Args, Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ClickOptions {
+    /// Set the cooldown in milliseconds
+    #[arg(short, env = "THECLICKER_COOLDOWN", default_value_t = 25)]
+    pub cooldown: u64,
+
+    /// Set cooldown in milliseconds, between press and release
+    #[arg(short = 'C', default_value_t = 0)]
+    pub cooldown_press_release: u64,
+
+    /// Milliseconds added or removed from the cooldown by the faster and slower binds
+    #[arg(long, default_value_t = 5)]
+    pub cooldown_step: u64,
+
+    /// Set the cooldown in milliseconds for the left autoclicker only
+    #[arg(long)]
+    pub cooldown_left: Option<u64>,
+
+    /// Set the cooldown in milliseconds for the right autoclicker only
+    #[arg(long)]
+    pub cooldown_right: Option<u64>,
+
+    /// Target clicks per second, replaces the cooldown
+    /// (the time between press and release is taken into account)
+    #[arg(long)]
+    pub cps: Option<f64>,
+
+    /// Randomize every cooldown uniformly in `cooldown ± jitter` milliseconds
+    #[arg(long, default_value_t = 0)]
+    pub cooldown_jitter: u64,
+
+    /// Randomize every time between press and release uniformly in
+    /// `cooldown_press_release ± jitter` milliseconds
+    #[arg(long, default_value_t = 0)]
+    pub press_jitter: u64,
+
+    /// Humanize mode, the cooldown is drawn from a normal distribution around `cooldown`
+    /// with occasional longer pauses
+    #[arg(long, default_value_t = false, conflicts_with = "cooldown_jitter")]
+    pub humanize: bool,
+
+    /// Standard deviation in milliseconds for the humanize mode
+    #[arg(long, default_value_t = 12.0)]
+    pub humanize_stddev: f64,
+
+    /// Chance from 0 to 1 that a humanized cooldown is followed by a longer pause
+    #[arg(long, default_value_t = 0.02)]
+    pub humanize_pause_chance: f64,
+
+    /// Average length in milliseconds of the longer humanize pauses
+    #[arg(long, default_value_t = 350)]
+    pub humanize_pause: u64,
+
+    /// Drag clicking preset, bursts of 20 to 30 clicks per second with short breaks in between,
+    /// replaces the cooldown (best with the hold mode)
+    #[arg(long, default_value_t = false, conflicts_with_all = ["cps", "humanize", "cooldown_jitter"])]
+    pub drag_click: bool,
+
+    /// Button clicked by the left autoclicker
+    #[arg(long, value_enum, default_value_t = MouseButton::Left)]
+    pub left_button: MouseButton,
+
+    /// Button clicked by the right autoclicker
+    #[arg(long, value_enum, default_value_t = MouseButton::Right)]
+    pub right_button: MouseButton,
+
+    /// Keycode or key name pressed by the left autoclicker instead of a mouse button
+    /// Keyboard: 18 E, 57 Space
+    #[arg(long, value_parser = parse_key)]
+    #[serde(deserialize_with = "key_code::option::deserialize")]
+    pub left_key: Option<u16>,
+
+    /// Keycode or key name pressed by the right autoclicker instead of a mouse button
+    #[arg(long, value_parser = parse_key)]
+    #[serde(deserialize_with = "key_code::option::deserialize")]
+    pub right_key: Option<u16>,
+
+    /// Text typed by the left autoclicker every cooldown (US layout)
+    #[arg(long)]
+    pub left_text: Option<String>,
+
+    /// Text typed by the right autoclicker every cooldown (US layout)
+    #[arg(long)]
+    pub right_text: Option<String>,
+
+    /// Time in milliseconds between the typed characters
+    #[arg(long, default_value_t = 15)]
+    pub type_delay: u64,
+
+    /// Buttons and keycodes pressed one after the other by the left autoclicker every cooldown,
+    /// like `16,left` for Q then left click
+    #[arg(long, value_delimiter = ',')]
+    pub left_actions: Vec<Output>,
+
+    /// Buttons and keycodes pressed one after the other by the right autoclicker every cooldown
+    #[arg(long, value_delimiter = ',')]
+    pub right_actions: Vec<Output>,
+
+    /// Drag with the left autoclicker, press then move by these `X,Y` offsets and release,
+    /// repeat to make a path
+    #[arg(long, allow_hyphen_values = true)]
+    pub left_drag: Vec<Point>,
+
+    /// Drag with the right autoclicker, press then move by these `X,Y` offsets and release,
+    /// repeat to make a path
+    #[arg(long, allow_hyphen_values = true)]
+    pub right_drag: Vec<Point>,
+
+    /// In how many small movements every drag offset is split
+    #[arg(long, default_value_t = 10)]
+    pub drag_steps: u32,
+
+    /// Time in milliseconds between the small drag movements
+    #[arg(long, default_value_t = 10)]
+    pub drag_delay: u64,
+
+    /// Scroll the wheel by this many notches every cooldown with the left autoclicker,
+    /// instead of clicking (positive is up, negative is down)
+    #[arg(long, allow_hyphen_values = true)]
+    pub left_scroll: Option<i32>,
+
+    /// Scroll the wheel by this many notches every cooldown with the right autoclicker,
+    /// instead of clicking (positive is up, negative is down)
+    #[arg(long, allow_hyphen_values = true)]
+    pub right_scroll: Option<i32>,
+
+    /// Modifier held while the autoclicker is active, like shift-click (can be repeated)
+    #[arg(long = "modifier", value_enum)]
+    pub modifiers: Vec<Modifier>,
+
+    /// Move the pointer to this position before every click, like `960,540`,
+    /// when repeated the clicks go through the positions one after the other
+    #[arg(long = "position", requires = "screen")]
+    pub positions: Vec<Point>,
+
+    /// Every click lands at a random point within this many pixels of the position
+    #[arg(long, default_value_t = 0)]
+    pub position_jitter: u32,
+
+    /// Size of the screen for `--position`, like `1920x1080`
+    #[arg(long)]
+    pub screen: Option<ScreenSize>,
+
+    /// Mouse jiggler, move the pointer a little every interval to keep the session alive, like `30s`
+    #[arg(long)]
+    pub jiggle: Option<HumanDuration>,
+
+    /// How many pixels the jiggler moves the pointer
+    #[arg(long, default_value_t = 1)]
+    pub jiggle_distance: i32,
+
+    /// Only jiggle when there was no input from the device for this long, like `2m`
+    #[arg(long, requires = "jiggle")]
+    pub jiggle_idle: Option<HumanDuration>,
+
+    /// Send single, double or triple clicks, or hold the button down
+    #[arg(long, value_enum, default_value_t = ClickMode::Single)]
+    pub click_mode: ClickMode,
+
+    /// Time in milliseconds between the clicks of a double or triple click,
+    /// keep it under the double click time of your desktop
+    #[arg(long, default_value_t = 30)]
+    pub multi_click_gap: u64,
+
+    /// Pattern played by the left autoclicker instead of left clicks (only from the config file)
+    #[arg(skip)]
+    pub left_pattern: Option<Vec<PatternStep>>,
+
+    /// Pattern played by the right autoclicker instead of right clicks (only from the config file)
+    #[arg(skip)]
+    pub right_pattern: Option<Vec<PatternStep>>,
+
+    /// Macro recorded with `record` replayed by the left autoclicker instead of left clicks
+    #[arg(long)]
+    pub left_macro: Option<PathBuf>,
+
+    /// Macro recorded with `record` replayed by the right autoclicker instead of right clicks
+    #[arg(long)]
+    pub right_macro: Option<PathBuf>,
+
+    /// Chance from 0 to 1 that a scheduled click is silently skipped
+    #[arg(long, default_value_t = 0.0)]
+    pub miss_rate: f64,
+
+    /// Deactivate the autoclicker after sending this many clicks
+    #[arg(long)]
+    pub max_clicks: Option<u64>,
+
+    /// Exit instead of deactivating when `--max-clicks` is reached
+    #[arg(long, default_value_t = false, requires = "max_clicks")]
+    pub max_clicks_exit: bool,
+
+    /// Duty cycle, click for this long then rest for `--rest`, while the autoclicker stays active
+    #[arg(long = "active", requires = "duty_rest")]
+    pub duty_active: Option<HumanDuration>,
+
+    /// Duty cycle, how long to rest after clicking for `--active`
+    #[arg(long = "rest", requires = "duty_active")]
+    pub duty_rest: Option<HumanDuration>,
+
+    /// Lock the autoclicker after this much time from the first activation, like `90s`, `10m` or `1h`,
+    /// counted again from the next activation after the unlock. Without a lock or enable bind it is
+    /// only stopped
+    #[arg(long)]
+    pub run_for: Option<HumanDuration>,
+
+    /// Pause or only click depending on the focused window, like `pause:firefox` or
+    /// `only:title=Minecraft`, the window class is matched by default
+    #[arg(long = "window-rule")]
+    pub window_rules: Vec<WindowRule>,
+
+    /// Pause while the desktop session is locked or switched away, using systemd-logind
+    #[arg(long, default_value_t = false)]
+    pub session_pause: bool,
+
+    /// Also deactivate the autoclicker when the session is locked, so it does not resume on unlock
+    #[arg(long, default_value_t = false, requires = "session_pause")]
+    pub session_deactivate: bool,
+
+    /// Device whose override keys pause the clicking while held, like a keyboard, can be repeated
+    #[arg(short = 'o', long = "override-device")]
+    pub override_devices: Vec<String>,
+
+    /// Use the first device in /dev/input that looks like a full keyboard as an override device
+    #[arg(long, default_value_t = false)]
+    pub override_auto: bool,
+
+    /// Key or combo that pauses the clicking while held on the override devices, like `KEY_F8` or
+    /// `29+57` for Ctrl+Space, can be repeated
+    #[arg(long = "override-key")]
+    pub override_keys: Vec<Bind>,
+
+    /// Pause when any key of the override devices is pressed, like while typing, instead of only
+    /// the override keys
+    #[arg(long, default_value_t = false)]
+    pub override_any: bool,
+
+    /// Time without any key press before clicking again with `--override-any`, like `800ms`
+    #[arg(long, default_value = "800ms", requires = "override_any")]
+    pub override_quiet: HumanDuration,
+
+    /// Only click while an override key is held, like a pedal, instead of pausing
+    #[arg(long, default_value_t = false)]
+    pub override_invert: bool,
+
+    /// While an override device is gone, like a sleeping Bluetooth keyboard, keep clicking or
+    /// pause until it comes back
+    #[arg(long, value_enum, default_value_t = OverrideLost::Continue)]
+    pub override_lost: OverrideLost,
+
+    /// Grab the override devices, their override keys are swallowed and the other events are
+    /// relayed through the virtual device
+    #[arg(long, default_value_t = false)]
+    pub override_grab: bool,
+
+    /// Wait this long after the override keys or the other pauses end before clicking again, like
+    /// `300ms`, so no click lands in the middle of a keystroke
+    #[arg(long)]
+    pub override_resume_delay: Option<HumanDuration>,
+
+    /// Click right away when a pause ends, instead of waiting for a cooldown first
+    #[arg(long, default_value_t = false)]
+    pub override_resume_click: bool,
+
+    /// The virtual device is also a gamepad, with the gamepad buttons and sticks, for sending
+    /// gamepad buttons like BTN_SOUTH with `--left-key`
+    #[arg(long, default_value_t = false)]
+    pub gamepad: bool,
+
+    /// Name of the virtual device, like `Logitech G305` so it looks like an ordinary mouse
+    #[arg(long, default_value = "TheClicker")]
+    pub device_name: String,
+
+    /// Bus of the virtual device
+    #[arg(long, value_enum, default_value_t = Bus::Usb)]
+    pub device_bus: Bus,
+
+    /// Vendor id, product id and version of the virtual device in hex, like `046d:c08b` or
+    /// `046d:c08b:0111`
+    #[arg(long, default_value_t = DeviceId::default())]
+    pub device_id: DeviceId,
+
+    /// A virtual mouse and a separate virtual keyboard instead of one device with both, for the
+    /// compositors and games that mishandle the hybrid device
+    #[arg(long, default_value_t = false)]
+    pub split_devices: bool,
+
+    /// Keyboard LED lit while the autoclicker is active, on the input and override devices that
+    /// have it
+    #[arg(long, value_enum)]
+    pub led: Option<Led>,
+
+    /// What the LED shows
+    #[arg(long, value_enum, default_value_t = LedShows::Active, requires = "led")]
+    pub led_shows: LedShows,
+
+    /// Short rumble of the input or override device with force feedback when the state changes,
+    /// a silent alternative to the beep
+    #[arg(long, default_value_t = false)]
+    pub haptic: bool,
+
+    /// Print the clicks, keys and movements instead of sending them, without grabbing any
+    /// device, for trying the options safely
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Anti-AFK, a small mouse movement or a key tap at random times, independently of the binds
+    #[arg(long, default_value_t = false)]
+    pub anti_afk: bool,
+
+    /// Average time between the anti-AFK actions, like `1m`
+    #[arg(long, default_value = "1m", requires = "anti_afk")]
+    pub anti_afk_interval: HumanDuration,
+
+    /// Keycode or key name tapped by the anti-AFK
+    /// Keyboard: 57 Space, 17 W
+    #[arg(long, default_value_t = 57, value_parser = parse_key, requires = "anti_afk")]
+    #[serde(deserialize_with = "key_code::deserialize")]
+    pub anti_afk_key: u16,
+
+    /// External program told about the binds, the state and the pauses on its stdin, that writes
+    /// the events to send on its stdout, one JSON object per line. It runs unsandboxed as the
+    /// clicker's user
+    #[arg(long)]
+    pub script: Option<PathBuf>,
+
+    /// Time in milliseconds between the `tick` hooks sent to the script
+    #[arg(long, default_value_t = 100, requires = "script")]
+    pub script_tick: u64,
+
+    /// Enable a compiled in plugin by its name, like `pause-file` (can be repeated)
+    #[arg(long = "plugin")]
+    pub plugins: Vec<String>,
+}
+
+impl Default for ClickOptions {
+    fn default() -> Self {
+        Self {
+            cooldown: 25,
+            cooldown_press_release: 0,
+            cooldown_step: 5,
+            cooldown_left: None,
+            cooldown_right: None,
+            cps: None,
+            cooldown_jitter: 0,
+            press_jitter: 0,
+            humanize: false,
+            humanize_stddev: 12.0,
+            humanize_pause_chance: 0.02,
+            humanize_pause: 350,
+            drag_click: false,
+            left_button: MouseButton::Left,
+            right_button: MouseButton::Right,
+            left_key: None,
+            right_key: None,
+            left_text: None,
+            right_text: None,
+            type_delay: 15,
+            left_actions: Vec::new(),
+            right_actions: Vec::new(),
+            left_drag: Vec::new(),
+            right_drag: Vec::new(),
+            drag_steps: 10,
+            drag_delay: 10,
+            left_scroll: None,
+            right_scroll: None,
+            modifiers: Vec::new(),
+            positions: Vec::new(),
+            position_jitter: 0,
+            screen: None,
+            jiggle: None,
+            jiggle_distance: 1,
+            jiggle_idle: None,
+            click_mode: ClickMode::Single,
+            multi_click_gap: 30,
+            left_pattern: None,
+            right_pattern: None,
+            left_macro: None,
+            right_macro: None,
+            miss_rate: 0.0,
+            max_clicks: None,
+            max_clicks_exit: false,
+            duty_active: None,
+            duty_rest: None,
+            run_for: None,
+            window_rules: Vec::new(),
+            session_pause: false,
+            session_deactivate: false,
+            override_devices: Vec::new(),
+            override_auto: false,
+            override_keys: Vec::new(),
+            override_any: false,
+            override_quiet: HumanDuration(Duration::from_millis(800)),
+            override_invert: false,
+            override_lost: OverrideLost::Continue,
+            override_grab: false,
+            override_resume_delay: None,
+            override_resume_click: false,
+            gamepad: false,
+            device_name: "TheClicker".to_string(),
+            device_bus: Bus::Usb,
+            device_id: DeviceId::default(),
+            split_devices: false,
+            led: None,
+            led_shows: LedShows::Active,
+            haptic: false,
+            dry_run: false,
+            anti_afk: false,
+            anti_afk_interval: HumanDuration(Duration::from_secs(60)),
+            anti_afk_key: 57,
+            script: None,
+            script_tick: 100,
+            plugins: Vec::new(),
+        }
+    }
+}
+
+impl ClickOptions {
+    pub fn left_output(&self) -> Key {
+        output_key(self.left_key, self.left_button)
+    }
+
+    pub fn right_output(&self) -> Key {
+        output_key(self.right_key, self.right_button)
+    }
+
+    /// If the virtual device has to send keyboard keys
+    pub fn needs_keyboard(&self) -> bool {
+        let pattern_has_keys = |pattern: &Option<Vec<PatternStep>>| {
+            pattern.iter().flatten().any(|step| matches!(step, PatternStep::Key { .. }))
+        };
+
+        let actions_have_keys =
+            |actions: &[Output]| actions.iter().any(|action| matches!(action, Output::Key(_)));
+
+        !self.modifiers.is_empty()
+            || actions_have_keys(&self.left_actions)
+            || actions_have_keys(&self.right_actions)
+            || self.left_key.is_some()
+            || self.right_key.is_some()
+            || self.left_text.is_some()
+            || self.right_text.is_some()
+            || pattern_has_keys(&self.left_pattern)
+            || pattern_has_keys(&self.right_pattern)
+            || self.macros().any(|path| Macro::load_or_exit(path).has_keys())
+            || self.anti_afk
+            || self.script.is_some()
+    }
+
+    /// The ids the virtual device is created with
+    pub fn device_identity(&self) -> input_linux::InputId {
+        input_linux::InputId {
+            bustype: self.device_bus.code(),
+            vendor: self.device_id.vendor,
+            product: self.device_id.product,
+            version: self.device_id.version,
+        }
+    }
+
+    /// If the virtual device has to send gamepad buttons
+    pub fn needs_gamepad(&self) -> bool {
+        let gamepad_buttons =
+            input_linux::sys::BTN_GAMEPAD as u16..=input_linux::sys::BTN_THUMBR as u16;
+        self.gamepad
+            || [self.left_key, self.right_key]
+                .iter()
+                .flatten()
+                .any(|code| gamepad_buttons.contains(code))
+    }
+
+    /// If the virtual device has to move the pointer
+    pub fn needs_pointer(&self) -> bool {
+        self.jiggle.is_some()
+            || self.anti_afk
+            || self.script.is_some()
+            || !self.left_drag.is_empty()
+            || !self.right_drag.is_empty()
+            || self.macros().next().is_some()
+    }
+
+    /// If the virtual device has to send the scroll wheel
+    pub fn needs_wheel(&self) -> bool {
+        self.left_scroll.is_some()
+            || self.right_scroll.is_some()
+            || self.macros().next().is_some()
+            || self.script.is_some()
+    }
+
+    fn macros(&self) -> impl Iterator<Item = &PathBuf> {
+        self.left_macro.iter().chain(&self.right_macro)
+    }
+
+    pub fn left_cooldown(&self) -> Duration {
+        self.cooldown_left
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| self.effective_cooldown())
+    }
+
+    pub fn right_cooldown(&self) -> Duration {
+        self.cooldown_right
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| self.effective_cooldown())
+    }
+
+    /// Cooldown after the release, derived from `cps` when it is set
+    pub fn effective_cooldown(&self) -> Duration {
+        let press_release = Duration::from_millis(self.cooldown_press_release);
+        match self.cps {
+            Some(cps) if cps > 0.0 => {
+                Duration::from_secs_f64(1.0 / cps).saturating_sub(press_release)
+            }
+            _ => Duration::from_millis(self.cooldown),
+        }
+    }
+}
+
+/// Resolves the keycode or exits if it is not a valid key
+pub fn key_from_code(code: u16) -> Key {
+    let Ok(key) = Key::from_code(code) else {
+        eprintln!("Invalid keycode: {code}");
+        std::process::exit(1);
+    };
+    key
+}
+
+/// Parses a keycode, or a key name like `KEY_F6` or `BTN_SIDE`
+pub fn parse_key(s: &str) -> Result<u16, String> {
+    let s = s.trim();
+    if let Ok(code) = s.parse() {
+        return Ok(code);
+    }
+    let upper = s.to_ascii_uppercase();
+    let name = if let Some(name) = upper.strip_prefix("KEY_") {
+        // The number row is Num1 to Num0
+        if name.bytes().all(|byte| byte.is_ascii_digit()) {
+            format!("NUM{name}")
+        } else {
+            name.to_string()
+        }
+    } else if let Some(name) = upper.strip_prefix("BTN_") {
+        // The face buttons of the gamepads are named after their position
+        let name = match name {
+            "A" | "GAMEPAD" => "SOUTH",
+            "B" => "EAST",
+            "X" => "NORTH",
+            "Y" => "WEST",
+            name => name,
+        };
+        format!("BUTTON{name}")
+    } else {
+        return Err(format!("{s:?} is not a keycode or a key name like KEY_F6 or BTN_SIDE"));
+    };
+    let name = name.replace('_', "");
+    Key::iter()
+        .find(|key| format!("{key:?}").to_ascii_uppercase() == name)
+        .map(|key| key as u16)
+        .ok_or_else(|| format!("Unknown key name {s:?}"))
+}
+
+/// The key name of a keycode, like `KEY_F6` or `BTN_SIDE`, the inverse of `parse_key`
+pub fn key_name(code: u16) -> Option<String> {
+    let key = format!("{:?}", Key::from_code(code).ok()?);
+    // The codes without a key are numbers
+    if key.starts_with("Unknown") {
+        return None;
+    }
+    let name = match key.strip_prefix("Button") {
+        Some(button) => format!("BTN_{}", button.to_ascii_uppercase()),
+        None => match key.strip_prefix("Num") {
+            // The number row is KEY_1 to KEY_0
+            Some(digits) if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => {
+                format!("KEY_{digits}")
+            }
+            _ => format!("KEY_{}", key.to_ascii_uppercase()),
+        },
+    };
+    (parse_key(&name) == Ok(code)).then_some(name)
+}
+
+/// Keycodes in the config, a number or a key name like `"KEY_F6"`
+mod key_code {
+    use serde::{Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum KeyValue {
+        Code(u16),
+        Name(String),
+    }
+
+    impl TryFrom<KeyValue> for u16 {
+        type Error = String;
+
+        fn try_from(value: KeyValue) -> Result<Self, Self::Error> {
+            match value {
+                KeyValue::Code(code) => Ok(code),
+                KeyValue::Name(name) => super::parse_key(&name),
+            }
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+        KeyValue::deserialize(deserializer)?
+            .try_into()
+            .map_err(serde::de::Error::custom)
+    }
+
+    /// A key the virtual device sends, refused when the kernel has no such key
+    pub fn sent<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+        let code = deserialize(deserializer)?;
+        match input_linux::Key::from_code(code) {
+            Ok(_) => Ok(code),
+            Err(_) => Err(serde::de::Error::custom(format!("invalid keycode {code}"))),
+        }
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<u16>, D::Error> {
+            Option::<KeyValue>::deserialize(deserializer)?
+                .map(u16::try_from)
+                .transpose()
+                .map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+fn output_key(code: Option<u16>, button: MouseButton) -> Key {
+    match code {
+        Some(code) => key_from_code(code),
+        None => button.key(),
+    }
+}
+
+impl std::fmt::Display for ClickOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.cps {
+            Some(cps) => write!(f, "--cps {cps}")?,
+            None => write!(f, "-c{}", self.cooldown)?,
+        }
+        write!(f, " -C{}", self.cooldown_press_release)?;
+        if let Some(cooldown) = self.cooldown_left {
+            write!(f, " --cooldown-left {cooldown}")?;
+        }
+        if let Some(cooldown) = self.cooldown_right {
+            write!(f, " --cooldown-right {cooldown}")?;
+        }
+        if self.cooldown_step != 5 {
+            write!(f, " --cooldown-step {}", self.cooldown_step)?;
+        }
+        if self.cooldown_jitter != 0 {
+            write!(f, " --cooldown-jitter {}", self.cooldown_jitter)?;
+        }
+        if self.press_jitter != 0 {
+            write!(f, " --press-jitter {}", self.press_jitter)?;
+        }
+        if self.humanize {
+            write!(
+                f,
+                " --humanize --humanize-stddev {} --humanize-pause-chance {} --humanize-pause {}",
+                self.humanize_stddev, self.humanize_pause_chance, self.humanize_pause
+            )?;
+        }
+        if self.drag_click {
+            write!(f, " --drag-click")?;
+        }
+        if self.left_button != MouseButton::Left {
+            write!(f, " --left-button {}", self.left_button)?;
+        }
+        if self.right_button != MouseButton::Right {
+            write!(f, " --right-button {}", self.right_button)?;
+        }
+        if let Some(key) = self.left_key {
+            write!(f, " --left-key {key}")?;
+        }
+        if let Some(key) = self.right_key {
+            write!(f, " --right-key {key}")?;
+        }
+        if let Some(text) = &self.left_text {
+            write!(f, " --left-text {text:?}")?;
+        }
+        if let Some(text) = &self.right_text {
+            write!(f, " --right-text {text:?}")?;
+        }
+        if self.left_text.is_some() || self.right_text.is_some() {
+            write!(f, " --type-delay {}", self.type_delay)?;
+        }
+        for (name, actions) in [
+            ("left", &self.left_actions),
+            ("right", &self.right_actions),
+        ] {
+            if !actions.is_empty() {
+                let actions: Vec<String> = actions.iter().map(|a| a.to_string()).collect();
+                write!(f, " --{name}-actions {}", actions.join(","))?;
+            }
+        }
+        for (name, path) in [("left", &self.left_drag), ("right", &self.right_drag)] {
+            for offset in path {
+                write!(f, " --{name}-drag {offset}")?;
+            }
+        }
+        if !(self.left_drag.is_empty() && self.right_drag.is_empty()) {
+            write!(
+                f,
+                " --drag-steps {} --drag-delay {}",
+                self.drag_steps, self.drag_delay
+            )?;
+        }
+        if let Some(notches) = self.left_scroll {
+            write!(f, " --left-scroll {notches}")?;
+        }
+        if let Some(notches) = self.right_scroll {
+            write!(f, " --right-scroll {notches}")?;
+        }
+        for modifier in &self.modifiers {
+            write!(f, " --modifier {modifier}")?;
+        }
+        for position in &self.positions {
+            write!(f, " --position {position}")?;
+        }
+        if self.position_jitter != 0 {
+            write!(f, " --position-jitter {}", self.position_jitter)?;
+        }
+        if let Some(screen) = self.screen {
+            write!(f, " --screen {screen}")?;
+        }
+        if let Some(jiggle) = self.jiggle {
+            write!(f, " --jiggle {jiggle} --jiggle-distance {}", self.jiggle_distance)?;
+            if let Some(idle) = self.jiggle_idle {
+                write!(f, " --jiggle-idle {idle}")?;
+            }
+        }
+        if self.click_mode != ClickMode::Single {
+            write!(
+                f,
+                " --click-mode {} --multi-click-gap {}",
+                self.click_mode, self.multi_click_gap
+            )?;
+        }
+        if self.miss_rate != 0.0 {
+            write!(f, " --miss-rate {}", self.miss_rate)?;
+        }
+        if let Some(max) = self.max_clicks {
+            write!(f, " --max-clicks {max}")?;
+            if self.max_clicks_exit {
+                write!(f, " --max-clicks-exit")?;
+            }
+        }
+        if let (Some(active), Some(rest)) = (self.duty_active, self.duty_rest) {
+            write!(f, " --active {active} --rest {rest}")?;
+        }
+        if let Some(run_for) = self.run_for {
+            write!(f, " --run-for {run_for}")?;
+        }
+        if let Some(path) = &self.left_macro {
+            write!(f, " --left-macro {}", path.display())?;
+        }
+        if let Some(path) = &self.right_macro {
+            write!(f, " --right-macro {}", path.display())?;
+        }
+        for rule in &self.window_rules {
+            write!(f, " --window-rule {rule}")?;
+        }
+        if self.session_pause {
+            write!(f, " --session-pause")?;
+            if self.session_deactivate {
+                write!(f, " --session-deactivate")?;
+            }
+        }
+        for device in &self.override_devices {
+            write!(f, " -o {device:?}")?;
+        }
+        if self.override_auto {
+            write!(f, " --override-auto")?;
+        }
+        for bind in &self.override_keys {
+            write!(f, " --override-key {bind}")?;
+        }
+        if self.override_any {
+            write!(f, " --override-any --override-quiet {}", self.override_quiet)?;
+        }
+        if self.override_invert {
+            write!(f, " --override-invert")?;
+        }
+        if self.override_lost != OverrideLost::Continue {
+            write!(f, " --override-lost {}", self.override_lost)?;
+        }
+        if self.override_grab {
+            write!(f, " --override-grab")?;
+        }
+        if let Some(delay) = self.override_resume_delay {
+            write!(f, " --override-resume-delay {delay}")?;
+        }
+        if self.override_resume_click {
+            write!(f, " --override-resume-click")?;
+        }
+        if self.gamepad {
+            write!(f, " --gamepad")?;
+        }
+        if self.device_name != "TheClicker" {
+            write!(f, " --device-name {:?}", self.device_name)?;
+        }
+        if self.device_bus != Bus::Usb {
+            write!(f, " --device-bus {}", self.device_bus)?;
+        }
+        if self.device_id != DeviceId::default() {
+            write!(f, " --device-id {}", self.device_id)?;
+        }
+        if self.split_devices {
+            write!(f, " --split-devices")?;
+        }
+        if let Some(led) = self.led {
+            write!(f, " --led {led} --led-shows {}", self.led_shows)?;
+        }
+        if self.haptic {
+            write!(f, " --haptic")?;
+        }
+        if self.dry_run {
+            write!(f, " --dry-run")?;
+        }
+        if self.anti_afk {
+            write!(
+                f,
+                " --anti-afk --anti-afk-interval {} --anti-afk-key {}",
+                self.anti_afk_interval, self.anti_afk_key
+            )?;
+        }
+        if let Some(path) = &self.script {
+            write!(f, " --script {} --script-tick {}", path.display(), self.script_tick)?;
+        }
+        for plugin in &self.plugins {
+            write!(f, " --plugin {plugin}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Virtual device created with /dev/uinput
+    #[default]
+    Uinput,
+    /// Virtual pointer and keyboard of a wlroots compositor
+    Wayland,
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Backend::Uinput => "uinput",
+            Backend::Wayland => "wayland",
+        })
+    }
+}
+
+/// Where the beep is played
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BeepSound {
+    /// The speaker when stdout is not a terminal, the terminal otherwise
+    #[default]
+    Auto,
+    /// The bell character written to the terminal
+    Terminal,
+    /// A tone of the PC speaker input device, like pcspkr
+    Speaker,
+}
+
+impl std::fmt::Display for BeepSound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BeepSound::Auto => "auto",
+            BeepSound::Terminal => "terminal",
+            BeepSound::Speaker => "speaker",
+        })
+    }
+}
+
+/// How a bind activates its autoclicker
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BindMode {
+    /// Every press turns it on or off
+    Toggle,
+    /// Active while the bind is held
+    Hold,
+}
+
+impl std::fmt::Display for BindMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BindMode::Toggle => "toggle",
+            BindMode::Hold => "hold",
+        })
+    }
+}
+
+/// Which autoclickers are active on launch
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StartActive {
+    Left,
+    Right,
+    Both,
+}
+
+impl StartActive {
+    /// If the left and right autoclickers start active
+    pub fn sides(self) -> [bool; 2] {
+        [
+            matches!(self, StartActive::Left | StartActive::Both),
+            matches!(self, StartActive::Right | StartActive::Both),
+        ]
+    }
+}
+
+impl std::fmt::Display for StartActive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            StartActive::Left => "left",
+            StartActive::Right => "right",
+            StartActive::Both => "both",
+        })
+    }
+}
+
+/// What the clicking does while an override device is gone
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverrideLost {
+    Continue,
+    Pause,
+}
+
+/// The keyboard LEDs that can show the state
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Led {
+    Scroll,
+    Caps,
+    Num,
+    Compose,
+    Kana,
+}
+
+impl Led {
+    pub fn kind(self) -> input_linux::LedKind {
+        match self {
+            Led::Scroll => input_linux::LedKind::ScrollLock,
+            Led::Caps => input_linux::LedKind::CapsLock,
+            Led::Num => input_linux::LedKind::NumLock,
+            Led::Compose => input_linux::LedKind::Compose,
+            Led::Kana => input_linux::LedKind::Kana,
+        }
+    }
+}
+
+impl std::fmt::Display for Led {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Led::Scroll => "scroll",
+            Led::Caps => "caps",
+            Led::Num => "num",
+            Led::Compose => "compose",
+            Led::Kana => "kana",
+        })
+    }
+}
+
+/// What the keyboard LED is lit for
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LedShows {
+    /// An autoclicker is active
+    Active,
+    /// The binds are locked
+    Locked,
+}
+
+impl std::fmt::Display for LedShows {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LedShows::Active => "active",
+            LedShows::Locked => "locked",
+        })
+    }
+}
+
+/// The bus the virtual device claims to be on
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Bus {
+    Usb,
+    Bluetooth,
+    /// The PS/2 port
+    I8042,
+    Virtual,
+}
+
+impl Bus {
+    pub fn code(self) -> u16 {
+        match self {
+            Bus::Usb => input_linux::sys::BUS_USB,
+            Bus::Bluetooth => input_linux::sys::BUS_BLUETOOTH,
+            Bus::I8042 => input_linux::sys::BUS_I8042,
+            Bus::Virtual => input_linux::sys::BUS_VIRTUAL,
+        }
+    }
+}
+
+impl std::fmt::Display for Bus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Bus::Usb => "usb",
+            Bus::Bluetooth => "bluetooth",
+            Bus::I8042 => "i8042",
+            Bus::Virtual => "virtual",
+        })
+    }
+}
+
+impl std::fmt::Display for OverrideLost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            OverrideLost::Continue => "continue",
+            OverrideLost::Pause => "pause",
+        })
+    }
+}
+
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ClickMode {
+    #[default]
+    Single,
+    Double,
+    Triple,
+    /// Press once and keep the button held until the autoclicker is deactivated
+    Hold,
+}
+
+impl ClickMode {
+    pub fn repeat(self) -> u8 {
+        match self {
+            ClickMode::Single | ClickMode::Hold => 1,
+            ClickMode::Double => 2,
+            ClickMode::Triple => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for ClickMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ClickMode::Single => "single",
+            ClickMode::Double => "double",
+            ClickMode::Triple => "triple",
+            ClickMode::Hold => "hold",
+        })
+    }
+}
+
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseButton {
+    #[default]
+    Left,
+    Right,
+    Middle,
+    Side,
+    Extra,
+}
+
+impl MouseButton {
+    pub fn key(self) -> Key {
+        match self {
+            MouseButton::Left => Key::ButtonLeft,
+            MouseButton::Right => Key::ButtonRight,
+            MouseButton::Middle => Key::ButtonMiddle,
+            MouseButton::Side => Key::ButtonSide,
+            MouseButton::Extra => Key::ButtonExtra,
+        }
+    }
+}
+
+impl std::fmt::Display for MouseButton {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MouseButton::Left => "left",
+            MouseButton::Right => "right",
+            MouseButton::Middle => "middle",
+            MouseButton::Side => "side",
+            MouseButton::Extra => "extra",
+        })
+    }
+}
+
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Modifier {
+    Shift,
+    Ctrl,
+    Alt,
+    Super,
+}
+
+impl Modifier {
+    pub fn key(self) -> Key {
+        match self {
+            Modifier::Shift => Key::LeftShift,
+            Modifier::Ctrl => Key::LeftCtrl,
+            Modifier::Alt => Key::LeftAlt,
+            Modifier::Super => Key::LeftMeta,
+        }
+    }
+}
+
+impl std::fmt::Display for Modifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Modifier::Shift => "shift",
+            Modifier::Ctrl => "ctrl",
+            Modifier::Alt => "alt",
+            Modifier::Super => "super",
+        })
+    }
+}
+
+/// A mouse button by name or a keycode
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum Output {
+    Button(MouseButton),
+    Key(#[serde(deserialize_with = "key_code::deserialize")] u16),
+}
+
+impl Output {
+    pub fn key(self) -> Key {
+        match self {
+            Output::Button(button) => button.key(),
+            Output::Key(code) => key_from_code(code),
+        }
+    }
+}
+
+impl std::str::FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use clap::ValueEnum;
+
+        if let Ok(button) = MouseButton::from_str(s, true) {
+            return Ok(Output::Button(button));
+        }
+        parse_key(s).map(Output::Key).map_err(|_| {
+            format!("{s:?} is not a button (left, right, middle, side, extra), a keycode or a key name")
+        })
+    }
+}
+
+impl std::fmt::Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Output::Button(button) => button.fmt(f),
+            Output::Key(code) => code.fmt(f),
+        }
+    }
+}
+
+/// A step of a click pattern, like `{"button": "left", "hold": 50}`, `{"key": 18, "hold": 30}` or `{"wait": 200}`
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Config {
-    pub debug: bool,
-    pub beep: bool,
-    pub command: ConfigCommand,
+#[serde(untagged)]
+pub enum PatternStep {
+    Click {
+        button: MouseButton,
+        /// Milliseconds between press and release
+        #[serde(default)]
+        hold: u64,
+    },
+    Key {
+        #[serde(deserialize_with = "key_code::sent")]
+        key: u16,
+        /// Milliseconds between press and release
+        #[serde(default)]
+        hold: u64,
+    },
+    Wait {
+        /// Milliseconds
+        wait: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Pause while the window is focused
+    Pause,
+    /// Only click while one of these windows is focused
+    Only,
+}
+
+/// Decides from the focused window if the autoclicker is paused, `pause:firefox` or
+/// `only:title=Minecraft` on the command line, the class and title are case insensitive substrings
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WindowRule {
+    pub action: RuleAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl std::str::FromStr for WindowRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("{s:?} is not a window rule, try `pause:firefox` or `only:title=Minecraft`");
+        let (action, matchers) = s.split_once(':').ok_or_else(error)?;
+        let mut rule = Self {
+            action: match action.trim() {
+                "pause" => RuleAction::Pause,
+                "only" => RuleAction::Only,
+                _ => return Err(error()),
+            },
+            class: None,
+            title: None,
+        };
+
+        for matcher in matchers.split(',') {
+            if let Some(title) = matcher.strip_prefix("title=") {
+                rule.title = Some(title.to_owned());
+            } else {
+                let class = matcher.strip_prefix("class=").unwrap_or(matcher);
+                rule.class = Some(class.to_owned());
+            }
+        }
+        if rule.class.as_ref().or(rule.title.as_ref()).is_none_or(String::is_empty) {
+            return Err(error());
+        }
+        Ok(rule)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(tag = "type")]
-pub enum ConfigCommand {
-    Run {
-        device_query: String,
-        left_bind: u16,
-        right_bind: u16,
-        lock_unlock_bind: Option<u16>,
-        hold: bool,
-        grab: bool,
-        cooldown: u64,
-        cooldown_press_release: u64,
+impl std::fmt::Display for WindowRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self.action {
+            RuleAction::Pause => "pause:",
+            RuleAction::Only => "only:",
+        })?;
+        match (&self.class, &self.title) {
+            (Some(class), Some(title)) => write!(f, "class={class},title={title}"),
+            (Some(class), None) => write!(f, "class={class}"),
+            (None, Some(title)) => write!(f, "title={title}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// A keycode, or keycodes pressed together like `29+64` for Ctrl+F6, the last one triggers the bind
+/// and the others have to be held, a number or a string in the config. `wheel-up` and
+/// `wheel-down` are a notch of the wheel, `gesture-left`, `-right`, `-up` and `-down` a sharp
+/// movement
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "BindValue", into = "BindValue")]
+pub struct Bind(pub Vec<u16>);
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum BindValue {
+    Key(u16),
+    Chord(String),
+}
+
+/// Not keycodes, a notch of the wheel up or down in a bind
+pub const WHEEL_UP: u16 = 0x300;
+pub const WHEEL_DOWN: u16 = 0x301;
+
+/// Not keycodes either, a sharp movement of the pointer in a bind
+pub const GESTURE_LEFT: u16 = 0x302;
+pub const GESTURE_RIGHT: u16 = 0x303;
+pub const GESTURE_UP: u16 = 0x304;
+pub const GESTURE_DOWN: u16 = 0x305;
+pub const GESTURES: [u16; 4] = [GESTURE_LEFT, GESTURE_RIGHT, GESTURE_UP, GESTURE_DOWN];
+
+/// The names of the binds that are not keycodes
+const BIND_NAMES: [(u16, &str); 6] = [
+    (WHEEL_UP, "wheel-up"),
+    (WHEEL_DOWN, "wheel-down"),
+    (GESTURE_LEFT, "gesture-left"),
+    (GESTURE_RIGHT, "gesture-right"),
+    (GESTURE_UP, "gesture-up"),
+    (GESTURE_DOWN, "gesture-down"),
+];
+
+impl Bind {
+    /// The key that triggers the bind
+    pub fn key(&self) -> u16 {
+        *self.0.last().unwrap()
+    }
+
+    /// If pressing `code` triggers the bind while the `held` keys are down
+    pub fn matches(&self, code: u16, held: &HashSet<u16>) -> bool {
+        let (key, modifiers) = self.0.split_last().unwrap();
+        *key == code && modifiers.iter().all(|modifier| held.contains(modifier))
+    }
+
+    /// The bind with the key names, like `KEY_LEFTCTRL+KEY_F6`, the keycodes without a name stay
+    /// numbers
+    pub fn names(&self) -> String {
+        let keys: Vec<String> = self
+            .0
+            .iter()
+            .map(|key| match BIND_NAMES.iter().find(|(code, _)| code == key) {
+                Some((_, name)) => name.to_string(),
+                None => key_name(*key).unwrap_or_else(|| key.to_string()),
+            })
+            .collect();
+        keys.join("+")
+    }
+}
+
+impl From<u16> for Bind {
+    fn from(key: u16) -> Self {
+        Self(vec![key])
+    }
+}
+
+impl std::str::FromStr for Bind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split('+')
+            .map(|key| match BIND_NAMES.iter().find(|(_, name)| *name == key.trim()) {
+                Some((code, _)) => Ok(*code),
+                None => parse_key(key),
+            })
+            .collect::<Result<Vec<u16>, _>>()
+            .map_err(|_| {
+                format!("{s:?} is not a bind, try `275`, `BTN_SIDE` or `29+64` for keys pressed together")
+            })?;
+        Ok(Self(keys))
+    }
+}
+
+impl std::fmt::Display for Bind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keys: Vec<String> = self
+            .0
+            .iter()
+            .map(|key| match BIND_NAMES.iter().find(|(code, _)| code == key) {
+                Some((_, name)) => name.to_string(),
+                None => key.to_string(),
+            })
+            .collect();
+        write!(f, "{}", keys.join("+"))
+    }
+}
+
+impl TryFrom<BindValue> for Bind {
+    type Error = String;
+
+    fn try_from(value: BindValue) -> Result<Self, Self::Error> {
+        match value {
+            BindValue::Key(key) => Ok(key.into()),
+            BindValue::Chord(chord) => chord.parse(),
+        }
+    }
+}
+
+/// The key names are written in the configs, they are easier to read and edit than the keycodes
+impl From<Bind> for BindValue {
+    fn from(bind: Bind) -> Self {
+        match bind.0[..] {
+            [key] if key_name(key).is_none() && key < WHEEL_UP => BindValue::Key(key),
+            _ => BindValue::Chord(bind.names()),
+        }
+    }
+}
+
+/// What a key from `--bind` does
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BindingAction {
+    /// Toggles or holds the left autoclicker
+    Left,
+    /// Toggles or holds the right autoclicker
+    Right,
+    /// Locks and unlocks the other binds
+    Lock,
+    /// The other binds only work while it is held
+    Enable,
+    /// Switches to the next profile
+    Profile,
+    /// Shortens the cooldown
+    Faster,
+    /// Lengthens the cooldown
+    Slower,
+    /// Plays a macro
+    Macro {
+        path: PathBuf,
+        #[serde(default = "MacroBind::default_times")]
+        times: u32,
+        #[serde(default)]
+        while_held: bool,
     },
-    RunLegacy {
-        device_query: String,
-        cooldown: u64,
-        cooldown_press_release: u64,
+    /// Clicks once after a delay
+    React {
+        delay: HumanDuration,
+        #[serde(default)]
+        jitter: Option<HumanDuration>,
     },
+    /// Clicks once for every press, without the autoclickers
+    Click {
+        #[serde(default)]
+        button: MouseButton,
+    },
+    /// Clicks a number of times for every press, with the interval in between
+    Burst {
+        clicks: u32,
+        interval: HumanDuration,
+        #[serde(default)]
+        button: MouseButton,
+    },
+}
+
+/// A key and its action, `KEYCODE:ACTION` on the command line, with `:release` at the end
+/// to fire it when the key is released
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub key: Bind,
+    #[serde(flatten)]
+    pub action: BindingAction,
+    /// Fire the action once when the key is released instead of when it is pressed
+    #[serde(default)]
+    pub on_release: bool,
+}
+
+impl Binding {
+    pub fn new(key: Bind, action: BindingAction) -> Self {
+        Self {
+            key,
+            action,
+            on_release: false,
+        }
+    }
+}
+
+impl std::str::FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("{s:?} is not a bind, try `275:left` or `275:macro:macro.json`");
+        let (s, on_release) = match s.strip_suffix(":release") {
+            Some(s) => (s, true),
+            None => (s, false),
+        };
+        let (key, rest) = s.split_once(':').ok_or_else(error)?;
+        let (action, args) = rest.split_once(':').unwrap_or((rest, ""));
+        let mut args = args.split(':').filter(|arg| !arg.is_empty());
+
+        let action = match action.trim() {
+            "left" => BindingAction::Left,
+            "right" => BindingAction::Right,
+            "lock" => BindingAction::Lock,
+            "enable" => BindingAction::Enable,
+            "profile" => BindingAction::Profile,
+            "faster" => BindingAction::Faster,
+            "slower" => BindingAction::Slower,
+            "macro" => {
+                let path = PathBuf::from(args.next().ok_or_else(error)?);
+                let (times, while_held) = match args.next() {
+                    None => (1, false),
+                    Some("held") => (1, true),
+                    Some(times) => (times.parse().map_err(|_| error())?, false),
+                };
+                BindingAction::Macro {
+                    path,
+                    times,
+                    while_held,
+                }
+            }
+            "react" => BindingAction::React {
+                delay: args.next().ok_or_else(error)?.parse()?,
+                jitter: args.next().map(str::parse).transpose()?,
+            },
+            "click" => BindingAction::Click {
+                button: parse_button(args.next())?,
+            },
+            "burst" => BindingAction::Burst {
+                clicks: args.next().ok_or_else(error)?.parse().map_err(|_| error())?,
+                interval: args.next().ok_or_else(error)?.parse()?,
+                button: parse_button(args.next())?,
+            },
+            action => {
+                return Err(format!(
+                    "Unknown bind action {action:?}, use left, right, lock, enable, profile, faster, slower, macro, react, click or burst"
+                ))
+            }
+        };
+        if args.next().is_some() {
+            return Err(error());
+        }
+        Ok(Self {
+            key: key.parse()?,
+            action,
+            on_release,
+        })
+    }
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:", self.key)?;
+        match &self.action {
+            BindingAction::Left => write!(f, "left"),
+            BindingAction::Right => write!(f, "right"),
+            BindingAction::Lock => write!(f, "lock"),
+            BindingAction::Enable => write!(f, "enable"),
+            BindingAction::Profile => write!(f, "profile"),
+            BindingAction::Faster => write!(f, "faster"),
+            BindingAction::Slower => write!(f, "slower"),
+            BindingAction::Macro {
+                path,
+                times,
+                while_held,
+            } => {
+                write!(f, "macro:{}", path.display())?;
+                if *while_held {
+                    write!(f, ":held")
+                } else if *times != 1 {
+                    write!(f, ":{times}")
+                } else {
+                    Ok(())
+                }
+            }
+            BindingAction::React { delay, jitter } => {
+                write!(f, "react:{delay}")?;
+                if let Some(jitter) = jitter {
+                    write!(f, ":{jitter}")?;
+                }
+                Ok(())
+            }
+            BindingAction::Click { button } => {
+                write!(f, "click")?;
+                if *button != MouseButton::Left {
+                    write!(f, ":{button}")?;
+                }
+                Ok(())
+            }
+            BindingAction::Burst {
+                clicks,
+                interval,
+                button,
+            } => {
+                write!(f, "burst:{clicks}:{interval}")?;
+                if *button != MouseButton::Left {
+                    write!(f, ":{button}")?;
+                }
+                Ok(())
+            }
+        }?;
+        if self.on_release {
+            write!(f, ":release")?;
+        }
+        Ok(())
+    }
+}
+
+/// The optional button at the end of the click binds, the left one by default
+fn parse_button(button: Option<&str>) -> Result<MouseButton, String> {
+    button
+        .map(|button| clap::ValueEnum::from_str(button, true))
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+impl From<&MacroBind> for Binding {
+    fn from(bind: &MacroBind) -> Self {
+        Self::new(
+            bind.key.into(),
+            BindingAction::Macro {
+                path: bind.path.clone(),
+                times: bind.times,
+                while_held: bind.while_held,
+            },
+        )
+    }
+}
+
+impl From<&ReactBind> for Binding {
+    fn from(bind: &ReactBind) -> Self {
+        Self::new(
+            bind.key.into(),
+            BindingAction::React {
+                delay: bind.delay,
+                jitter: bind.jitter,
+            },
+        )
+    }
+}
+
+/// Plays a macro when the key is pressed, `KEYCODE:FILE`, `KEYCODE:FILE:3` or `KEYCODE:FILE:held`
+/// on the command line
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MacroBind {
+    #[serde(deserialize_with = "key_code::deserialize")]
+    pub key: u16,
+    pub path: PathBuf,
+    /// How many times the macro is played for every press
+    #[serde(default = "MacroBind::default_times")]
+    pub times: u32,
+    /// Loop the macro while the key is held instead
+    #[serde(default)]
+    pub while_held: bool,
+}
+
+impl MacroBind {
+    fn default_times() -> u32 {
+        1
+    }
+}
+
+impl std::str::FromStr for MacroBind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("{s:?} is not a macro bind, try `275:macro.json` or `275:macro.json:held`");
+        let mut parts = s.splitn(3, ':');
+        let key = parse_key(parts.next().unwrap()).map_err(|_| error())?;
+        let path = parts.next().filter(|path| !path.is_empty()).ok_or_else(error)?;
+
+        let mut bind = Self {
+            key,
+            path: PathBuf::from(path),
+            times: 1,
+            while_held: false,
+        };
+        match parts.next().map(str::trim) {
+            None => {}
+            Some("held") => bind.while_held = true,
+            Some(times) => bind.times = times.parse().map_err(|_| error())?,
+        }
+        Ok(bind)
+    }
+}
+
+impl std::fmt::Display for MacroBind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.key, self.path.display())?;
+        if self.while_held {
+            write!(f, ":held")
+        } else if self.times != 1 {
+            write!(f, ":{}", self.times)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Clicks once after a delay when the key is pressed, `KEYCODE:DELAY` or `KEYCODE:DELAY:JITTER`
+/// on the command line
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReactBind {
+    #[serde(deserialize_with = "key_code::deserialize")]
+    pub key: u16,
+    pub delay: HumanDuration,
+    /// The delay is randomized uniformly in `delay ± jitter`
+    #[serde(default)]
+    pub jitter: Option<HumanDuration>,
+}
+
+impl std::str::FromStr for ReactBind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("{s:?} is not a reaction, try `57:150ms` or `57:150ms:20ms`");
+        let mut parts = s.splitn(3, ':');
+        let key = parse_key(parts.next().unwrap()).map_err(|_| error())?;
+        let delay = parts.next().ok_or_else(error)?.parse()?;
+        let jitter = parts.next().map(str::parse).transpose()?;
+        Ok(Self { key, delay, jitter })
+    }
+}
+
+impl std::fmt::Display for ReactBind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.key, self.delay)?;
+        if let Some(jitter) = self.jitter {
+            write!(f, ":{jitter}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A position on the screen in pixels, `X,Y` on the command line and `[X, Y]` in the config
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Point(pub i32, pub i32);
+
+impl std::str::FromStr for Point {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("{s:?} is not a position, try `960,540`");
+        let (x, y) = s.split_once(',').ok_or_else(error)?;
+        Ok(Self(
+            x.trim().parse().map_err(|_| error())?,
+            y.trim().parse().map_err(|_| error())?,
+        ))
+    }
+}
+
+impl std::fmt::Display for Point {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.0, self.1)
+    }
 }
 
-#[derive(clap::Subcommand, Debug)]
+/// Screen size in pixels, `WIDTHxHEIGHT`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct ScreenSize {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl std::str::FromStr for ScreenSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("{s:?} is not a screen size, try `1920x1080`");
+        let (width, height) = s.split_once('x').ok_or_else(error)?;
+        let size = Self {
+            width: width.trim().parse().map_err(|_| error())?,
+            height: height.trim().parse().map_err(|_| error())?,
+        };
+        if size.width <= 0 || size.height <= 0 {
+            return Err(error());
+        }
+        Ok(size)
+    }
+}
+
+impl std::fmt::Display for ScreenSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl TryFrom<String> for ScreenSize {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ScreenSize> for String {
+    fn from(value: ScreenSize) -> Self {
+        value.to_string()
+    }
+}
+
+/// Ids of the virtual device, `VENDOR:PRODUCT[:VERSION]` in hex
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct DeviceId {
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+}
+
+impl Default for DeviceId {
+    fn default() -> Self {
+        Self {
+            vendor: 0x3232,
+            product: 0x5678,
+            version: 0x1234,
+        }
+    }
+}
+
+impl std::str::FromStr for DeviceId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("{s:?} is not a device id, try `046d:c08b` or `046d:c08b:0111`");
+        let hex = |id: &str| u16::from_str_radix(id.trim(), 16).map_err(|_| err());
+        let mut ids = s.split(':');
+        let (Some(vendor), Some(product)) = (ids.next(), ids.next()) else {
+            return Err(err());
+        };
+        let version = ids.next().map_or(Ok(1), hex)?;
+        if ids.next().is_some() {
+            return Err(err());
+        }
+        Ok(Self {
+            vendor: hex(vendor)?,
+            product: hex(product)?,
+            version,
+        })
+    }
+}
+
+impl std::fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04x}:{:04x}:{:04x}", self.vendor, self.product, self.version)
+    }
+}
+
+impl TryFrom<String> for DeviceId {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<DeviceId> for String {
+    fn from(value: DeviceId) -> Self {
+        value.to_string()
+    }
+}
+
+/// Duration written with a unit: `500ms`, `30s`, `10m`, `1h` (plain numbers are seconds)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct HumanDuration(pub Duration);
+
+impl std::str::FromStr for HumanDuration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+        let value: f64 = value
+            .parse()
+            .map_err(|_| format!("{s:?} is not a duration, try `30s` or `10m`"))?;
+        let secs = match unit.trim() {
+            "ms" => value / 1000.0,
+            "" | "s" => value,
+            "m" => value * 60.0,
+            "h" => value * 3600.0,
+            unit => return Err(format!("Unknown duration unit {unit:?}, use ms, s, m or h")),
+        };
+        Ok(Self(Duration::from_secs_f64(secs)))
+    }
+}
+
+impl std::fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let millis = self.0.as_millis();
+        if millis.is_multiple_of(3_600_000) && millis != 0 {
+            write!(f, "{}h", millis / 3_600_000)
+        } else if millis.is_multiple_of(60_000) && millis != 0 {
+            write!(f, "{}m", millis / 60_000)
+        } else if millis.is_multiple_of(1000) {
+            write!(f, "{}s", millis / 1000)
+        } else {
+            write!(f, "{millis}ms")
+        }
+    }
+}
+
+impl TryFrom<String> for HumanDuration {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<HumanDuration> for String {
+    fn from(value: HumanDuration) -> Self {
+        value.to_string()
+    }
+}
+
+// Only parsed once, the run options do not need to be boxed
+#[allow(clippy::large_enum_variant)]
+#[derive(clap::Subcommand, Debug, Clone)]
 pub enum Command {
     Run {
-        /// Device name or path when the first character is `/`
-        /// (first looks for exact match, then takes the first device that contains the name)
-        #[arg(short = 'd')]
+        /// Device name, path when the first character is `/`, USB ids like `046d:c08b`, a
+        /// pattern like `re:Logitech.*Receiver` or `auto` for the first mouse
+        /// (first looks for exact match, then takes the first device that contains the name),
+        /// without it the first `--profile` is run
+        #[arg(short = 'd', env = "THECLICKER_DEVICE", default_value = "", hide_default_value = true)]
         device_query: String,
 
-        /// Bind left autoclicker to keycode
+        /// Bind left autoclicker to keycode or key name like `BTN_SIDE`, or keys pressed together like
+        /// `29+64` for Ctrl+F6, `wheel-up` and `wheel-down` are a notch of the wheel, `gesture-right`
+        /// a sharp movement
         /// Mouse: 275 ButtonSide
         /// Keyboard: 26 LeftBrace
         #[arg(short)]
-        left_bind: u16,
+        left_bind: Option<Bind>,
 
-        /// Bind right autoclicker to keycode
+        /// Bind right autoclicker to keycode, or keycodes pressed together like `275+276`
         /// Mouse: 276 ButtonExtra
         /// Keyboard: 27 RightBrace
         #[arg(short)]
-        right_bind: u16,
+        right_bind: Option<Bind>,
 
         /// Bind lock/unlock to keycode
         /// Mouse: 274 ButtonMiddle
         /// With this you can bind to the lefr and right button, and the bindings will be used when is unlocked.
         /// Useful for mouses without side buttons.
         #[arg(short = 'T')]
-        lock_unlock_bind: Option<u16>,
+        lock_unlock_bind: Option<Bind>,
 
         /// Hold mode, when a keybind is pressed the autoclicker will be active until the keybind release
         #[arg(short = 'H', default_value_t = false)]
         hold: bool,
 
-        /// This will grab the device,
+        /// This will grab the device, and relay everything except the bind keys, also on keyboards
         #[arg(long, default_value_t = false)]
         grab: bool,
 
-        /// Set the cooldown in milliseconds
-        #[arg(short, default_value_t = 25)]
-        cooldown: u64,
+        /// Play a macro when the key is pressed, `KEYCODE:FILE` once, `KEYCODE:FILE:3` three times
+        /// or `KEYCODE:FILE:held` in a loop while the key is held
+        #[arg(long = "macro-bind")]
+        macro_binds: Vec<MacroBind>,
+
+        /// Click once with the left autoclicker button after a delay when the key is pressed,
+        /// `KEYCODE:DELAY` or `KEYCODE:DELAY:JITTER` like `57:150ms:20ms`, the key is not consumed
+        #[arg(long = "react")]
+        react_binds: Vec<ReactBind>,
+
+        /// Write every event read from the device to this file in the `evemu-record` format,
+        /// it can be played back with `replay`
+        #[arg(long)]
+        record_raw: Option<PathBuf>,
+
+        /// Multiply every physical left and right click into this many clicks, spaced by the cooldown,
+        /// instead of clicking on its own
+        #[arg(long, requires = "grab")]
+        multiply: Option<u32>,
+
+        /// Only toggle the left and right autoclickers when their bind is tapped twice within
+        /// this many milliseconds, the single taps go through
+        #[arg(long)]
+        double_tap: Option<u64>,
+
+        /// Only trigger the left and right binds when they are held for more than this many
+        /// milliseconds, the shorter presses go through
+        #[arg(long)]
+        long_press: Option<u64>,
 
-        /// Set cooldown in milliseconds, between press and release
-        #[arg(short = 'C', default_value_t = 0)]
-        cooldown_press_release: u64,
+        /// Toggle or hold mode for the left bind only, instead of `-H`
+        #[arg(long, value_enum)]
+        left_mode: Option<BindMode>,
+
+        /// Toggle or hold mode for the right bind only, instead of `-H`
+        #[arg(long, value_enum)]
+        right_mode: Option<BindMode>,
+
+        /// Bind that shortens the cooldown by `--cooldown-step` while running
+        #[arg(long)]
+        faster_bind: Option<Bind>,
+
+        /// Bind that lengthens the cooldown by `--cooldown-step` while running
+        #[arg(long)]
+        slower_bind: Option<Bind>,
+
+        /// Bind a key to an action, `KEYCODE:ACTION` like `275:left`, `29+64:lock`, `78:faster`,
+        /// `275:macro:FILE`, `275:macro:FILE:3`, `275:macro:FILE:held`, `57:react:150ms:20ms`,
+        /// `275:click:right`, `275:burst:5:50ms` or `29:enable`,
+        /// with `:release` at the end to fire it when the key is released like `275:left:release`
+        #[arg(long = "bind")]
+        binds: Vec<Binding>,
+
+        /// The other binds only work while this bind is held, instead of the lock toggle
+        #[arg(long)]
+        enable_bind: Option<Bind>,
+
+        /// Named profile of the config, or config file, with a `Run` command to switch to with the
+        /// profile bind, can be repeated, the command line options are the first profile
+        #[arg(long = "profile")]
+        profiles: Vec<PathBuf>,
+
+        /// Bind that switches to the next profile
+        #[arg(long)]
+        profile_bind: Option<Bind>,
+
+        /// Bind that releases the virtual buttons, gives the device back and exits right away,
+        /// even when the binds are locked
+        #[arg(long)]
+        panic_bind: Option<Bind>,
+
+        /// Read the binds from this device too, like a keyboard while grabbing only the mouse,
+        /// its events are never relayed
+        #[arg(long)]
+        bind_device: Option<String>,
+
+        /// Start clicking on launch without a bind press, for headless and scripted runs
+        #[arg(long, value_enum)]
+        start_active: Option<StartActive>,
+
+        /// Wait this long with a countdown before the binds work, like `5s`, to focus the target
+        /// window, pressing a bind starts right away
+        #[arg(long)]
+        start_delay: Option<HumanDuration>,
+
+        /// Also pause while an override key is held on the input device itself, so one keyboard
+        /// can host both the binds and the override keys
+        #[arg(long, default_value_t = false, requires = "override_keys")]
+        override_main: bool,
+
+        /// Another input device read like the main one, like a second mouse, can be repeated
+        #[arg(long = "extra-device")]
+        extra_devices: Vec<String>,
+
+        /// Do not grab the devices again when they come back, like a Bluetooth mouse that woke up
+        #[arg(long, default_value_t = false, requires = "grab")]
+        no_regrab: bool,
+
+        /// Unix socket that switches the profiles, a `profile NAME` or `profile next` line
+        /// written to it, like with `echo profile work | socat - UNIX-CONNECT:PATH`
+        #[arg(long)]
+        control: Option<PathBuf>,
+
+        #[command(flatten)]
+        click: ClickOptions,
     },
     RunLegacy {
-        /// Device name or path when the first character is `/`
+        /// Device name, path when the first character is `/`, USB ids like `046d:c08b`, a
+        /// pattern like `re:Logitech.*Receiver` or `auto` for the first mouse
         /// (first looks for exact match, then takes the first device that contains the name)
-        #[arg(short = 'd')]
+        #[arg(short = 'd', env = "THECLICKER_DEVICE")]
         device_query: String,
 
-        /// Set the cooldown in milliseconds
-        #[arg(short, default_value_t = 25)]
-        cooldown: u64,
+        #[command(flatten)]
+        click: ClickOptions,
+    },
+    /// Record the events of a device into a macro, stop with Ctrl+C or the stop key
+    Record {
+        /// Device name, path when the first character is `/`, USB ids like `046d:c08b`, a
+        /// pattern like `re:Logitech.*Receiver` or `auto` for the first mouse
+        /// (first looks for exact match, then takes the first device that contains the name)
+        #[arg(short = 'd', env = "THECLICKER_DEVICE")]
+        device_query: String,
+
+        /// File the macro is saved to
+        #[arg(short, long, default_value = "macro.json")]
+        output: PathBuf,
+
+        /// Keycode or key name that stops the recording, it is not recorded
+        #[arg(long, value_parser = parse_key)]
+        stop_key: Option<u16>,
+    },
+    /// Play back a recording from `run --record-raw` or `evemu-record` with its original timing
+    Replay {
+        /// The recording
+        recording: PathBuf,
+    },
+    /// Convert an `evemu-record` recording into a macro
+    Import {
+        /// The recording, from `evemu-record`
+        recording: PathBuf,
+
+        /// File the macro is saved to
+        #[arg(short, long, default_value = "macro.json")]
+        output: PathBuf,
+    },
+    /// Work with config files without running them
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Run the profiles of the config that have a `for_device` while their device is plugged in,
+    /// each in its own process
+    Daemon,
+}
 
-        /// Set cooldown in milliseconds, between press and release
-        #[arg(short = 'C', default_value_t = 0)]
-        cooldown_press_release: u64,
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum ConfigAction {
+    /// Check the config, its devices and the keys of its binds, and report every problem
+    Validate {
+        /// The config, JSON or TOML
+        file: PathBuf,
+    },
+    /// Run the interactive setup and save it in a config, without starting the clicker
+    Generate {
+        /// The config, JSON or TOML, the default config when not given
+        path: Option<PathBuf>,
+    },
+    /// Write the JSON Schema of the config, for the completion and the checks of the editors
+    Schema {
+        /// File the schema is saved to
+        #[arg(short, long, default_value = "config.schema.json")]
+        output: PathBuf,
     },
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    #[arg(long, default_value_t = false)]
+    #[arg(long, env = "THECLICKER_DEBUG", value_parser = clap::builder::FalseyValueParser::new())]
     pub debug: bool,
 
     /// For not beeping when the autoclicker state is changed
     #[arg(long, default_value_t = false)]
     pub beep: bool,
 
-    /// Load configuration from JSON file
+    /// Where the beep is played, `speaker` works without a terminal, like when running as a service
+    #[arg(long, value_enum)]
+    pub beep_sound: Option<BeepSound>,
+
+    /// Where the clicks are sent, `wayland` works without /dev/uinput on wlroots compositors
+    #[arg(long, value_enum)]
+    pub backend: Option<Backend>,
+
+    /// Load configuration from a JSON file, or a TOML one ending with `.toml`, also taken from
+    /// `THECLICKER_CONFIG`
     #[arg(short, long)]
     pub config: Option<PathBuf>,
 
-    /// Load default config.json from current directory
+    /// Load the default config, `$XDG_CONFIG_HOME/theclicker/config.toml` or `config.json`, or
+    /// `config.json` from the current directory when there is none, the default config is also
+    /// loaded without this when there is no `--config`
     #[arg(short, long, default_value_t = false)]
     pub default: bool,
 
+    /// Do not load the default config
+    #[arg(long, default_value_t = false, conflicts_with_all = ["config", "default"])]
+    pub no_config: bool,
+
+    /// Save the settings in a config, JSON or TOML by the extension, with the command of the
+    /// command line or of the interactive setup, then run it
+    #[arg(long)]
+    pub save_config: Option<PathBuf>,
+
+    /// Print the settings as a JSON config, after the config, the environment and the command
+    /// line are merged, without running them
+    #[arg(long, default_value_t = false)]
+    pub export_effective_config: bool,
+
+    /// Keys that cannot be chosen as binds in the interactive setup, Ctrl and C by default
+    #[arg(long, value_parser = parse_key, default_values_t = [29, 46])]
+    pub blacklist: Vec<u16>,
+
+    /// Allow every key in the interactive setup, for binding keys next to Ctrl on purpose
+    #[arg(long, default_value_t = false)]
+    pub no_blacklist: bool,
+
     #[command(subcommand)]
     pub command: Option<Command>,
+
+    /// The named profiles of the loaded config
+    #[arg(skip)]
+    pub named_profiles: BTreeMap<String, ConfigCommand>,
 }
 
 impl Args {
-    pub fn load_from_config_or_default(mut self) -> Result<Self, Box<dyn std::error::Error>> {
-        let config_path = if self.default {
-            Some(PathBuf::from("config.json"))
-        } else {
+    /// Fills what the command line leaves out from the config, `matches` are the ones `self` is
+    /// parsed from, they tell the options given over a profile
+    pub fn load_from_config_or_default(
+        mut self,
+        matches: &ArgMatches,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let config_path = if self.no_config {
+            None
+        } else if self.config.is_some() {
             self.config.clone()
+        } else if self.default {
+            // The config.json of the current directory, from before the config directory
+            Some(Config::find_default().unwrap_or_else(|| PathBuf::from("config.json")))
+        } else if let Some(path) =
+            std::env::var_os("THECLICKER_CONFIG").filter(|path| !path.is_empty())
+        {
+            Some(PathBuf::from(path))
+        } else {
+            let path = Config::find_default();
+            if let Some(path) = path.as_ref().filter(|_| !self.export_effective_config) {
+                println!("Using the config {}", path.display());
+            }
+            path
         };
 
+        // The command of the config takes the environment variables after its profile
+        let mut from_config = false;
         if let Some(config_path) = config_path {
-            let config_content = std::fs::read_to_string(&config_path)?;
-            let config: Config = serde_json::from_str(&config_content)?;
+            let config = Config::load(&config_path)?;
+            // The daemon runs the profiles with it
+            self.config = Some(config_path);
             
-            // Override with config values if not set via CLI
-            if !self.debug {
+            // Override with config values if not set via CLI or the environment
+            if !self.debug && std::env::var_os("THECLICKER_DEBUG").is_none() {
                 self.debug = config.debug;
             }
             if !self.beep {
                 self.beep = config.beep;
             }
+            if self.beep_sound.is_none() {
+                self.beep_sound = Some(config.beep_sound);
+            }
+            if self.backend.is_none() {
+                self.backend = Some(config.backend);
+            }
             if self.command.is_none() {
                 self.command = Some(config.command.into());
+                from_config = true;
+            }
+            self.named_profiles = config.profiles;
+        }
+
+        // Without a device the first profile is run, the others stay profiles to switch to, with
+        // the options given on the command line over it
+        let given = if from_config { None } else { self.command.clone() };
+        if let Some(Command::Run {
+            device_query,
+            profiles,
+            ..
+        }) = &mut self.command
+        {
+            if device_query.is_empty() {
+                if profiles.is_empty() {
+                    return Err("`run` needs a device with `-d`, or a profile with `--profile`".into());
+                }
+                let first = profiles.remove(0);
+                let command = match self.named_profiles.get(&*first.to_string_lossy()) {
+                    Some(command) => command.clone(),
+                    None if first.is_file() => Config::load(&first)?.command,
+                    None => return Err(format!("There is no profile named {first:?}").into()),
+                };
+                let mut command = match &given {
+                    Some(run) => given_over(command, run, matches, &first.to_string_lossy())?,
+                    None => Command::from(command),
+                };
+                if let Command::Run {
+                    profiles: own, ..
+                } = &mut command
+                {
+                    own.append(profiles);
+                }
+                self.command = Some(command);
+            }
+        }
+        if from_config {
+            if let Some(command) = &mut self.command {
+                env_overrides(command)?;
             }
         }
         Ok(self)
     }
 }
 
-impl From<ConfigCommand> for Command {
-    fn from(config_cmd: ConfigCommand) -> Self {
-        match config_cmd {
-            ConfigCommand::Run {
-                device_query,
-                left_bind,
-                right_bind,
-                lock_unlock_bind,
-                hold,
-                grab,
-                cooldown,
-                cooldown_press_release,
-            } => Command::Run {
-                device_query,
-                left_bind,
-                right_bind,
-                lock_unlock_bind,
-                hold,
-                grab,
-                cooldown,
-                cooldown_press_release,
-            },
-            ConfigCommand::RunLegacy {
-                device_query,
-                cooldown,
-                cooldown_press_release,
-            } => Command::RunLegacy {
-                device_query,
-                cooldown,
-                cooldown_press_release,
-            },
+/// The options of `run` given on the command line or by their environment variable over the
+/// profile it runs, the others are the ones of the profile
+fn given_over(
+    profile: ConfigCommand,
+    run: &Command,
+    matches: &ArgMatches,
+    name: &str,
+) -> Result<Command, String> {
+    let Some(("run", matches)) = matches.subcommand() else {
+        return Ok(profile.into());
+    };
+    let given = serde_json::to_value(ConfigCommand::try_from(run.clone())?)
+        .map_err(|err| err.to_string())?;
+    let mut layered = serde_json::to_value(profile).map_err(|err| err.to_string())?;
+    for id in matches.ids() {
+        let id = id.as_str();
+        // The profiles to switch to are added to the ones of the profile
+        if id == "profiles"
+            || !matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        {
+            continue;
         }
+        // The groups of options, like the flattened `ClickOptions`
+        let Some(value) = given.get(id) else {
+            continue;
+        };
+        let Some(slot) = layered.get_mut(id) else {
+            return Err(format!(
+                "The profile {name} is a `run-legacy` command, `{id}` cannot be given with it"
+            ));
+        };
+        *slot = value.clone();
     }
+    let layered: ConfigCommand = serde_json::from_value(layered).map_err(|err| err.to_string())?;
+    Ok(layered.into())
 }
 
-impl Config {
-    pub fn save_to_file(&self, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-        let json = serde_json::to_string_pretty(self)?;
-        std::fs::write(path, json)?;
-        Ok(())
+/// The `THECLICKER_DEVICE` and `THECLICKER_COOLDOWN` environment variables over the command of
+/// the config, after its profile, the command line takes them like its options
+fn env_overrides(command: &mut Command) -> Result<(), String> {
+    let (device_query, click) = match command {
+        Command::Run {
+            device_query,
+            click,
+            ..
+        }
+        | Command::RunLegacy {
+            device_query,
+            click,
+        } => (device_query, click),
+        _ => return Ok(()),
+    };
+    if let Ok(device) = std::env::var("THECLICKER_DEVICE") {
+        if !device.is_empty() {
+            *device_query = device;
+        }
+    }
+    if let Ok(cooldown) = std::env::var("THECLICKER_COOLDOWN") {
+        click.cooldown = cooldown
+            .trim()
+            .parse()
+            .map_err(|_| format!("THECLICKER_COOLDOWN={cooldown:?} is not a number of milliseconds"))?;
+    }
+    Ok(())
+}
+
+impl Command {
+    /// The binds of the `Run` command, the shorthand options are binds too, with the lock last
+    pub fn bindings(&self) -> Vec<Binding> {
+        let Command::Run {
+            left_bind,
+            right_bind,
+            lock_unlock_bind,
+            macro_binds,
+            react_binds,
+            faster_bind,
+            slower_bind,
+            binds,
+            enable_bind,
+            profile_bind,
+            ..
+        } = self
+        else {
+            return Vec::new();
+        };
+
+        let mut bindings = Vec::new();
+        let shorthands = [
+            (left_bind, BindingAction::Left),
+            (right_bind, BindingAction::Right),
+            (faster_bind, BindingAction::Faster),
+            (slower_bind, BindingAction::Slower),
+            (profile_bind, BindingAction::Profile),
+        ];
+        for (key, action) in shorthands {
+            bindings.extend(key.clone().map(|key| Binding::new(key, action)));
+        }
+        bindings.extend(macro_binds.iter().map(Binding::from));
+        bindings.extend(react_binds.iter().map(Binding::from));
+        bindings.extend(binds.iter().cloned());
+        bindings.extend(lock_unlock_bind.clone().map(|key| Binding::new(key, BindingAction::Lock)));
+        bindings.extend(enable_bind.clone().map(|key| Binding::new(key, BindingAction::Enable)));
+        bindings
     }
 }
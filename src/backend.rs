@@ -0,0 +1,28 @@
+use std::io;
+
+use input_linux::{sys::input_event, Key, KeyState, RelativeAxis};
+
+use crate::PauseSource;
+
+/// Where the clicks are sent, the uinput virtual device or a Wayland compositor
+pub trait OutputBackend: Send + Sync {
+    /// Relays raw events from the grabbed input device, `Ok(0)` when they were dropped
+    fn write(&self, events: &[input_event]) -> io::Result<usize>;
+
+    fn send_key(&self, key: Key, state: KeyState);
+
+    /// Sends the relative axes in one frame
+    fn send_relative(&self, axes: &[(RelativeAxis, i32)]);
+
+    /// Moves the pointer to the position on the screen
+    fn send_position(&self, x: i32, y: i32);
+
+    /// Scrolls the wheel by `notches`, positive is up
+    fn send_scroll(&self, notches: i32);
+
+    /// Removes the virtual device before exiting, the kernel releases what it still holds
+    fn destroy(&self) {}
+
+    /// Pauses the clicking while the backend is down, shown on the status line
+    fn watch(&self, _pause: PauseSource) {}
+}
@@ -0,0 +1,88 @@
+use std::{io::IsTerminal, sync::Arc, thread, time::Duration};
+
+use input_linux::{
+    sys::input_event, EventKind, InputEvent, SoundEvent, SoundKind, SynchronizeEvent,
+};
+
+use crate::{args::BeepSound, device::get_current_time, InputDevice};
+
+/// Where the beep on a state change is played
+pub enum Beeper {
+    /// The bell character, when stdout is a terminal
+    Terminal,
+    /// A tone, or the bell when there is no tone, of the PC speaker input device
+    Speaker(Arc<InputDevice>, SoundKind),
+}
+
+impl Beeper {
+    /// Falls back to the terminal bell when there is no device that makes sounds
+    pub fn new(sound: BeepSound) -> Self {
+        let speaker = match sound {
+            BeepSound::Terminal => false,
+            BeepSound::Speaker => true,
+            BeepSound::Auto => !std::io::stdout().is_terminal(),
+        };
+        if !speaker {
+            return Self::Terminal;
+        }
+
+        let found = InputDevice::devices().into_iter().find_map(|device| {
+            if !device
+                .handler
+                .event_bits()
+                .is_ok_and(|bits| bits.get(EventKind::Sound))
+            {
+                return None;
+            }
+            let bits = device.handler.sound_bits().ok()?;
+            let kind = [SoundKind::Tone, SoundKind::Bell]
+                .into_iter()
+                .find(|kind| bits.get(*kind))?;
+            Some((device, kind))
+        });
+        match found {
+            Some((device, kind)) => Self::Speaker(Arc::new(device), kind),
+            None => {
+                eprintln!(
+                    "\x1B[1;33mWarning: no input device makes sounds, like the pcspkr one, beeping on the terminal\x1B[0;39m"
+                );
+                Self::Terminal
+            }
+        }
+    }
+
+    pub fn beep(&self) {
+        match self {
+            Self::Terminal => {
+                // ansi beep sound
+                print!("\x07");
+            }
+            Self::Speaker(device, kind) => {
+                let on = match kind {
+                    SoundKind::Tone => 880,
+                    _ => 1,
+                };
+                Self::sound(device, *kind, on);
+                let device = device.clone();
+                let kind = *kind;
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(100));
+                    Self::sound(&device, kind, 0);
+                });
+            }
+        }
+    }
+
+    fn sound(device: &InputDevice, kind: SoundKind, value: i32) {
+        let time = get_current_time();
+        let events: [input_event; 2] = [
+            InputEvent::from(SoundEvent::new(time, kind, value))
+                .as_raw()
+                .to_owned(),
+            InputEvent::from(SynchronizeEvent::report(time))
+                .as_raw()
+                .to_owned(),
+        ];
+        _ = device.handler.write(&events);
+    }
+}
@@ -0,0 +1,864 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    args::{
+        Backend, BeepSound, Bind, BindMode, Binding, BindingAction, ClickOptions, Command,
+        HumanDuration, MacroBind, ReactBind, StartActive,
+    },
+    device::parse_id,
+    resolve_device_query, toml, InputDevice, KeyCode, Macro,
+};
+
+/// The config file, loaded with `--config` and checked by the `config` commands
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Config {
+    /// The version of the format, the loaded configs are migrated to the current one, see
+    /// `migrate`
+    #[serde(default = "Config::default_version")]
+    pub version: u64,
+    pub debug: bool,
+    pub beep: bool,
+    #[serde(default)]
+    pub beep_sound: BeepSound,
+    #[serde(default)]
+    pub backend: Backend,
+    pub command: ConfigCommand,
+    /// Commands chosen by their name with `run --profile NAME`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ConfigCommand>,
+}
+
+impl Config {
+    fn default_version() -> u64 {
+        VERSION
+    }
+
+    /// Loads a JSON config, or a TOML one when the file ends with `.toml`, the configs of older
+    /// versions are migrated
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_value(parse(path, &content)?)?)
+    }
+
+    /// `$XDG_CONFIG_HOME/theclicker`, or `~/.config/theclicker`
+    pub fn dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join("theclicker"))
+    }
+
+    /// The `config.toml` or else the `config.json` of the config directory, when there is one
+    pub fn find_default() -> Option<PathBuf> {
+        let dir = Self::dir()?;
+        ["config.toml", "config.json"]
+            .into_iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    }
+
+    /// Where the setup saves the config, in the config directory
+    pub fn default_path() -> PathBuf {
+        Self::dir().map_or_else(
+            || PathBuf::from("config.json"),
+            |dir| dir.join("config.json"),
+        )
+    }
+
+    /// Saves as JSON, or as TOML when the file ends with `.toml`, the directory is created when
+    /// missing
+    pub fn save_to_file(&self, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let text = if is_toml(path) {
+            toml::to_string(&serde_json::to_value(self)?)?
+        } else {
+            serde_json::to_string_pretty(self)?
+        };
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// The text of a config in the current version, JSON or TOML by the extension of `path`
+fn parse(path: &Path, text: &str) -> Result<Value, String> {
+    let mut config = if is_toml(path) {
+        toml::from_str(text)?
+    } else {
+        serde_json::from_str(text).map_err(|err| err.to_string())?
+    };
+    migrate(&mut config)?;
+    inherit(&mut config)?;
+    Ok(config)
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"))
+}
+
+// Only parsed once, the run options do not need to be boxed
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum ConfigCommand {
+    Run {
+        #[serde(default)]
+        device_query: String,
+        /// USB ids of the device the command is for, like `046d:c08b`, `theclicker daemon` runs it
+        /// while the device is plugged in, it is the device when there is no `device_query`
+        #[serde(default)]
+        for_device: Option<String>,
+        #[serde(default)]
+        left_bind: Option<Bind>,
+        #[serde(default)]
+        right_bind: Option<Bind>,
+        lock_unlock_bind: Option<Bind>,
+        hold: bool,
+        grab: bool,
+        #[serde(default)]
+        macro_binds: Vec<MacroBind>,
+        #[serde(default)]
+        react_binds: Vec<ReactBind>,
+        #[serde(default)]
+        record_raw: Option<PathBuf>,
+        #[serde(default)]
+        multiply: Option<u32>,
+        #[serde(default)]
+        double_tap: Option<u64>,
+        #[serde(default)]
+        long_press: Option<u64>,
+        #[serde(default)]
+        left_mode: Option<BindMode>,
+        #[serde(default)]
+        right_mode: Option<BindMode>,
+        #[serde(default)]
+        faster_bind: Option<Bind>,
+        #[serde(default)]
+        slower_bind: Option<Bind>,
+        #[serde(default)]
+        binds: Vec<Binding>,
+        #[serde(default)]
+        enable_bind: Option<Bind>,
+        #[serde(default)]
+        profiles: Vec<PathBuf>,
+        #[serde(default)]
+        profile_bind: Option<Bind>,
+        #[serde(default)]
+        panic_bind: Option<Bind>,
+        #[serde(default)]
+        bind_device: Option<String>,
+        #[serde(default)]
+        start_active: Option<StartActive>,
+        #[serde(default)]
+        start_delay: Option<HumanDuration>,
+        #[serde(default)]
+        override_main: bool,
+        #[serde(default)]
+        extra_devices: Vec<String>,
+        #[serde(default)]
+        no_regrab: bool,
+        #[serde(default)]
+        control: Option<PathBuf>,
+        #[serde(flatten)]
+        click: ClickOptions,
+    },
+    RunLegacy {
+        #[serde(default)]
+        device_query: String,
+        /// USB ids of the device the command is for, like `046d:c08b`, `theclicker daemon` runs it
+        /// while the device is plugged in, it is the device when there is no `device_query`
+        #[serde(default)]
+        for_device: Option<String>,
+        #[serde(flatten)]
+        click: ClickOptions,
+    },
+}
+
+impl From<ConfigCommand> for Command {
+    fn from(config_cmd: ConfigCommand) -> Self {
+        match config_cmd {
+            ConfigCommand::Run {
+                device_query,
+                for_device,
+                left_bind,
+                right_bind,
+                lock_unlock_bind,
+                hold,
+                grab,
+                macro_binds,
+                react_binds,
+                record_raw,
+                multiply,
+                double_tap,
+                long_press,
+                left_mode,
+                right_mode,
+                faster_bind,
+                slower_bind,
+                binds,
+                enable_bind,
+                profiles,
+                profile_bind,
+                panic_bind,
+                bind_device,
+                start_active,
+                start_delay,
+                override_main,
+                extra_devices,
+                no_regrab,
+                control,
+                click,
+            } => Command::Run {
+                device_query: device_query_or(device_query, for_device),
+                left_bind,
+                right_bind,
+                lock_unlock_bind,
+                hold,
+                grab,
+                macro_binds,
+                react_binds,
+                record_raw,
+                multiply,
+                double_tap,
+                long_press,
+                left_mode,
+                right_mode,
+                faster_bind,
+                slower_bind,
+                binds,
+                enable_bind,
+                profiles,
+                profile_bind,
+                panic_bind,
+                bind_device,
+                start_active,
+                start_delay,
+                override_main,
+                extra_devices,
+                no_regrab,
+                control,
+                click,
+            },
+            ConfigCommand::RunLegacy {
+                device_query,
+                for_device,
+                click,
+            } => Command::RunLegacy {
+                device_query: device_query_or(device_query, for_device),
+                click,
+            },
+        }
+    }
+}
+
+/// The ids of `for_device` are the device of the commands without a `device_query`
+fn device_query_or(device_query: String, for_device: Option<String>) -> String {
+    match for_device {
+        Some(ids) if device_query.is_empty() => ids,
+        _ => device_query,
+    }
+}
+
+/// Only the run commands are saved in the configs
+impl TryFrom<Command> for ConfigCommand {
+    type Error = String;
+
+    fn try_from(command: Command) -> Result<Self, Self::Error> {
+        match command {
+            Command::Run {
+                device_query,
+                left_bind,
+                right_bind,
+                lock_unlock_bind,
+                hold,
+                grab,
+                macro_binds,
+                react_binds,
+                record_raw,
+                multiply,
+                double_tap,
+                long_press,
+                left_mode,
+                right_mode,
+                faster_bind,
+                slower_bind,
+                binds,
+                enable_bind,
+                profiles,
+                profile_bind,
+                panic_bind,
+                bind_device,
+                start_active,
+                start_delay,
+                override_main,
+                extra_devices,
+                no_regrab,
+                control,
+                click,
+            } => Ok(ConfigCommand::Run {
+                device_query,
+                for_device: None,
+                left_bind,
+                right_bind,
+                lock_unlock_bind,
+                hold,
+                grab,
+                macro_binds,
+                react_binds,
+                record_raw,
+                multiply,
+                double_tap,
+                long_press,
+                left_mode,
+                right_mode,
+                faster_bind,
+                slower_bind,
+                binds,
+                enable_bind,
+                profiles,
+                profile_bind,
+                panic_bind,
+                bind_device,
+                start_active,
+                start_delay,
+                override_main,
+                extra_devices,
+                no_regrab,
+                control,
+                click,
+            }),
+            Command::RunLegacy {
+                device_query,
+                click,
+            } => Ok(ConfigCommand::RunLegacy {
+                device_query,
+                for_device: None,
+                click,
+            }),
+            Command::Record { .. }
+            | Command::Replay { .. }
+            | Command::Import { .. }
+            | Command::Config { .. }
+            | Command::Daemon => {
+                Err("only `run` and `run-legacy` can be saved in a config".to_string())
+            }
+        }
+    }
+}
+
+/// The version of the config format, written in the saved configs
+pub const VERSION: u64 = 2;
+
+/// Brings a config written by an older version to the current format, the configs without a
+/// `version` are version 1
+pub fn migrate(config: &mut Value) -> Result<(), String> {
+    let Value::Object(config) = config else {
+        return Ok(());
+    };
+    let version = match config.get("version") {
+        None => 1,
+        Some(version) => version
+            .as_u64()
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| format!("{version} is not a config version"))?,
+    };
+    if version > VERSION {
+        return Err(format!(
+            "the config is version {version}, this theclicker reads up to version {VERSION}"
+        ));
+    }
+
+    let mut commands = Vec::new();
+    for (key, value) in config.iter_mut() {
+        match (key.as_str(), value) {
+            ("command", command) => commands.push(command),
+            ("profiles", Value::Object(profiles)) => commands.extend(profiles.values_mut()),
+            _ => {}
+        }
+    }
+    for command in commands.into_iter().filter_map(Value::as_object_mut) {
+        // Version 2: `position` became `positions`, a list of them
+        if version < 2 {
+            if let Some(position) = command.remove("position") {
+                if !position.is_null() && !command.contains_key("positions") {
+                    command.insert("positions".to_string(), Value::Array(vec![position]));
+                }
+            }
+        }
+    }
+
+    config.insert("version".to_string(), Value::from(VERSION));
+    Ok(())
+}
+
+/// Resolves the profiles with `inherits = "NAME"`, they are the profile `NAME` with their own
+/// fields over it, `default` is the `command` when there is no profile with that name
+fn inherit(config: &mut Value) -> Result<(), String> {
+    let Some(Value::Object(profiles)) = config.get("profiles") else {
+        return Ok(());
+    };
+    let command = config.get("command").cloned().unwrap_or(Value::Null);
+
+    let mut resolved = Map::new();
+    for name in profiles.keys() {
+        let profile = resolve_profile(name, profiles, &command, &mut Vec::new())?;
+        resolved.insert(name.clone(), profile);
+    }
+    config["profiles"] = Value::Object(resolved);
+    Ok(())
+}
+
+/// The profile `name` with the fields of the profiles it inherits, `chain` are the profiles
+/// inheriting from it
+fn resolve_profile(
+    name: &str,
+    profiles: &Map<String, Value>,
+    command: &Value,
+    chain: &mut Vec<String>,
+) -> Result<Value, String> {
+    let profile = match profiles.get(name) {
+        Some(profile) => profile,
+        None if name == "default" && command.is_object() => return Ok(command.clone()),
+        None => return Err(format!("there is no profile {name:?} to inherit from")),
+    };
+    let Value::Object(fields) = profile else {
+        return Ok(profile.clone());
+    };
+    let Some(base) = fields.get("inherits") else {
+        return Ok(profile.clone());
+    };
+    let Some(base) = base.as_str() else {
+        return Err(format!("profiles.{name}.inherits: has to be the name of a profile"));
+    };
+    if chain.iter().any(|inheriting| inheriting == name) {
+        chain.push(name.to_string());
+        return Err(format!("the profiles inherit in a loop, {}", chain.join(" -> ")));
+    }
+
+    chain.push(name.to_string());
+    let mut merged = resolve_profile(base, profiles, command, chain)?;
+    chain.pop();
+    let Value::Object(merged_fields) = &mut merged else {
+        return Err(format!("profiles.{name}: cannot inherit from {base:?}"));
+    };
+    for (key, value) in fields {
+        if key != "inherits" {
+            merged_fields.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(merged)
+}
+
+/// A problem found in a config, with the field and the line it is on
+struct Problem {
+    field: String,
+    line: Option<usize>,
+    message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.field.is_empty(), self.line) {
+            (true, _) => write!(f, "{}", self.message),
+            (false, Some(line)) => write!(f, "{} (line {line}): {}", self.field, self.message),
+            (false, None) => write!(f, "{}: {}", self.field, self.message),
+        }
+    }
+}
+
+/// The text of the config, to tell the lines of the fields
+struct Source<'a> {
+    text: &'a str,
+    toml: bool,
+}
+
+impl Source<'_> {
+    /// The line of `key` in the table at `section`, like `profiles.fast`, found by its text
+    fn line_of(&self, section: &str, key: &str) -> Option<usize> {
+        let lines: Vec<&str> = self.text.lines().map(str::trim_start).collect();
+        let mut start = 0;
+        if self.toml {
+            if !section.is_empty() {
+                let header = format!("[{section}]");
+                start = lines.iter().position(|line| line.starts_with(&header))?;
+            }
+            let table = format!("[[{section}.{key}]]");
+            lines[start..]
+                .iter()
+                .position(|line| {
+                    line.starts_with(&table)
+                        || line
+                            .strip_prefix(key)
+                            .is_some_and(|rest| rest.trim_start().starts_with('='))
+                })
+                .map(|index| start + index + 1)
+        } else {
+            for segment in section.split('.').filter(|segment| !segment.is_empty()) {
+                let quoted = format!("\"{segment}\"");
+                start += lines[start..]
+                    .iter()
+                    .position(|line| line.contains(&quoted))?;
+            }
+            let quoted = format!("\"{key}\"");
+            lines[start..]
+                .iter()
+                .position(|line| line.contains(&quoted))
+                .map(|index| start + index + 1)
+        }
+    }
+}
+
+struct Validator<'a> {
+    source: Source<'a>,
+    problems: Vec<Problem>,
+}
+
+impl Validator<'_> {
+    fn problem(&mut self, section: &str, key: &str, message: impl Into<String>) {
+        let field = match (section.is_empty(), key.is_empty()) {
+            (true, _) => key.to_string(),
+            (false, true) => section.to_string(),
+            (false, false) => format!("{section}.{key}"),
+        };
+        let line = if key.is_empty() {
+            None
+        } else {
+            self.source.line_of(section, key)
+        };
+        self.problems.push(Problem {
+            field,
+            line,
+            message: message.into(),
+        });
+    }
+
+    fn config(&mut self, value: &Value) -> Vec<(String, Command)> {
+        let Value::Object(config) = value else {
+            self.problem("", "", "The config has to be an object");
+            return Vec::new();
+        };
+        for (key, value) in config {
+            let result = match key.as_str() {
+                "version" | "$schema" => Ok(()),
+                "debug" | "beep" => serde_json::from_value::<bool>(value.clone()).map(|_| ()),
+                "beep_sound" => serde_json::from_value::<BeepSound>(value.clone()).map(|_| ()),
+                "backend" => serde_json::from_value::<Backend>(value.clone()).map(|_| ()),
+                "command" | "profiles" => Ok(()),
+                _ => {
+                    self.problem("", key, "unknown field");
+                    continue;
+                }
+            };
+            if let Err(err) = result {
+                self.problem("", key, err.to_string());
+            }
+        }
+        for key in ["debug", "beep", "command"] {
+            if !config.contains_key(key) {
+                self.problem("", key, "missing field");
+            }
+        }
+
+        let mut commands = Vec::new();
+        if let Some(command) = config
+            .get("command")
+            .and_then(|value| self.command("command", value))
+        {
+            commands.push(("command".to_string(), command));
+        }
+        match config.get("profiles") {
+            None => {}
+            Some(Value::Object(profiles)) => {
+                for (name, value) in profiles {
+                    let section = format!("profiles.{name}");
+                    if let Some(command) = self.command(&section, value) {
+                        commands.push((section, command));
+                    }
+                }
+            }
+            Some(_) => self.problem("", "profiles", "has to be a table of named commands"),
+        }
+        commands
+    }
+
+    /// Checks every field of the command on its own, so all of the wrong ones are reported
+    fn command(&mut self, section: &str, value: &Value) -> Option<Command> {
+        let Value::Object(command) = value else {
+            self.problem(section, "", "has to be a table with a `type`");
+            return None;
+        };
+        let kind = command.get("type").and_then(Value::as_str);
+        let mut base = Map::new();
+        base.insert("type".to_string(), Value::from(kind));
+        base.insert("device_query".to_string(), Value::from(""));
+        match kind {
+            Some("Run") => {
+                base.insert("hold".to_string(), Value::from(false));
+                base.insert("grab".to_string(), Value::from(false));
+                base.insert("lock_unlock_bind".to_string(), Value::Null);
+            }
+            Some("RunLegacy") => {}
+            _ => {
+                self.problem(section, "type", "has to be \"Run\" or \"RunLegacy\"");
+                return None;
+            }
+        }
+        // The fields of the command, with the defaults written out
+        let known = serde_json::from_value::<ConfigCommand>(Value::Object(base.clone()))
+            .and_then(serde_json::to_value)
+            .ok()?;
+        let known = known.as_object()?;
+
+        let before = self.problems.len();
+        for (key, value) in command {
+            if !known.contains_key(key) {
+                self.problem(section, key, "unknown field");
+                continue;
+            }
+            if key == "for_device" && value.as_str().is_some_and(|ids| parse_id(ids).is_none()) {
+                self.problem(section, key, "has to be USB ids like \"046d:c08b\"");
+                continue;
+            }
+            let mut alone = base.clone();
+            alone.insert(key.clone(), value.clone());
+            if let Err(err) = serde_json::from_value::<ConfigCommand>(Value::Object(alone)) {
+                self.problem(section, key, err.to_string());
+            }
+        }
+        match serde_json::from_value::<ConfigCommand>(value.clone()) {
+            Ok(command) => {
+                let command = Command::from(command);
+                if let Command::Run {
+                    grab: false,
+                    multiply: Some(_),
+                    ..
+                } = command
+                {
+                    // The physical click would go through besides the multiplied ones
+                    self.problem(section, "multiply", "needs `grab` to be true");
+                }
+                Some(command)
+            }
+            Err(err) => {
+                if self.problems.len() == before {
+                    self.problem(section, "", err.to_string());
+                }
+                None
+            }
+        }
+    }
+
+    /// Resolves the device queries and checks that the devices send the bind keys
+    fn devices(&mut self, section: &str, command: &Command) {
+        let (device_query, extra_devices, bind_device, profiles) = match command {
+            Command::Run {
+                device_query,
+                extra_devices,
+                bind_device,
+                profiles,
+                ..
+            } => (
+                device_query,
+                extra_devices.as_slice(),
+                bind_device,
+                profiles.as_slice(),
+            ),
+            Command::RunLegacy { device_query, .. } => (device_query, &[][..], &None, &[][..]),
+            _ => return,
+        };
+        // Without a device the first profile is run
+        if device_query.is_empty() && !profiles.is_empty() {
+            return;
+        }
+
+        let queries = std::iter::once(("device_query", device_query))
+            .chain(extra_devices.iter().map(|query| ("extra_devices", query)))
+            .chain(bind_device.iter().map(|query| ("bind_device", query)));
+        let mut readers: Vec<InputDevice> = Vec::new();
+        for (key, query) in queries {
+            match resolve_device_query(query) {
+                Ok(device) => readers.push(device),
+                Err((_, err)) => self.problem(section, key, err),
+            }
+        }
+        if readers.is_empty() {
+            return;
+        }
+
+        let Command::Run {
+            left_bind,
+            right_bind,
+            lock_unlock_bind,
+            faster_bind,
+            slower_bind,
+            enable_bind,
+            profile_bind,
+            panic_bind,
+            macro_binds,
+            react_binds,
+            binds,
+            ..
+        } = command
+        else {
+            return;
+        };
+        let singles = [
+            ("left_bind", left_bind),
+            ("right_bind", right_bind),
+            ("lock_unlock_bind", lock_unlock_bind),
+            ("faster_bind", faster_bind),
+            ("slower_bind", slower_bind),
+            ("enable_bind", enable_bind),
+            ("profile_bind", profile_bind),
+            ("panic_bind", panic_bind),
+        ];
+        let lists: [(&str, Vec<Binding>); 3] = [
+            (
+                "macro_binds",
+                macro_binds.iter().map(Binding::from).collect(),
+            ),
+            (
+                "react_binds",
+                react_binds.iter().map(Binding::from).collect(),
+            ),
+            ("binds", binds.clone()),
+        ];
+        let binds =
+            singles
+                .into_iter()
+                .filter_map(|(key, bind)| Some((key, bind.clone()?)))
+                .chain(lists.into_iter().flat_map(|(key, binds)| {
+                    binds.into_iter().map(move |binding| (key, binding.key))
+                }));
+        for (key, Bind(codes)) in binds {
+            for code in codes {
+                if !readers.iter().any(|device| device.has_key(code)) {
+                    self.problem(
+                        section,
+                        key,
+                        format!("no input device sends the key, {}", KeyCode(code)),
+                    );
+                }
+            }
+        }
+    }
+
+    /// The macros have to load, with keys that can be sent
+    fn macros(&mut self, section: &str, command: &Command) {
+        let (click, macro_binds, binds) = match command {
+            Command::Run {
+                click,
+                macro_binds,
+                binds,
+                ..
+            } => (click, macro_binds.as_slice(), binds.as_slice()),
+            Command::RunLegacy { click, .. } => (click, &[][..], &[][..]),
+            _ => return,
+        };
+        let paths = [
+            ("left_macro", click.left_macro.as_ref()),
+            ("right_macro", click.right_macro.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(key, path)| Some((key, path?)))
+        .chain(macro_binds.iter().map(|bind| ("macro_binds", &bind.path)))
+        .chain(binds.iter().filter_map(|binding| match &binding.action {
+            BindingAction::Macro { path, .. } => Some(("binds", path)),
+            _ => None,
+        }));
+        for (key, path) in paths {
+            if let Err(err) = Macro::load(path) {
+                let path = path.display();
+                self.problem(section, key, format!("cannot load the macro {path}: {err}"));
+            }
+        }
+    }
+
+    /// The profiles to switch to have to be named profiles or config files
+    fn profiles(&mut self, section: &str, command: &Command, named: &[&str]) {
+        let Command::Run { profiles, .. } = command else {
+            return;
+        };
+        for profile in profiles {
+            let name = profile.to_string_lossy();
+            if named.contains(&&*name) {
+                continue;
+            }
+            if let Err(err) = Config::load(profile) {
+                self.problem(
+                    section,
+                    "profiles",
+                    format!("cannot load the profile {name}: {err}"),
+                );
+            }
+        }
+    }
+}
+
+/// `config validate`, reports every problem of the config without running it, returns if it is
+/// valid
+pub fn validate(path: &Path) -> bool {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("Cannot read {}: {err}", path.display());
+            return false;
+        }
+    };
+    let value = match parse(path, &text) {
+        Ok(value) => value,
+        Err(err) => {
+            println!("{}: {err}", path.display());
+            return false;
+        }
+    };
+
+    let mut validator = Validator {
+        source: Source {
+            text: &text,
+            toml: is_toml(path),
+        },
+        problems: Vec::new(),
+    };
+    let commands = validator.config(&value);
+    let named: Vec<&str> = value
+        .get("profiles")
+        .and_then(Value::as_object)
+        .map_or(Vec::new(), |profiles| {
+            profiles.keys().map(String::as_str).collect()
+        });
+    // The devices are only looked up when they can be listed
+    let devices = Path::new("/dev/input").is_dir();
+    if !devices {
+        eprintln!("\x1B[1;33mWarning: the devices cannot be checked without /dev/input\x1B[0;39m");
+    }
+    for (section, command) in &commands {
+        validator.profiles(section, command, &named);
+        validator.macros(section, command);
+        if devices {
+            validator.devices(section, command);
+        }
+    }
+
+    if validator.problems.is_empty() {
+        println!("{} is valid", path.display());
+        return true;
+    }
+    let count = validator.problems.len();
+    let plural = if count == 1 { "" } else { "s" };
+    println!("{}: {count} problem{plural}", path.display());
+    for problem in &validator.problems {
+        println!("  {problem}");
+    }
+    false
+}
@@ -0,0 +1,75 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// Unix socket that switches the profiles, the input thread polls `fd` and takes the names of
+/// the profiles to switch to, `None` for the next one
+pub struct ControlSocket {
+    requests: Receiver<Option<String>>,
+    /// Readable when there are requests
+    wake: UnixStream,
+}
+
+impl ControlSocket {
+    /// Listens on `path`, replacing the socket left by a previous run, `names` are the profiles
+    /// that can be switched to
+    pub fn bind(path: &Path, names: Vec<String>) -> io::Result<Self> {
+        if fs::symlink_metadata(path).is_ok() {
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let (wake, mut waker) = UnixStream::pair()?;
+        wake.set_nonblocking(true)?;
+        let (sender, requests) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let Ok(mut reply) = stream.try_clone() else {
+                    continue;
+                };
+                for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                    let request = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                        ["profile", "next"] => Ok(None),
+                        ["profile", name] if names.iter().any(|known| known == name) => {
+                            Ok(Some(name.to_string()))
+                        }
+                        ["profile", name] => Err(format!("unknown profile {name:?}")),
+                        _ => Err("use `profile NAME` or `profile next`".to_string()),
+                    };
+                    let answer = match request {
+                        Ok(request) => {
+                            if sender.send(request).is_err() {
+                                return;
+                            }
+                            _ = waker.write_all(&[0]);
+                            "ok".to_string()
+                        }
+                        Err(err) => format!("error: {err}"),
+                    };
+                    _ = writeln!(reply, "{answer}");
+                }
+            }
+        });
+
+        Ok(Self { requests, wake })
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.wake.as_raw_fd()
+    }
+
+    /// The requests since the last call
+    pub fn take(&mut self) -> Vec<Option<String>> {
+        let mut buffer = [0; 64];
+        while matches!(self.wake.read(&mut buffer), Ok(1..)) {}
+        self.requests.try_iter().collect()
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    process::{Child, Command},
+};
+
+use crate::{device::parse_id, ConfigCommand, Hotplug, InputDevice};
+
+/// A profile of the config with a `for_device`, run by the daemon while its device is plugged in
+struct Watched {
+    name: String,
+    ids: String,
+    vendor: u16,
+    product: u16,
+}
+
+enum Slot {
+    Running(Child),
+    /// The profile stopped on its own, it runs again when its device is plugged in again
+    Stopped,
+}
+
+/// `theclicker daemon`, runs `theclicker --config CONFIG run --profile NAME` for every profile
+/// with a `for_device` whose device is plugged in, the profiles wait for their device themselves
+/// when it is unplugged
+pub fn run(config: &Path, profiles: &BTreeMap<String, ConfigCommand>) -> ! {
+    let mut watched = Vec::new();
+    for (name, command) in profiles {
+        let (ConfigCommand::Run { for_device, .. } | ConfigCommand::RunLegacy { for_device, .. }) =
+            command;
+        let Some(ids) = for_device else {
+            continue;
+        };
+        let Some((vendor, product)) = parse_id(ids) else {
+            eprintln!("The profile {name} is for {ids:?}, that are not USB ids like `046d:c08b`");
+            std::process::exit(1);
+        };
+        watched.push(Watched {
+            name: name.clone(),
+            ids: ids.clone(),
+            vendor,
+            product,
+        });
+    }
+    if watched.is_empty() {
+        eprintln!("No profile of {} has a `for_device`", config.display());
+        std::process::exit(1);
+    }
+    for profile in &watched {
+        println!("Watching {} for the profile {}", profile.ids, profile.name);
+    }
+
+    let exe = std::env::current_exe().unwrap_or_else(|err| {
+        eprintln!("Cannot find the theclicker executable: {err}");
+        std::process::exit(1);
+    });
+    let hotplug = Hotplug::new();
+    let mut slots: HashMap<String, Slot> = HashMap::new();
+    loop {
+        for slot in slots.values_mut() {
+            if let Slot::Running(child) = slot {
+                if !matches!(child.try_wait(), Ok(None)) {
+                    *slot = Slot::Stopped;
+                }
+            }
+        }
+
+        let plugged: Vec<(u16, u16)> = InputDevice::devices()
+            .iter()
+            .filter_map(|device| device.handler.device_id().ok())
+            .map(|id| (id.vendor, id.product))
+            .collect();
+        for profile in &watched {
+            if !plugged.contains(&(profile.vendor, profile.product)) {
+                if matches!(slots.get(&profile.name), Some(Slot::Stopped)) {
+                    slots.remove(&profile.name);
+                }
+                continue;
+            }
+            if slots.contains_key(&profile.name) {
+                continue;
+            }
+
+            println!(
+                "{} is plugged in, running the profile {}",
+                profile.ids, profile.name
+            );
+            let child = Command::new(&exe)
+                .arg("--config")
+                .arg(config)
+                .args(["run", "--profile", &profile.name])
+                // The device of the profile, not the one of the daemon
+                .env_remove("THECLICKER_DEVICE")
+                .spawn();
+            match child {
+                Ok(child) => _ = slots.insert(profile.name.clone(), Slot::Running(child)),
+                Err(err) => {
+                    eprintln!("Cannot run the profile {}: {err}", profile.name);
+                    slots.insert(profile.name.clone(), Slot::Stopped);
+                }
+            }
+        }
+
+        hotplug.wait_added();
+    }
+}
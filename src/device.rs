@@ -1,23 +1,24 @@
 use std::{
+    collections::HashMap,
     fs::{self, File},
     io,
     os::fd::AsRawFd,
     path::PathBuf,
     process::exit,
-    time::SystemTime,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime},
 };
 
 use input_linux::{
-    sys::{input_event, BUS_USB},
-    EvdevHandle, EventKind, EventTime, InputEvent, InputId, Key, KeyEvent, KeyState,
+    sys::{input_event, repeat_settings},
+    AbsoluteAxis, AbsoluteEvent, AutorepeatEvent, AutorepeatKind, AbsoluteInfo, AbsoluteInfoSetup, EvdevHandle, EventKind, ForceFeedbackKind,
+    EventTime, InputEvent, InputId, Key, KeyEvent, KeyState, MiscKind, RelativeAxis, RelativeEvent,
     SynchronizeEvent, UInputHandle,
 };
 
-use crate::{choose_usize, choose_yes};
+use crate::{backend::OutputBackend, choose_usize, choose_yes, DeviceRegex};
 
-const VENDOR: u16 = 0x3232;
-const VERSION: u16 = 0x1234;
-const PRODUCT: u16 = 0x5678;
 
 pub enum DeviceType {
     Mouse,
@@ -39,6 +40,8 @@ pub struct InputDevice {
     pub path: PathBuf,
     pub filename: String,
     pub handler: EvdevHandle<File>,
+    /// The bus and USB ids, read when it is opened so they are still known once it is gone
+    pub id: InputId,
 }
 
 impl InputDevice {
@@ -49,7 +52,13 @@ impl InputDevice {
                 .join(std::fs::read_link(&path).unwrap().file_name().unwrap());
         }
 
-        let file = match File::open(&path) {
+        // Writable for the LEDs, the force feedback and the tones, read only when that is denied
+        let file = match File::options()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .or_else(|_| File::open(&path))
+        {
             Ok(file) => file,
             Err(err) => {
                 println!("Error: {}", err);
@@ -64,12 +73,14 @@ impl InputDevice {
         let name = String::from_utf8_lossy(&name_bytes);
 
         let name = format!("{name}-{}", path.file_name().unwrap().to_str().unwrap());
+        let id = handler.device_id().unwrap_or_default();
 
         Ok(Self {
             filename: path.file_name().unwrap().to_str().unwrap().to_owned(),
             path,
             handler,
             name,
+            id,
         })
     }
 
@@ -94,6 +105,17 @@ impl InputDevice {
     }
 
     pub fn find_device(device_name: &str) -> Option<InputDevice> {
+        if device_name == "auto" {
+            return Self::find_mouse();
+        }
+        if let Some((vendor, product)) = parse_id(device_name) {
+            return Self::find_by_id(vendor, product);
+        }
+        if let Some(pattern) = device_name.strip_prefix("re:") {
+            let pattern = DeviceRegex::new(pattern).ok()?;
+            return Self::find_matching(&pattern).into_iter().next();
+        }
+
         let devices = Self::devices();
 
         for device in devices {
@@ -108,6 +130,98 @@ impl InputDevice {
             .find(|device| device.name.trim().contains(device_name))
     }
 
+    /// If the device can send the key, the codes of the wheel and gesture binds count as sent
+    pub fn has_key(&self, code: u16) -> bool {
+        let Ok(key) = Key::from_code(code) else {
+            return true;
+        };
+        self.handler.key_bits().is_ok_and(|bits| bits.get(key))
+    }
+
+    /// If the device has the keys from Esc to Space, unlike the power buttons and the mice
+    pub fn is_full_keyboard(&self) -> bool {
+        self.handler.key_bits().is_ok_and(|bits| {
+            (Key::Esc as u16..=Key::Space as u16)
+                .all(|code| Key::from_code(code).is_ok_and(|key| bits.get(key)))
+        })
+    }
+
+    /// If the kernel repeats the held keys of the device
+    pub fn repeats(&self) -> bool {
+        self.handler
+            .event_bits()
+            .is_ok_and(|bits| bits.get(EventKind::Autorepeat))
+    }
+
+    /// The /dev/input/by-id link of the device, or the by-path one, that stay the same when the
+    /// event numbers change after a reboot, the path itself when there is none
+    pub fn stable_path(&self) -> PathBuf {
+        ["/dev/input/by-id", "/dev/input/by-path"]
+            .into_iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flat_map(|entries| entries.filter_map(|entry| entry.ok()))
+            .map(|entry| entry.path())
+            .find(|link| {
+                fs::read_link(link).is_ok_and(|target| {
+                    target.file_name().and_then(|name| name.to_str()) == Some(&self.filename)
+                })
+            })
+            .unwrap_or_else(|| self.path.clone())
+    }
+
+    /// The devices whose name matches the pattern, by event number
+    pub(crate) fn find_matching(pattern: &DeviceRegex) -> Vec<InputDevice> {
+        let mut devices: Vec<_> = Self::devices()
+            .into_iter()
+            .filter(|device| pattern.is_match(device.name.trim()))
+            .collect();
+        devices.sort_by_key(InputDevice::event_number);
+        devices
+    }
+
+    /// The device with these USB ids and the lowest event number, a receiver often has a few
+    pub fn find_by_id(vendor: u16, product: u16) -> Option<InputDevice> {
+        Self::devices()
+            .into_iter()
+            .filter(|device| {
+                device
+                    .handler
+                    .device_id()
+                    .is_ok_and(|id| id.vendor == vendor && id.product == product)
+            })
+            .min_by_key(InputDevice::event_number)
+    }
+
+    /// If the device looks like a real mouse, with a left button and both relative axes
+    pub fn is_real_mouse(&self) -> bool {
+        let has_axes = self.handler.relative_bits().is_ok_and(|bits| {
+            bits.get(RelativeAxis::X) && bits.get(RelativeAxis::Y)
+        });
+        has_axes && self.has_key(Key::ButtonLeft as u16)
+    }
+
+    /// The real mouse with the lowest event number
+    pub fn find_mouse() -> Option<InputDevice> {
+        Self::devices()
+            .into_iter()
+            .filter(|device| device.is_real_mouse())
+            .min_by_key(InputDevice::event_number)
+    }
+
+    /// The full keyboard with the lowest event number
+    pub fn find_keyboard() -> Option<InputDevice> {
+        Self::devices()
+            .into_iter()
+            .filter(|device| device.is_full_keyboard())
+            .min_by_key(InputDevice::event_number)
+    }
+
+    /// The `N` of /dev/input/eventN, the other devices go last
+    fn event_number(&self) -> u32 {
+        let number = self.filename.strip_prefix("event").map(str::parse);
+        number.and_then(Result::ok).unwrap_or(u32::MAX)
+    }
+
     pub fn select_device() -> InputDevice {
         loop {
             let mut devices = Self::devices();
@@ -143,6 +257,62 @@ impl InputDevice {
         }
     }
 
+    /// Uploads a short rumble, or a sine wave on the devices without rumble, returns the id that
+    /// plays it
+    pub fn upload_rumble(&self, length: Duration) -> io::Result<i16> {
+        let rumble = self
+            .handler
+            .force_feedback_bits()
+            .is_ok_and(|bits| bits.get(ForceFeedbackKind::Rumble));
+        let mut effect: input_linux::sys::ff_effect = unsafe { std::mem::zeroed() };
+        effect.id = -1;
+        effect.replay.length = length.as_millis().min(u16::MAX as u128) as u16;
+        effect.type_ = if rumble {
+            input_linux::sys::FF_RUMBLE
+        } else {
+            input_linux::sys::FF_PERIODIC
+        };
+        let union: &mut input_linux::sys::ff_effect_union = (&mut effect).into();
+        if rumble {
+            let rumble = union.rumble_mut();
+            rumble.strong_magnitude = 0xc000;
+            rumble.weak_magnitude = 0xc000;
+        } else {
+            let periodic = union.periodic_mut();
+            periodic.waveform = input_linux::sys::FF_SINE;
+            periodic.period = 20;
+            periodic.magnitude = 0x6000;
+        }
+        self.handler.send_force_feedback(&mut effect)?;
+        Ok(effect.id)
+    }
+
+    /// Plays an uploaded effect once
+    pub fn play_effect(&self, id: i16) -> io::Result<()> {
+        let report = InputEvent::from(SynchronizeEvent::report(get_current_time()))
+            .as_raw()
+            .to_owned();
+        let play = input_event {
+            type_: input_linux::sys::EV_FF as u16,
+            code: id as u16,
+            value: 1,
+            ..report
+        };
+        self.handler.write(&[play, report])?;
+        Ok(())
+    }
+
+    /// Another handle on the same device, like for setting up the virtual device again
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            filename: self.filename.clone(),
+            handler: EvdevHandle::new(self.handler.as_inner().try_clone()?),
+            id: self.id,
+        })
+    }
+
     pub fn read(&self, events: &mut [input_event]) -> io::Result<usize> {
         self.handler.read(events)
     }
@@ -169,23 +339,57 @@ impl InputDevice {
     }
 }
 
+/// The keys of the keyboards, the codes before the mouse buttons and the ones between the
+/// buttons after them
+pub(crate) fn keyboard_keys() -> impl Iterator<Item = Key> {
+    (1..=input_linux::sys::KEY_MAX as u16)
+        .filter(|code| is_keyboard_key(*code))
+        .filter_map(|code| Key::from_code(code).ok())
+}
+
+/// If the code is one of the `keyboard_keys`
+pub(crate) fn is_keyboard_key(code: u16) -> bool {
+    use input_linux::sys::{BTN_DPAD_RIGHT, BTN_DPAD_UP, BTN_MISC, BTN_TRIGGER_HAPPY, KEY_OK};
+
+    let code = code as i32;
+    (1..BTN_MISC).contains(&code)
+        || (KEY_OK..BTN_DPAD_UP).contains(&code)
+        || (BTN_DPAD_RIGHT + 1..BTN_TRIGGER_HAPPY).contains(&code)
+}
+
+/// The repeats of a held key, a virtual device with key repeat makes its own so they are not
+/// relayed
+pub(crate) fn is_key_repeat(event: &input_event) -> bool {
+    event.type_ as i32 == input_linux::sys::EV_KEY && event.value == 2
+}
+
+/// The vendor and product ids of a query like `046d:c08b`
+pub fn parse_id(query: &str) -> Option<(u16, u16)> {
+    let (vendor, product) = query.split_once(':')?;
+    let hex = |id: &str| {
+        (id.len() == 4)
+            .then(|| u16::from_str_radix(id, 16).ok())
+            .flatten()
+    };
+    Some((hex(vendor)?, hex(product)?))
+}
+
 pub struct OutputDevice {
     pub name: String,
     pub path: PathBuf,
     pub filename: String,
     pub handler: UInputHandle<File>,
+    /// Ranges of the absolute axes, given when the device is created
+    abs_setup: Mutex<Vec<AbsoluteInfoSetup>>,
+    /// Key repeat delay and period of the grabbed keyboard, set once the device is created
+    repeat: Mutex<Option<repeat_settings>>,
+    id: InputId,
 }
 
 impl OutputDevice {
-    pub fn uinput_open(path: PathBuf, name: &str) -> Result<Self, String> {
-        let file = match fs::OpenOptions::new().write(true).open(&path) {
-            Ok(file) => file,
-            Err(err) => {
-                println!("Error: {}", err);
-                println!("Not having access to create device, try as root!");
-                exit(1);
-            }
-        };
+    pub fn uinput_open(path: PathBuf, name: &str, id: InputId) -> io::Result<Self> {
+        // Read too, for the LEDs and the force feedback of the applications
+        let file = fs::OpenOptions::new().read(true).write(true).open(&path)?;
 
         let handler = UInputHandle::new(file);
 
@@ -194,6 +398,9 @@ impl OutputDevice {
             handler,
             name: name.to_string(),
             filename: name.to_string(),
+            abs_setup: Mutex::new(Vec::new()),
+            repeat: Mutex::new(None),
+            id,
         })
     }
 
@@ -201,8 +408,131 @@ impl OutputDevice {
         self.handler.set_evbit(EventKind::Key).unwrap();
         self.handler.set_evbit(EventKind::Synchronize).unwrap();
 
-        self.handler.set_keybit(Key::ButtonLeft).unwrap();
-        self.handler.set_keybit(Key::ButtonRight).unwrap();
+        for key in [
+            Key::ButtonLeft,
+            Key::ButtonRight,
+            Key::ButtonMiddle,
+            Key::ButtonSide,
+            Key::ButtonExtra,
+        ] {
+            self.handler.set_keybit(key).unwrap();
+        }
+    }
+
+    /// For moving the pointer
+    pub fn add_pointer_attributes(&self) {
+        self.handler.set_evbit(EventKind::Relative).unwrap();
+        self.handler.set_relbit(RelativeAxis::X).unwrap();
+        self.handler.set_relbit(RelativeAxis::Y).unwrap();
+    }
+
+    /// For sending the scroll wheel, with the high resolution axis
+    pub fn add_wheel_attributes(&self) {
+        self.handler.set_evbit(EventKind::Relative).unwrap();
+        self.handler.set_relbit(RelativeAxis::Wheel).unwrap();
+        self.handler.set_relbit(RelativeAxis::WheelHiRes).unwrap();
+    }
+
+    /// For moving the pointer to a position on a screen of `width` x `height`
+    pub fn add_absolute_attributes(&self, width: i32, height: i32) {
+        self.handler.set_evbit(EventKind::Absolute).unwrap();
+        let mut abs_setup = self.abs_setup.lock().unwrap();
+        for (axis, size) in [(AbsoluteAxis::X, width), (AbsoluteAxis::Y, height)] {
+            self.handler.set_absbit(axis).unwrap();
+            // The range of the screen wins over the one copied from a grabbed device
+            abs_setup.retain(|setup| setup.axis != axis);
+            abs_setup.push(AbsoluteInfoSetup {
+                axis,
+                info: AbsoluteInfo {
+                    maximum: size - 1,
+                    ..Default::default()
+                },
+            });
+        }
+    }
+
+    /// For sending keyboard keys, every key that is not a mouse, joystick or gamepad button
+    pub fn add_keyboard_attributes(&self) {
+        self.handler.set_evbit(EventKind::Key).unwrap();
+        self.handler.set_evbit(EventKind::Synchronize).unwrap();
+
+        for key in keyboard_keys() {
+            self.handler.set_keybit(key).unwrap();
+        }
+    }
+
+    /// For relaying a grabbed keyboard, the kernel repeats the held keys at the rate of the
+    /// keyboard and the scan codes go through
+    pub fn add_repeat_attributes(&self, from: &InputDevice) {
+        self.handler.set_evbit(EventKind::Autorepeat).unwrap();
+        self.handler.set_evbit(EventKind::Misc).unwrap();
+        self.handler.set_mscbit(MiscKind::Scancode).unwrap();
+        *self.repeat.lock().unwrap() = from.handler.repeat_settings().ok();
+    }
+
+    /// For sending gamepad buttons, with the two sticks and the d-pad so it is seen as a gamepad
+    pub fn add_gamepad_attributes(&self) {
+        self.handler.set_evbit(EventKind::Key).unwrap();
+        self.handler.set_evbit(EventKind::Synchronize).unwrap();
+        self.handler.set_evbit(EventKind::Absolute).unwrap();
+
+        for code in input_linux::sys::BTN_GAMEPAD as u16..=input_linux::sys::BTN_THUMBR as u16 {
+            if let Ok(key) = Key::from_code(code) {
+                self.handler.set_keybit(key).unwrap();
+            }
+        }
+
+        let mut abs_setup = self.abs_setup.lock().unwrap();
+        let stick = AbsoluteInfo {
+            minimum: i16::MIN as i32,
+            maximum: i16::MAX as i32,
+            fuzz: 16,
+            flat: 128,
+            ..Default::default()
+        };
+        let hat = AbsoluteInfo {
+            minimum: -1,
+            maximum: 1,
+            ..Default::default()
+        };
+        for (axis, info) in [
+            (AbsoluteAxis::X, stick),
+            (AbsoluteAxis::Y, stick),
+            (AbsoluteAxis::RX, stick),
+            (AbsoluteAxis::RY, stick),
+            (AbsoluteAxis::Hat0X, hat),
+            (AbsoluteAxis::Hat0Y, hat),
+        ] {
+            // Already copied from a grabbed gamepad
+            if abs_setup.iter().any(|setup| setup.axis == axis) {
+                continue;
+            }
+            self.handler.set_absbit(axis).unwrap();
+            abs_setup.push(AbsoluteInfoSetup { axis, info });
+        }
+    }
+
+    /// For replaying these events, the absolute axes are left out because they need ranges
+    pub fn add_event_attributes(&self, events: &[input_event]) {
+        self.handler.set_evbit(EventKind::Synchronize).unwrap();
+        for event in events {
+            let Ok(kind) = EventKind::from_type(event.type_) else {
+                continue;
+            };
+            let set = match kind {
+                EventKind::Key => Key::from_code(event.code).map(|key| self.handler.set_keybit(key)),
+                EventKind::Relative => {
+                    RelativeAxis::from_code(event.code).map(|axis| self.handler.set_relbit(axis))
+                }
+                EventKind::Misc => {
+                    MiscKind::from_code(event.code).map(|misc| self.handler.set_mscbit(misc))
+                }
+                _ => continue,
+            };
+            if matches!(set, Ok(Ok(()))) {
+                self.handler.set_evbit(kind).unwrap();
+            }
+        }
     }
 
     /// Only copis attributes from DevInput to UInput
@@ -228,15 +558,27 @@ impl OutputDevice {
             }
         }
 
-        // FIX: - TheClicker: kernel bug: device has min == max on ABS_VOLUME
-        // if let Ok(bits) = from.absolute_bits() {
-        //     if debug {
-        //         println!("Copy absolute_bits: {bits:?}")
-        //     }
-        //     for bit in bits.iter() {
-        //         to.set_absbit(bit).unwrap();
-        //     }
-        // }
+        // The kernel refuses the axes with min == max, like ABS_VOLUME on some keyboards, and the
+        // multitouch axes need slots, the other axes are copied with their ranges
+        if let Ok(bits) = from.absolute_bits() {
+            if debug {
+                println!("Copy absolute_bits: {bits:?}")
+            }
+            let mut abs_setup = self.abs_setup.lock().unwrap();
+            for axis in bits.iter() {
+                let Ok(info) = from.absolute_info(axis) else {
+                    continue;
+                };
+                if info.minimum >= info.maximum
+                    || axis as i32 >= input_linux::sys::ABS_MT_SLOT
+                    || abs_setup.iter().any(|setup| setup.axis == axis)
+                {
+                    continue;
+                }
+                to.set_absbit(axis).unwrap();
+                abs_setup.push(AbsoluteInfoSetup { axis, info });
+            }
+        }
 
         if let Ok(bits) = from.misc_bits() {
             if debug {
@@ -255,40 +597,247 @@ impl OutputDevice {
                 to.set_keybit(bit).unwrap();
             }
         }
+
+        if let Ok(bits) = from.led_bits() {
+            if debug {
+                println!("Copy led_bits: {bits:?}")
+            }
+            for bit in bits.iter() {
+                to.set_ledbit(bit).unwrap();
+            }
+        }
+
+        // Needs `forward_feedback` once created, the applications wait for their effects
+        if let Ok(bits) = from.force_feedback_bits() {
+            if debug {
+                println!("Copy force_feedback_bits: {bits:?}")
+            }
+            for bit in bits.iter() {
+                to.set_ffbit(bit).unwrap();
+            }
+        }
     }
 
-    pub fn create(&self) {
-        self.handler
-            .create(
-                &InputId {
-                    bustype: BUS_USB,
-                    vendor: VENDOR,
-                    product: PRODUCT,
-                    version: VERSION,
-                },
-                self.name.as_bytes(),
-                input_linux::sys::FF_MAX_EFFECTS as u32,
-                &[],
-            )
-            .unwrap();
+    /// For the LEDs of a relayed keyboard, like Caps Lock, with `forward_feedback`
+    pub fn add_led_attributes(&self, from: &InputDevice) {
+        if let Ok(bits) = from.handler.led_bits() {
+            self.handler.set_evbit(EventKind::Led).unwrap();
+            for bit in bits.iter() {
+                self.handler.set_ledbit(bit).unwrap();
+            }
+        }
+    }
+
+    /// Sends the LEDs and the force feedback that the applications set on the virtual device to
+    /// the grabbed devices, the effects are uploaded to the first device with force feedback
+    pub fn forward_feedback(&self, debug: bool, to: &[&InputDevice]) {
+        let with = |kind: EventKind| -> Vec<EvdevHandle<File>> {
+            to.iter()
+                .filter(|device| device.handler.event_bits().is_ok_and(|bits| bits.get(kind)))
+                .filter_map(|device| device.handler.as_inner().try_clone().ok())
+                .map(EvdevHandle::new)
+                .collect()
+        };
+        let leds = with(EventKind::Led);
+        let rumble = with(EventKind::ForceFeedback).into_iter().next();
+        if leds.is_empty() && rumble.is_none() {
+            return;
+        }
+        let Ok(file) = self.handler.as_inner().try_clone() else {
+            return;
+        };
+        let uinput = UInputHandle::new(file);
+
+        thread::spawn(move || {
+            use input_linux::sys::{EV_FF, EV_LED, EV_UINPUT, FF_GAIN, UI_FF_ERASE, UI_FF_UPLOAD};
+
+            // The ids of the effects on the virtual device and on the real one
+            let mut ids: HashMap<i16, i16> = HashMap::new();
+            let mut events: [input_event; 16] = unsafe { std::mem::zeroed() };
+            while let Ok(count) = uinput.read(&mut events) {
+                for event in &events[..count] {
+                    if debug {
+                        println!("Feedback event: {event:?}");
+                    }
+                    match (event.type_ as i32, event.code as i32) {
+                        (EV_LED, _) => {
+                            let report = InputEvent::from(SynchronizeEvent::report(
+                                get_current_time(),
+                            ))
+                            .as_raw()
+                            .to_owned();
+                            for device in &leds {
+                                _ = device.write(&[*event, report]);
+                            }
+                        }
+                        (EV_FF, code) => {
+                            let Some(device) = &rumble else { continue };
+                            // The codes from FF_GAIN are settings, the others play an effect
+                            let mut event = *event;
+                            if code < FF_GAIN as i32 {
+                                let Some(id) = ids.get(&(code as i16)) else {
+                                    continue;
+                                };
+                                event.code = *id as u16;
+                            }
+                            _ = device.write(&[event]);
+                        }
+                        (EV_UINPUT, UI_FF_UPLOAD) => {
+                            let mut upload: input_linux::sys::uinput_ff_upload =
+                                unsafe { std::mem::zeroed() };
+                            upload.request_id = event.value as u32;
+                            if uinput.ff_upload_begin(&mut upload).is_err() {
+                                continue;
+                            }
+                            let mut effect = upload.effect;
+                            effect.id = ids.get(&upload.effect.id).copied().unwrap_or(-1);
+                            upload.retval = match rumble
+                                .as_ref()
+                                .map(|device| device.send_force_feedback(&mut effect))
+                            {
+                                Some(Ok(_)) => {
+                                    ids.insert(upload.effect.id, effect.id);
+                                    0
+                                }
+                                Some(Err(err)) => -err.raw_os_error().unwrap_or(libc::EINVAL),
+                                None => -libc::ENOSYS,
+                            };
+                            _ = uinput.ff_upload_end(&upload);
+                        }
+                        (EV_UINPUT, UI_FF_ERASE) => {
+                            let mut erase: input_linux::sys::uinput_ff_erase =
+                                unsafe { std::mem::zeroed() };
+                            erase.request_id = event.value as u32;
+                            if uinput.ff_erase_begin(&mut erase).is_err() {
+                                continue;
+                            }
+                            if let (Some(device), Some(id)) =
+                                (&rumble, ids.remove(&(erase.effect_id as i16)))
+                            {
+                                _ = device.erase_force_feedback(id);
+                            }
+                            _ = uinput.ff_erase_end(&erase);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        });
     }
 
-    pub fn write(&self, events: &[input_event]) -> io::Result<usize> {
+    pub fn create(&self) -> io::Result<()> {
+        self.handler.create(
+            &self.id,
+            self.name.as_bytes(),
+            input_linux::sys::FF_MAX_EFFECTS as u32,
+            &self.abs_setup.lock().unwrap(),
+        )?;
+
+        // The kernel starts with its own repeat rate, it is changed by sending the new one
+        if let Some(repeat) = *self.repeat.lock().unwrap() {
+            let time = get_current_time();
+            let events = [
+                (AutorepeatKind::Delay, repeat.delay),
+                (AutorepeatKind::Period, repeat.period),
+            ]
+            .map(|(kind, value)| {
+                InputEvent::from(AutorepeatEvent::new(time, kind, value as i32))
+                    .as_raw()
+                    .to_owned()
+            });
+            _ = self.handler.write(&events);
+        }
+        Ok(())
+    }
+}
+
+impl OutputBackend for OutputDevice {
+    fn write(&self, events: &[input_event]) -> io::Result<usize> {
         self.handler.write(events)
     }
 
-    pub fn send_key(&self, key: Key, state: KeyState) {
-        let events: [input_event; 2] = [
-            InputEvent::from(KeyEvent::new(get_current_time(), key, state))
-                .as_raw()
-                .to_owned(),
-            InputEvent::from(SynchronizeEvent::report(get_current_time()))
-                .as_raw()
-                .to_owned(),
-        ];
+    fn send_key(&self, key: Key, state: KeyState) {
+        let events = key_events(key, state);
         self.write(&events)
             .expect("Cannot send key event: {events:?}");
     }
+
+    fn send_relative(&self, axes: &[(RelativeAxis, i32)]) {
+        let events = relative_events(axes);
+        self.write(&events)
+            .expect("Cannot send relative event: {events:?}");
+    }
+
+    /// Needs `add_absolute_attributes`
+    fn send_position(&self, x: i32, y: i32) {
+        let events = position_events(x, y);
+        self.write(&events)
+            .expect("Cannot send absolute event: {events:?}");
+    }
+
+    fn send_scroll(&self, notches: i32) {
+        self.send_relative(&scroll_axes(notches));
+    }
+
+    fn destroy(&self) {
+        _ = self.handler.dev_destroy();
+    }
+}
+
+/// A key press or release, in its frame
+pub fn key_events(key: Key, state: KeyState) -> [input_event; 2] {
+    let time = get_current_time();
+    [
+        InputEvent::from(KeyEvent::new(time, key, state))
+            .as_raw()
+            .to_owned(),
+        InputEvent::from(SynchronizeEvent::report(time))
+            .as_raw()
+            .to_owned(),
+    ]
+}
+
+/// The relative axes in one frame
+pub fn relative_events(axes: &[(RelativeAxis, i32)]) -> Vec<input_event> {
+    let time = get_current_time();
+    let mut events: Vec<input_event> = axes
+        .iter()
+        .map(|(axis, value)| {
+            InputEvent::from(RelativeEvent::new(time, *axis, *value))
+                .as_raw()
+                .to_owned()
+        })
+        .collect();
+    events.push(
+        InputEvent::from(SynchronizeEvent::report(time))
+            .as_raw()
+            .to_owned(),
+    );
+    events
+}
+
+/// A position on the screen, in its frame
+pub fn position_events(x: i32, y: i32) -> [input_event; 3] {
+    let time = get_current_time();
+    [
+        InputEvent::from(AbsoluteEvent::new(time, AbsoluteAxis::X, x))
+            .as_raw()
+            .to_owned(),
+        InputEvent::from(AbsoluteEvent::new(time, AbsoluteAxis::Y, y))
+            .as_raw()
+            .to_owned(),
+        InputEvent::from(SynchronizeEvent::report(time))
+            .as_raw()
+            .to_owned(),
+    ]
+}
+
+/// The wheel axes for `notches`, with the high resolution one
+pub fn scroll_axes(notches: i32) -> [(RelativeAxis, i32); 2] {
+    [
+        (RelativeAxis::Wheel, notches),
+        (RelativeAxis::WheelHiRes, notches * 120),
+    ]
 }
 
 pub fn get_current_time() -> EventTime {
@@ -298,3 +847,63 @@ pub fn get_current_time() -> EventTime {
 
     EventTime::new(time.as_secs() as i64, time.subsec_micros() as i64)
 }
+
+#[cfg(test)]
+mod tests {
+    use input_linux::sys::{EV_KEY, EV_MSC, EV_SYN, MSC_SCAN, SYN_REPORT};
+
+    use super::*;
+
+    fn event(type_: i32, code: u16, value: i32) -> input_event {
+        input_event {
+            time: unsafe { std::mem::zeroed() },
+            type_: type_ as u16,
+            code,
+            value,
+        }
+    }
+
+    #[test]
+    fn keyboard_keys_cover_the_keyboards() {
+        let keys: Vec<Key> = keyboard_keys().collect();
+        for key in [
+            Key::Esc,
+            Key::A,
+            Key::LeftCtrl,
+            Key::F12,
+            Key::F24,
+            Key::MicMute,
+            Key::Ok,
+            Key::Fn,
+            Key::BrightnessMin,
+        ] {
+            assert!(keys.contains(&key), "{key:?} is missing");
+        }
+    }
+
+    #[test]
+    fn keyboard_keys_leave_out_the_buttons() {
+        let keys: Vec<Key> = keyboard_keys().collect();
+        for key in [
+            Key::ButtonLeft,
+            Key::ButtonSide,
+            Key::ButtonTrigger,
+            Key::ButtonSouth,
+            Key::ButtonThumbr,
+            Key::ButtonToolPen,
+            Key::ButtonDpadUp,
+            Key::ButtonTriggerHappy1,
+        ] {
+            assert!(!keys.contains(&key), "{key:?} is a button");
+        }
+    }
+
+    #[test]
+    fn only_the_key_repeats_are_dropped() {
+        assert!(is_key_repeat(&event(EV_KEY, Key::A as u16, 2)));
+        assert!(!is_key_repeat(&event(EV_KEY, Key::A as u16, 1)));
+        assert!(!is_key_repeat(&event(EV_KEY, Key::A as u16, 0)));
+        assert!(!is_key_repeat(&event(EV_MSC, MSC_SCAN as u16, 2)));
+        assert!(!is_key_repeat(&event(EV_SYN, SYN_REPORT as u16, 2)));
+    }
+}
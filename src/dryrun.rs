@@ -0,0 +1,61 @@
+use std::{
+    io::{self, stdout, IsTerminal},
+    time::Instant,
+};
+
+use input_linux::{sys::input_event, Key, KeyState, RelativeAxis};
+
+use crate::OutputBackend;
+
+/// Prints what would be sent instead of sending it, with the time since the start, for trying
+/// the options before letting them click
+pub struct DryRunOutput {
+    started: Instant,
+}
+
+impl Default for DryRunOutput {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+        }
+    }
+}
+
+impl DryRunOutput {
+    fn log(&self, what: std::fmt::Arguments) {
+        if stdout().is_terminal() {
+            print!("\x1b[0K");
+        }
+        println!(
+            "[{:>9.3}s] Would {what}",
+            self.started.elapsed().as_secs_f64()
+        );
+    }
+}
+
+impl OutputBackend for DryRunOutput {
+    fn write(&self, events: &[input_event]) -> io::Result<usize> {
+        Ok(events.len())
+    }
+
+    fn send_key(&self, key: Key, state: KeyState) {
+        let action = if state == KeyState::PRESSED {
+            "press"
+        } else {
+            "release"
+        };
+        self.log(format_args!("{action} {key:?}"));
+    }
+
+    fn send_relative(&self, axes: &[(RelativeAxis, i32)]) {
+        self.log(format_args!("move {axes:?}"));
+    }
+
+    fn send_position(&self, x: i32, y: i32) {
+        self.log(format_args!("move to {x}x{y}"));
+    }
+
+    fn send_scroll(&self, notches: i32) {
+        self.log(format_args!("scroll {notches}"));
+    }
+}
@@ -0,0 +1,1070 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
+};
+
+use input_linux::{Key, KeyState, RelativeAxis};
+
+use crate::{
+    args::{
+        ClickMode, ClickOptions, MacroBind, PatternStep, Point, ReactBind, ScreenSize,
+    },
+    macros::{Macro, MacroAction, MacroEvent},
+    print_active,
+    rng::Rng,
+    text,
+    script::{Hook, Script},
+    timing::{self, Timing},
+    AutoclickerState, Beeper, Haptic, Message, OutputBackend, StatusLed,
+};
+
+/// One thing a click does, clicks are played as a sequence of steps
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Press(Key),
+    Release(Key),
+    Scroll(i32),
+    MoveTo(i32, i32),
+    Move(i32, i32),
+    Wait(Duration),
+}
+
+/// What a button sends every time it clicks
+enum Action {
+    Click {
+        key: Key,
+        /// 1 for single clicks, 2 for double clicks...
+        repeat: u8,
+        /// Time between the clicks of a double or triple click
+        gap: Duration,
+    },
+    Pattern(Vec<PatternStep>),
+    /// Recorded events replayed with their timing
+    Macro(Vec<MacroEvent>),
+    /// Pressed once, released only when the button stops
+    Hold(Key),
+    /// Keys to type and if they need shift
+    Text {
+        keys: Vec<(Key, bool)>,
+        delay: Duration,
+    },
+    /// Wheel notches, positive is up
+    Scroll(i32),
+    /// Pressed and released one after the other
+    Sequence(Vec<Key>),
+    /// Pressed, moved along the path, then released
+    Drag {
+        key: Key,
+        path: Vec<Point>,
+        steps: u32,
+        delay: Duration,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl Action {
+    fn from_options(options: &ClickOptions, side: Side) -> Self {
+        let (key, recorded, pattern, actions, text, drag, scroll) = match side {
+            Side::Left => (
+                options.left_output(),
+                &options.left_macro,
+                &options.left_pattern,
+                &options.left_actions,
+                &options.left_text,
+                &options.left_drag,
+                options.left_scroll,
+            ),
+            Side::Right => (
+                options.right_output(),
+                &options.right_macro,
+                &options.right_pattern,
+                &options.right_actions,
+                &options.right_text,
+                &options.right_drag,
+                options.right_scroll,
+            ),
+        };
+
+        if let Some(path) = recorded {
+            Action::Macro(Macro::load_or_exit(path).events)
+        } else if let Some(pattern) = pattern {
+            Action::Pattern(pattern.clone())
+        } else if !actions.is_empty() {
+            Action::Sequence(actions.iter().map(|action| action.key()).collect())
+        } else if let Some(text) = text {
+            Action::Text {
+                keys: text::text_to_keys(text),
+                delay: Duration::from_millis(options.type_delay),
+            }
+        } else if !drag.is_empty() {
+            Action::Drag {
+                key,
+                path: drag.clone(),
+                steps: options.drag_steps.max(1),
+                delay: Duration::from_millis(options.drag_delay),
+            }
+        } else if let Some(notches) = scroll {
+            Action::Scroll(notches)
+        } else if options.click_mode == ClickMode::Hold {
+            Action::Hold(key)
+        } else {
+            Action::Click {
+                key,
+                repeat: options.click_mode.repeat(),
+                gap: Duration::from_millis(options.multi_click_gap),
+            }
+        }
+    }
+
+    /// `press` decides the time between press and release of the clicks
+    fn steps(&self, press: &mut dyn Timing, rng: &mut Rng) -> Vec<Step> {
+        let mut steps = Vec::new();
+        match self {
+            Action::Click { key, repeat, gap } => {
+                for i in 0..(*repeat).max(1) {
+                    if i != 0 {
+                        steps.push(Step::Wait(*gap));
+                    }
+                    steps.extend([
+                        Step::Press(*key),
+                        Step::Wait(press.next_cooldown(rng)),
+                        Step::Release(*key),
+                    ]);
+                }
+            }
+            Action::Hold(key) => steps.push(Step::Press(*key)),
+            Action::Scroll(notches) => steps.push(Step::Scroll(*notches)),
+            Action::Drag {
+                key,
+                path,
+                steps: count,
+                delay,
+            } => {
+                steps.push(Step::Press(*key));
+                for Point(x, y) in path {
+                    // Spread the offset so the small movements add up exactly
+                    let (mut done_x, mut done_y) = (0, 0);
+                    for i in 1..=*count as i32 {
+                        let (to_x, to_y) = (x * i / *count as i32, y * i / *count as i32);
+                        steps.push(Step::Move(to_x - done_x, to_y - done_y));
+                        steps.push(Step::Wait(*delay));
+                        (done_x, done_y) = (to_x, to_y);
+                    }
+                }
+                steps.push(Step::Release(*key));
+            }
+            Action::Sequence(keys) => {
+                for key in keys {
+                    steps.extend([
+                        Step::Press(*key),
+                        Step::Wait(press.next_cooldown(rng)),
+                        Step::Release(*key),
+                    ]);
+                }
+            }
+            Action::Text { keys, delay } => {
+                for (i, (key, shift)) in keys.iter().enumerate() {
+                    if i != 0 {
+                        steps.push(Step::Wait(*delay));
+                    }
+                    if *shift {
+                        steps.push(Step::Press(Key::LeftShift));
+                    }
+                    steps.extend([
+                        Step::Press(*key),
+                        Step::Wait(press.next_cooldown(rng)),
+                        Step::Release(*key),
+                    ]);
+                    if *shift {
+                        steps.push(Step::Release(Key::LeftShift));
+                    }
+                }
+            }
+            Action::Macro(events) => {
+                let mut at = 0;
+                for event in events {
+                    if event.at > at {
+                        steps.push(Step::Wait(Duration::from_millis(event.at - at)));
+                        at = event.at;
+                    }
+                    // The keys are checked when the macro is loaded
+                    steps.extend(match event.action {
+                        MacroAction::Key { key, pressed } => Key::from_code(key)
+                            .ok()
+                            .map(|key| if pressed { Step::Press(key) } else { Step::Release(key) }),
+                        MacroAction::Move { x, y } => Some(Step::Move(x, y)),
+                        MacroAction::Scroll { scroll } => Some(Step::Scroll(scroll)),
+                    });
+                }
+            }
+            Action::Pattern(pattern) => {
+                for step in pattern {
+                    match step {
+                        PatternStep::Click { button, hold } => steps.extend([
+                            Step::Press(button.key()),
+                            Step::Wait(Duration::from_millis(*hold)),
+                            Step::Release(button.key()),
+                        ]),
+                        // The keys are checked when the config is loaded
+                        PatternStep::Key { key, hold } => {
+                            if let Ok(key) = Key::from_code(*key) {
+                                steps.extend([
+                                    Step::Press(key),
+                                    Step::Wait(Duration::from_millis(*hold)),
+                                    Step::Release(key),
+                                ]);
+                            }
+                        }
+                        PatternStep::Wait { wait } => {
+                            steps.push(Step::Wait(Duration::from_millis(*wait)))
+                        }
+                    }
+                }
+            }
+        }
+        steps
+    }
+}
+
+/// Every button has its own schedule, so they can click at different rates
+struct Button {
+    action: Action,
+    timing: Box<dyn Timing>,
+    next_press: Instant,
+    /// Steps left from the click in progress
+    queue: VecDeque<Step>,
+    step_at: Instant,
+    held: Vec<Key>,
+    /// Milliseconds added to every cooldown, from the faster and slower binds
+    adjust: i64,
+}
+
+impl Button {
+    fn new(action: Action, timing: Box<dyn Timing>) -> Self {
+        let now = Instant::now();
+        Self {
+            action,
+            timing,
+            next_press: now,
+            queue: VecDeque::new(),
+            step_at: now,
+            held: Vec::new(),
+            adjust: 0,
+        }
+    }
+
+    fn next_cooldown(&mut self, rng: &mut Rng) -> Duration {
+        let cooldown = self.timing.next_cooldown(rng);
+        let adjust = Duration::from_millis(self.adjust.unsigned_abs());
+        if self.adjust < 0 {
+            cooldown.saturating_sub(adjust)
+        } else {
+            cooldown + adjust
+        }
+    }
+
+    fn busy(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    /// If the button is kept down by the hold mode
+    fn holding(&self) -> bool {
+        matches!(self.action, Action::Hold(_)) && !self.held.is_empty()
+    }
+
+    /// If a new click can start
+    fn ready(&self, now: Instant) -> bool {
+        !self.busy() && !self.holding() && now >= self.next_press
+    }
+
+    /// The next time this button has something to do
+    fn next_event(&self, active: bool) -> Option<Instant> {
+        if self.busy() {
+            Some(self.step_at)
+        } else if active && !self.holding() {
+            Some(self.next_press)
+        } else {
+            None
+        }
+    }
+
+    fn start(&mut self, target: Option<Point>, press: &mut dyn Timing, rng: &mut Rng) {
+        if let Some(Point(x, y)) = target {
+            self.queue.push_back(Step::MoveTo(x, y));
+        }
+        self.queue.extend(self.action.steps(press, rng));
+        self.step_at = Instant::now();
+    }
+
+    /// Plays the steps that are due, and schedules the next click when the sequence ends
+    fn advance(&mut self, output: &dyn OutputBackend, rng: &mut Rng) {
+        while self.busy() && Instant::now() >= self.step_at {
+            match self.queue.pop_front().unwrap() {
+                Step::Press(key) => {
+                    output.send_key(key, KeyState::PRESSED);
+                    self.held.push(key);
+                }
+                Step::Release(key) => {
+                    output.send_key(key, KeyState::RELEASED);
+                    self.held.retain(|held| *held != key);
+                }
+                Step::Scroll(notches) => output.send_scroll(notches),
+                Step::MoveTo(x, y) => output.send_position(x, y),
+                Step::Move(x, y) => {
+                    output.send_relative(&[(RelativeAxis::X, x), (RelativeAxis::Y, y)])
+                }
+                Step::Wait(duration) => self.step_at = Instant::now() + duration,
+            }
+
+            if !self.busy() {
+                self.next_press = Instant::now() + self.next_cooldown(rng);
+            }
+        }
+    }
+
+    /// Aborts the click in progress and releases everything that is still held
+    fn stop(&mut self, output: &dyn OutputBackend, rng: &mut Rng) {
+        if self.queue.is_empty() && self.held.is_empty() {
+            return;
+        }
+        self.queue.clear();
+        for key in self.held.drain(..) {
+            output.send_key(key, KeyState::RELEASED);
+        }
+        self.next_press = Instant::now() + self.next_cooldown(rng);
+    }
+
+    /// Lets a scheduled click pass without sending it
+    fn skip(&mut self, rng: &mut Rng) {
+        self.next_press = Instant::now() + self.next_cooldown(rng);
+    }
+}
+
+/// Where the pointer is moved before the clicks, one after the other
+struct Targets {
+    points: Vec<Point>,
+    next: usize,
+    /// Radius in pixels around the points
+    jitter: f64,
+    screen: Option<ScreenSize>,
+}
+
+impl Targets {
+    /// Picks the next position to click, with the jitter applied
+    fn next(&mut self, rng: &mut Rng) -> Option<Point> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let Point(x, y) = self.points[self.next % self.points.len()];
+        self.next = self.next.wrapping_add(1);
+
+        let (dx, dy) = rng.in_circle(self.jitter);
+        let (mut x, mut y) = (x + dx.round() as i32, y + dy.round() as i32);
+        if let Some(screen) = self.screen {
+            x = x.clamp(0, screen.width - 1);
+            y = y.clamp(0, screen.height - 1);
+        }
+        Some(Point(x, y))
+    }
+}
+
+/// An action played on its own when its bind is pressed, for the macro binds and the multiplier
+struct Player {
+    button: Button,
+    times: u32,
+    while_held: bool,
+    /// Passes left to play
+    remaining: u32,
+}
+
+impl Player {
+    fn from_bind(bind: &MacroBind) -> Self {
+        Self {
+            button: Button::new(
+                Action::Macro(Macro::load_or_exit(&bind.path).events),
+                Box::new(timing::Fixed(Duration::ZERO)),
+            ),
+            times: bind.times.max(1),
+            while_held: bind.while_held,
+            remaining: 0,
+        }
+    }
+
+    /// Clicks `key` `times` times for every physical click, with the cooldown in between
+    fn echo(key: Key, times: u32, cooldown: Duration) -> Self {
+        Self {
+            button: Button::new(
+                Action::Click {
+                    key,
+                    repeat: 1,
+                    gap: Duration::ZERO,
+                },
+                Box::new(timing::Fixed(cooldown)),
+            ),
+            times: times.max(1),
+            while_held: false,
+            remaining: 0,
+        }
+    }
+
+    fn bind(&mut self, pressed: bool, output: &dyn OutputBackend, rng: &mut Rng) {
+        if pressed {
+            self.remaining = if self.while_held { u32::MAX } else { self.times };
+        } else if self.while_held {
+            self.remaining = 0;
+            self.button.stop(output, rng);
+        }
+    }
+}
+
+/// Sends the clicks to the virtual device, following the state from the input thread
+pub struct Engine {
+    buttons: [Button; 2],
+    /// Time between press and release
+    press: Box<dyn Timing>,
+    targets: Targets,
+    /// Held while any button is active
+    modifiers: Vec<Key>,
+    modifiers_down: bool,
+    miss_rate: f64,
+    max_clicks: Option<u64>,
+    max_clicks_exit: bool,
+    duty_cycle: Option<(Duration, Duration)>,
+    run_for: Option<Duration>,
+    /// A bind can unlock the autoclicker, else the end of `run_for` only stops it
+    unlock: bool,
+    /// Wait after a pause ends, and if the first click is right after it instead of a cooldown
+    resume_delay: Duration,
+    resume_click: bool,
+    /// Only click while override keys are held
+    override_invert: bool,
+    /// Change of the cooldown for every press of the faster and slower binds
+    cooldown_step: i64,
+    /// The faster bind stops when this cooldown is reached
+    cooldown_max: Duration,
+    macros: Vec<Player>,
+    /// Multiplied physical left and right clicks, `Message::Echo` uses their indexes
+    echoes: Vec<Player>,
+    /// Clicks of the click and burst binds, `Message::Click` uses their indexes
+    clicks: Vec<Player>,
+    /// Sends the reaction clicks, with the left autoclicker output
+    react: Button,
+    /// Delay of every reaction, `Message::React` uses their indexes
+    react_delays: Vec<Box<dyn Timing>>,
+    /// When the scheduled reaction clicks are due
+    react_queue: Vec<Instant>,
+    script: Option<Script>,
+    /// The other profiles with their names, in the order `Message::Profile` switches to them
+    profiles: VecDeque<(String, Engine)>,
+    led: Option<StatusLed>,
+    haptic: Option<Haptic>,
+}
+
+impl Engine {
+    pub fn from_options(options: &ClickOptions) -> Self {
+        timing::check_cps(options);
+        timing::check_humanize(options);
+        timing::check_drag_click(options);
+
+        Self {
+            buttons: [
+                Button::new(
+                    Action::from_options(options, Side::Left),
+                    timing::from_options(options, options.left_cooldown()),
+                ),
+                Button::new(
+                    Action::from_options(options, Side::Right),
+                    timing::from_options(options, options.right_cooldown()),
+                ),
+            ],
+            press: timing::press_from_options(options),
+            targets: Targets {
+                points: options.positions.clone(),
+                next: 0,
+                jitter: options.position_jitter as f64,
+                screen: options.screen,
+            },
+            modifiers: options.modifiers.iter().map(|modifier| modifier.key()).collect(),
+            modifiers_down: false,
+            miss_rate: options.miss_rate.clamp(0.0, 1.0),
+            max_clicks: options.max_clicks,
+            max_clicks_exit: options.max_clicks_exit,
+            duty_cycle: options
+                .duty_active
+                .zip(options.duty_rest)
+                .map(|(active, rest)| (active.0, rest.0)),
+            run_for: options.run_for.map(|run_for| run_for.0),
+            unlock: true,
+            resume_delay: options.override_resume_delay.map_or(Duration::ZERO, |delay| delay.0),
+            resume_click: options.override_resume_click,
+            override_invert: options.override_invert,
+            cooldown_step: options.cooldown_step as i64,
+            cooldown_max: options.left_cooldown().max(options.right_cooldown()),
+            macros: Vec::new(),
+            echoes: Vec::new(),
+            clicks: Vec::new(),
+            react: Button::new(
+                Action::Click {
+                    key: options.left_output(),
+                    repeat: 1,
+                    gap: Duration::ZERO,
+                },
+                Box::new(timing::Fixed(Duration::ZERO)),
+            ),
+            react_delays: Vec::new(),
+            react_queue: Vec::new(),
+            script: None,
+            profiles: VecDeque::new(),
+            led: None,
+            haptic: None,
+        }
+    }
+
+    /// Adds the macros played by the binds, `Message::Macro` uses their indexes
+    pub fn with_macros(mut self, binds: &[MacroBind]) -> Self {
+        self.macros = binds.iter().map(Player::from_bind).collect();
+        self
+    }
+
+    /// Multiplies every physical left and right click into `times` clicks
+    pub fn with_multiplier(mut self, times: Option<u32>, options: &ClickOptions) -> Self {
+        if let Some(times) = times {
+            self.echoes = vec![
+                Player::echo(Key::ButtonLeft, times, options.left_cooldown()),
+                Player::echo(Key::ButtonRight, times, options.right_cooldown()),
+            ];
+        }
+        self
+    }
+
+    /// Adds the clicks of the click binds, with the button, how many clicks every press sends
+    /// and the time between them, `Message::Click` uses their indexes
+    pub fn with_clicks(mut self, clicks: &[(Key, u32, Duration)]) -> Self {
+        self.clicks = clicks
+            .iter()
+            .map(|(key, times, gap)| Player::echo(*key, *times, *gap))
+            .collect();
+        self
+    }
+
+    /// Adds the delayed clicks of the reactions, `Message::React` uses their indexes
+    pub fn with_reactions(mut self, binds: &[ReactBind]) -> Self {
+        self.react_delays = binds
+            .iter()
+            .map(|bind| -> Box<dyn Timing> {
+                match bind.jitter {
+                    Some(jitter) => Box::new(timing::Uniform {
+                        base: bind.delay.0,
+                        jitter: jitter.0,
+                    }),
+                    None => Box::new(timing::Fixed(bind.delay.0)),
+                }
+            })
+            .collect();
+        self
+    }
+
+    /// Tells the script about the state and the pauses
+    pub fn with_script(mut self, script: Option<Script>) -> Self {
+        self.script = script;
+        self
+    }
+
+    /// Shows the state on a keyboard LED
+    pub fn with_led(mut self, led: Option<StatusLed>) -> Self {
+        self.led = led;
+        self
+    }
+
+    /// Rumbles the input device when the state changes
+    pub fn with_haptic(mut self, haptic: Option<Haptic>) -> Self {
+        self.haptic = haptic;
+        self
+    }
+
+    /// Without a bind that unlocks it the autoclicker is not locked when `run_for` ends
+    pub fn with_unlock(mut self, unlock: bool) -> Self {
+        self.unlock = unlock;
+        self
+    }
+
+    /// Adds the profiles that the profile bind switches to
+    pub fn with_profiles(mut self, profiles: Vec<(String, Engine)>) -> Self {
+        self.profiles = profiles.into();
+        self
+    }
+
+    /// Stops every click and macro in progress, before switching to another profile
+    fn stop_all(&mut self, output: &dyn OutputBackend, rng: &mut Rng) {
+        for button in self.buttons.iter_mut() {
+            button.stop(output, rng);
+        }
+        for player in self
+            .macros
+            .iter_mut()
+            .chain(self.echoes.iter_mut())
+            .chain(self.clicks.iter_mut())
+        {
+            player.remaining = 0;
+            player.button.stop(output, rng);
+        }
+        self.react_queue.clear();
+        self.react.stop(output, rng);
+        self.set_modifiers(false, output);
+    }
+
+    fn set_modifiers(&mut self, down: bool, output: &dyn OutputBackend) {
+        if self.modifiers_down == down {
+            return;
+        }
+        self.modifiers_down = down;
+        let state = if down {
+            KeyState::PRESSED
+        } else {
+            KeyState::RELEASED
+        };
+        for key in &self.modifiers {
+            output.send_key(*key, state);
+        }
+    }
+
+    /// `feedback` is used to tell the input thread when the engine changes the state by itself
+    pub fn run(
+        mut self,
+        beep: Option<&Beeper>,
+        receiver: Receiver<Message>,
+        feedback: Sender<AutoclickerState>,
+        output: &dyn OutputBackend,
+    ) {
+        let mut rng = Rng::from_time();
+        let mut toggle = AutoclickerState::default();
+        let mut clicks = 0;
+        let mut total_clicks = 0;
+        let mut started: Option<Instant> = None;
+        let mut deadline: Option<Instant> = None;
+        // When the current duty cycle phase ends
+        let mut phase_end: Option<Instant> = None;
+        // Sources that currently pause the clicking, and the held override keys when they enable it
+        let mut overrides = HashSet::new();
+        let mut enabling = HashSet::new();
+        // What pauses the clicking for the status line, and how many times each source paused
+        let mut paused_by = String::from("no override key");
+        let mut pause_counts: HashMap<String, u32> = HashMap::new();
+        // Last state sent to the script
+        let mut reported = None;
+        let mut profiles = std::mem::take(&mut self.profiles);
+        // Stays with the state when the profiles switch
+        let mut led = self.led.take();
+        let haptic = self.haptic.take();
+        let unlock = self.unlock;
+        let mut profile = String::from("default");
+        toggle.paused = self.override_invert;
+        println!();
+        print_active(&toggle, &paused_by);
+
+        loop {
+            if let Some(script) = self.script.as_ref().filter(|_| reported != Some(toggle)) {
+                _ = script.send(toggle.into());
+                reported = Some(toggle);
+            }
+            if let Some(led) = led.as_mut() {
+                led.show(&toggle);
+            }
+
+            let active = [toggle.left, toggle.right].map(|active| active && toggle.clicking());
+            let wake_at = self
+                .buttons
+                .iter()
+                .zip(active)
+                .filter_map(|(button, active)| button.next_event(active))
+                .chain(
+                    self.macros
+                        .iter()
+                        .chain(&self.echoes)
+                        .chain(&self.clicks)
+                        .filter_map(|player| player.button.next_event(player.remaining > 0)),
+                )
+                .chain(self.react.next_event(false))
+                .chain(self.react_queue.iter().min().copied())
+                .chain(deadline)
+                .chain(phase_end)
+                .min();
+
+            let recv = match wake_at {
+                Some(wake_at) => {
+                    match receiver.recv_timeout(wake_at.saturating_duration_since(Instant::now())) {
+                        Ok(recv) => Some(recv),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                None => match receiver.recv() {
+                    Ok(recv) => Some(recv),
+                    Err(_) => return,
+                },
+            };
+
+            match recv {
+                Some(Message::Override {
+                    source,
+                    paused,
+                    keys,
+                }) => {
+                    let inverted = keys && self.override_invert;
+                    let count = pause_counts.entry(source.clone()).or_default();
+                    if paused != inverted {
+                        *count += 1;
+                    }
+                    if let Some(script) = &self.script {
+                        _ = script.send(Hook::Override {
+                            source: &source,
+                            paused: paused != inverted,
+                            count: *count,
+                        });
+                    }
+                    let sources = if inverted {
+                        &mut enabling
+                    } else {
+                        &mut overrides
+                    };
+                    if paused {
+                        sources.insert(source);
+                    } else {
+                        sources.remove(&source);
+                    }
+                    let not_enabled = self.override_invert && enabling.is_empty();
+                    let paused = !overrides.is_empty() || not_enabled;
+                    let mut sources: Vec<_> = overrides.iter().map(String::as_str).collect();
+                    sources.sort_unstable();
+                    if not_enabled {
+                        sources.push("no override key");
+                    }
+                    let sources = sources.join(", ");
+                    if paused && toggle.paused && sources != paused_by {
+                        paused_by = sources;
+                        print_active(&toggle, &paused_by);
+                    } else if toggle.paused != paused {
+                        paused_by = sources;
+                        toggle.paused = paused;
+                        if paused {
+                            toggle.pauses += 1;
+                        }
+                        let now = Instant::now();
+                        for button in self.buttons.iter_mut() {
+                            if toggle.paused {
+                                button.stop(output, &mut rng);
+                            } else if self.resume_click {
+                                button.next_press = now + self.resume_delay;
+                            } else {
+                                button.next_press =
+                                    now + self.resume_delay + button.next_cooldown(&mut rng);
+                            }
+                        }
+                        if toggle.paused {
+                            for player in self.macros.iter_mut().chain(self.clicks.iter_mut()) {
+                                player.remaining = 0;
+                                player.button.stop(output, &mut rng);
+                            }
+                            self.react_queue.clear();
+                            self.react.stop(output, &mut rng);
+                        }
+                        print_active(&toggle, &paused_by);
+                    }
+                }
+                Some(Message::Macro { index, pressed }) => {
+                    if let Some(player) = self.macros.get_mut(index) {
+                        player.bind(pressed && !toggle.paused, output, &mut rng);
+                    }
+                }
+                Some(Message::Cooldown { faster }) => {
+                    let step = if faster {
+                        -self.cooldown_step
+                    } else {
+                        self.cooldown_step
+                    };
+                    toggle.cooldown_adjust = (toggle.cooldown_adjust + step)
+                        .max(-(self.cooldown_max.as_millis() as i64));
+                    for button in self.buttons.iter_mut() {
+                        button.adjust = toggle.cooldown_adjust;
+                    }
+                    print_active(&toggle, &paused_by);
+                }
+                Some(Message::Click { index }) if !toggle.paused => {
+                    if let Some(player) = self.clicks.get_mut(index) {
+                        player.bind(true, output, &mut rng);
+                    }
+                }
+                Some(Message::Profile { steps }) if !profiles.is_empty() => {
+                    self.stop_all(output, &mut rng);
+                    for _ in 0..steps {
+                        let Some((name, mut next)) = profiles.pop_front() else {
+                            break;
+                        };
+                        next.script = self.script.take();
+                        std::mem::swap(&mut self, &mut next);
+                        profiles.push_back((std::mem::replace(&mut profile, name), next));
+                    }
+
+                    // The cooldown of the new profile starts without the adjustment
+                    let now = Instant::now();
+                    for button in self.buttons.iter_mut() {
+                        button.next_press = now;
+                        button.adjust = 0;
+                    }
+                    toggle.cooldown_adjust = 0;
+                    println!("\x1b[0KSwitched to the profile {profile}");
+                    print_active(&toggle, &paused_by);
+                }
+                Some(Message::Echo { index }) => {
+                    // While paused the click goes through as it is
+                    if let Some(player) = self.echoes.get_mut(index) {
+                        let times = if toggle.paused { 1 } else { player.times };
+                        player.remaining = player.remaining.saturating_add(times);
+                    }
+                }
+                Some(Message::React { index }) if !toggle.paused => {
+                    if let Some(delay) = self.react_delays.get_mut(index) {
+                        self.react_queue
+                            .push(Instant::now() + delay.next_cooldown(&mut rng));
+                    }
+                }
+                Some(Message::Deactivate) if toggle.left | toggle.right => {
+                    for button in self.buttons.iter_mut() {
+                        button.stop(output, &mut rng);
+                    }
+                    self.set_modifiers(false, output);
+                    phase_end = None;
+                    toggle.left = false;
+                    toggle.right = false;
+                    toggle.resting = false;
+                    _ = feedback.send(toggle);
+                    print_active(&toggle, &paused_by);
+                }
+                Some(Message::State(mut recv)) => {
+                    let now = Instant::now();
+                    if !(toggle.left | toggle.right) {
+                        clicks = 0;
+                        phase_end = self.duty_cycle.map(|(active, _)| now + active);
+                    }
+                    if !(recv.left | recv.right) {
+                        phase_end = None;
+                    }
+                    recv.resting = toggle.resting && phase_end.is_some();
+                    recv.paused = toggle.paused;
+                    recv.pauses = toggle.pauses;
+                    recv.cooldown_adjust = toggle.cooldown_adjust;
+                    for (button, (was, is)) in self
+                        .buttons
+                        .iter_mut()
+                        .zip([(toggle.left, recv.left), (toggle.right, recv.right)])
+                    {
+                        if !was && is {
+                            button.next_press = now;
+                        } else if was && !is {
+                            button.stop(output, &mut rng);
+                        }
+                    }
+                    toggle = recv;
+
+                    if let Some(beep) = beep {
+                        beep.beep();
+                    }
+                    if let Some(haptic) = &haptic {
+                        haptic.play();
+                    }
+
+                    print_active(&toggle, &paused_by);
+
+                    if started.is_none() && (toggle.left | toggle.right) {
+                        started = Some(now);
+                        deadline = self.run_for.map(|run_for| now + run_for);
+                    }
+                }
+                _ => {}
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                deadline = None;
+                phase_end = None;
+                for button in self.buttons.iter_mut() {
+                    button.stop(output, &mut rng);
+                }
+                self.set_modifiers(false, output);
+                toggle.left = false;
+                toggle.right = false;
+                toggle.lock = unlock;
+                toggle.resting = false;
+                _ = feedback.send(toggle);
+
+                // The next activation after the unlock starts a new session
+                let elapsed = started
+                    .take()
+                    .map(|started| started.elapsed())
+                    .unwrap_or_default();
+                println!(
+                    "\x1b[0KSession ended after {:.1}s, sent {total_clicks} clicks",
+                    elapsed.as_secs_f64()
+                );
+                total_clicks = 0;
+                print_active(&toggle, &paused_by);
+                continue;
+            }
+
+            if let Some((active, rest)) = self.duty_cycle {
+                let now = Instant::now();
+                if phase_end.is_some_and(|phase_end| now >= phase_end) {
+                    toggle.resting = !toggle.resting;
+                    if toggle.resting {
+                        for button in self.buttons.iter_mut() {
+                            button.stop(output, &mut rng);
+                        }
+                        phase_end = Some(now + rest);
+                    } else {
+                        for button in self.buttons.iter_mut() {
+                            button.next_press = now;
+                        }
+                        phase_end = Some(now + active);
+                    }
+                    print_active(&toggle, &paused_by);
+                }
+            }
+
+            let now = Instant::now();
+            let max_clicks = self.max_clicks;
+            let limit_reached = |clicks| max_clicks.is_some_and(|max| clicks >= max);
+            let active = [toggle.left, toggle.right].map(|active| active && toggle.clicking());
+            if active.contains(&true) {
+                self.set_modifiers(true, output);
+            }
+            for (button, active) in self.buttons.iter_mut().zip(active) {
+                button.advance(output, &mut rng);
+
+                if active && button.ready(now) {
+                    if limit_reached(clicks) {
+                        continue;
+                    }
+                    if self.miss_rate > 0.0 && rng.next_f64() < self.miss_rate {
+                        button.skip(&mut rng);
+                        continue;
+                    }
+                    let target = self.targets.next(&mut rng);
+                    button.start(target, self.press.as_mut(), &mut rng);
+                    button.advance(output, &mut rng);
+                    clicks += 1;
+                    total_clicks += 1;
+                }
+            }
+
+            for player in self
+                .macros
+                .iter_mut()
+                .chain(self.echoes.iter_mut())
+                .chain(self.clicks.iter_mut())
+            {
+                player.button.advance(output, &mut rng);
+
+                if player.remaining > 0 && player.button.ready(now) {
+                    if !player.while_held {
+                        player.remaining -= 1;
+                    }
+                    player.button.start(None, self.press.as_mut(), &mut rng);
+                    player.button.advance(output, &mut rng);
+                }
+            }
+
+            // A reaction that is due while the previous click is still held waits for it
+            self.react.advance(output, &mut rng);
+            let now = Instant::now();
+            if let Some(due) = self.react_queue.iter().position(|at| *at <= now) {
+                if !self.react.busy() {
+                    self.react_queue.swap_remove(due);
+                    self.react.start(None, self.press.as_mut(), &mut rng);
+                    self.react.advance(output, &mut rng);
+                }
+            }
+
+            let idle = self.buttons.iter().all(|button| !button.busy());
+            if idle && !active.contains(&true) {
+                self.set_modifiers(false, output);
+            }
+            if limit_reached(clicks) && idle && (toggle.left | toggle.right) {
+                self.set_modifiers(false, output);
+                phase_end = None;
+                toggle.left = false;
+                toggle.right = false;
+                toggle.resting = false;
+                _ = feedback.send(toggle);
+
+                println!("\x1b[0KSent {clicks} clicks, the limit was reached");
+                if self.max_clicks_exit {
+                    std::process::exit(0);
+                }
+                print_active(&toggle, &paused_by);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use super::*;
+    use crate::{args::HumanDuration, DryRunOutput};
+
+    /// Runs an engine with `--run-for 50ms`, and the states it sends back
+    fn run_for(unlock: bool) -> (Sender<Message>, Receiver<AutoclickerState>) {
+        let options = ClickOptions {
+            run_for: Some(HumanDuration(Duration::from_millis(50))),
+            ..ClickOptions::default()
+        };
+        let engine = Engine::from_options(&options).with_unlock(unlock);
+        let (sender, receiver) = mpsc::channel();
+        let (feedback, states) = mpsc::channel();
+        thread::spawn(move || engine.run(None, receiver, feedback, &DryRunOutput::default()));
+        (sender, states)
+    }
+
+    fn activate(sender: &Sender<Message>) {
+        let state = AutoclickerState {
+            left: true,
+            ..AutoclickerState::default()
+        };
+        sender.send(Message::State(state)).unwrap();
+    }
+
+    fn ended(states: &Receiver<AutoclickerState>) -> AutoclickerState {
+        states.recv_timeout(Duration::from_secs(5)).unwrap()
+    }
+
+    #[test]
+    fn run_for_locks_when_a_bind_unlocks() {
+        let (sender, states) = run_for(true);
+        activate(&sender);
+        let state = ended(&states);
+        assert!(!state.left && !state.right);
+        assert!(state.lock);
+    }
+
+    #[test]
+    fn run_for_only_stops_without_an_unlock_bind() {
+        let (sender, states) = run_for(false);
+        activate(&sender);
+        let state = ended(&states);
+        assert!(!state.left && !state.right);
+        assert!(!state.lock);
+
+        // The next activation is a new session, with its own deadline
+        let started = Instant::now();
+        activate(&sender);
+        let state = ended(&states);
+        assert!(!state.left && !state.lock);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+}
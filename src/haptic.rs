@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use input_linux::{EventKind, ForceFeedbackKind};
+
+use crate::{args::ClickOptions, InputDevice};
+
+/// A short rumble on the device with force feedback, a silent alternative to the beep
+pub struct Haptic {
+    device: InputDevice,
+    effect: i16,
+}
+
+impl Haptic {
+    /// Uploads the effect to the first device that can rumble or play a sine wave, none of them
+    /// is a warning
+    pub fn from_options(options: &ClickOptions, devices: &[&InputDevice]) -> Option<Self> {
+        if !options.haptic {
+            return None;
+        }
+        let haptic = devices
+            .iter()
+            .filter(|device| {
+                device
+                    .handler
+                    .event_bits()
+                    .is_ok_and(|bits| bits.get(EventKind::ForceFeedback))
+                    && device.handler.force_feedback_bits().is_ok_and(|bits| {
+                        bits.get(ForceFeedbackKind::Rumble)
+                            || (bits.get(ForceFeedbackKind::Periodic)
+                                && bits.get(ForceFeedbackKind::Sine))
+                    })
+            })
+            .find_map(|device| {
+                let device = device.try_clone().ok()?;
+                let effect = device.upload_rumble(Duration::from_millis(80)).ok()?;
+                Some(Self { device, effect })
+            });
+        if haptic.is_none() {
+            eprintln!(
+                "\x1B[1;33mWarning: no input or override device can rumble for --haptic\x1B[0;39m"
+            );
+        }
+        haptic
+    }
+
+    pub fn play(&self) {
+        _ = self.device.play_effect(self.effect);
+    }
+}
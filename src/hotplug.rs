@@ -0,0 +1,135 @@
+use std::{
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    path::PathBuf,
+    time::Duration,
+};
+
+use input_linux::InputId;
+
+use crate::InputDevice;
+
+/// Checked again this often when the kernel events are missed or not available
+const RETRY: Duration = Duration::from_secs(1);
+
+/// Kernel uevents of the udev monitor, the multicast group of the kernel
+const UEVENT_GROUP: u32 = 1;
+
+/// Waits for the input devices to come back, woken by the kernel uevents when a device is added
+pub struct Hotplug {
+    socket: Option<OwnedFd>,
+}
+
+impl Hotplug {
+    pub fn new() -> Self {
+        Self {
+            socket: uevent_socket(),
+        }
+    }
+
+    /// Blocks until the device of the query can be opened again, `was` is the device before it
+    /// was lost
+    pub fn wait_for(&self, query: &str, was: &Identity) -> InputDevice {
+        loop {
+            if let Some(device) = reopen(query, was) {
+                return device;
+            }
+            self.wait_added();
+        }
+    }
+
+    /// Waits for an input device to be added, or for `RETRY`
+    pub fn wait_added(&self) {
+        let Some(socket) = &self.socket else {
+            std::thread::sleep(RETRY);
+            return;
+        };
+
+        let mut poll = libc::pollfd {
+            fd: socket.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let mut buffer = [0u8; 4096];
+        while unsafe { libc::poll(&mut poll, 1, RETRY.as_millis() as i32) } > 0 {
+            let len = unsafe {
+                libc::recv(
+                    socket.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                    0,
+                )
+            };
+            if len <= 0 {
+                return;
+            }
+            // Like `add@/devices/.../input/input21/event5`, the other fields follow
+            let header = buffer[..len as usize].split(|byte| *byte == 0).next();
+            let header = String::from_utf8_lossy(header.unwrap_or_default());
+            if header.starts_with("add@") && header.contains("/event") {
+                return;
+            }
+        }
+    }
+}
+
+impl Default for Hotplug {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What tells a device from another one, like the one that gets its `/dev/input/eventN` after
+/// a reconnect
+#[derive(Clone, PartialEq)]
+pub struct Identity {
+    name: String,
+    id: InputId,
+}
+
+impl Identity {
+    pub fn of(device: &InputDevice) -> Self {
+        Self {
+            name: device.name.clone(),
+            id: device.id,
+        }
+    }
+}
+
+/// Like `input_device_from_query`, without exiting when the device is not there. A path is only
+/// taken by the same device, another one may have its node now
+pub fn reopen(query: &str, was: &Identity) -> Option<InputDevice> {
+    if query.starts_with('/') {
+        let path = PathBuf::from(query);
+        path.exists()
+            .then(|| InputDevice::dev_open(path).ok())
+            .flatten()
+            .filter(|device| Identity::of(device) == *was)
+    } else {
+        InputDevice::find_device(query)
+    }
+}
+
+/// The netlink socket of the kernel uevents, `None` when it cannot be opened
+fn uevent_socket() -> Option<OwnedFd> {
+    unsafe {
+        let fd = libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_KOBJECT_UEVENT,
+        );
+        if fd < 0 {
+            return None;
+        }
+        let socket = OwnedFd::from_raw_fd(fd);
+
+        let mut address: libc::sockaddr_nl = std::mem::zeroed();
+        address.nl_family = libc::AF_NETLINK as u16;
+        address.nl_groups = UEVENT_GROUP;
+        let bound = libc::bind(
+            fd,
+            (&address as *const libc::sockaddr_nl).cast(),
+            std::mem::size_of::<libc::sockaddr_nl>() as u32,
+        );
+        (bound == 0).then_some(socket)
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use input_linux::RelativeAxis;
+
+use crate::{args::ClickOptions, OutputBackend};
+
+/// When the last real input was seen
+#[derive(Clone)]
+pub struct Activity(Arc<Mutex<Instant>>);
+
+impl Activity {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    pub fn touch(&self) {
+        *self.0.lock().unwrap() = Instant::now();
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.0.lock().unwrap().elapsed()
+    }
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Moves the pointer back and forth by a few pixels to keep the session alive
+pub struct Jiggler {
+    interval: Duration,
+    distance: i32,
+    /// Only jiggle when there was no real input for this long
+    idle: Option<Duration>,
+}
+
+impl Jiggler {
+    pub fn from_options(options: &ClickOptions) -> Option<Self> {
+        options.jiggle.map(|interval| Self {
+            interval: interval.0,
+            distance: options.jiggle_distance.max(1),
+            idle: options.jiggle_idle.map(|idle| idle.0),
+        })
+    }
+
+    pub fn spawn(self, output: Arc<dyn OutputBackend>, activity: Activity) {
+        thread::spawn(move || {
+            let mut direction = 1;
+            loop {
+                thread::sleep(self.interval);
+
+                if self.idle.is_some_and(|idle| activity.idle_for() < idle) {
+                    continue;
+                }
+
+                let distance = self.distance * direction;
+                output.send_relative(&[(RelativeAxis::X, distance), (RelativeAxis::Y, distance)]);
+                thread::sleep(Duration::from_millis(20));
+                output.send_relative(&[(RelativeAxis::X, -distance), (RelativeAxis::Y, -distance)]);
+                direction = -direction;
+            }
+        });
+    }
+}
@@ -0,0 +1,73 @@
+use input_linux::{sys::input_event, InputEvent, LedEvent, LedKind, SynchronizeEvent};
+
+use crate::{
+    args::{ClickOptions, LedShows},
+    device::get_current_time,
+    AutoclickerState, InputDevice,
+};
+
+/// Shows the state on a keyboard LED, like Scroll Lock, the compositor may set it back when a
+/// lock key is pressed
+pub struct StatusLed {
+    devices: Vec<InputDevice>,
+    led: LedKind,
+    locked: bool,
+    /// What the LED was last set to
+    lit: Option<bool>,
+}
+
+impl StatusLed {
+    /// Uses the devices that have the LED, none of them is a warning
+    pub fn from_options(options: &ClickOptions, devices: &[&InputDevice]) -> Option<Self> {
+        let led = options.led?;
+        let devices: Vec<_> = devices
+            .iter()
+            .filter(|device| {
+                device
+                    .handler
+                    .led_bits()
+                    .is_ok_and(|bits| bits.get(led.kind()))
+            })
+            .filter_map(|device| device.try_clone().ok())
+            .collect();
+        if devices.is_empty() {
+            eprintln!(
+                "\x1B[1;33mWarning: no input or override device has the {led} lock LED\x1B[0;39m"
+            );
+            return None;
+        }
+
+        Some(Self {
+            devices,
+            led: led.kind(),
+            locked: options.led_shows == LedShows::Locked,
+            lit: None,
+        })
+    }
+
+    /// Only writes to the devices when the LED changes
+    pub fn show(&mut self, state: &AutoclickerState) {
+        let lit = if self.locked {
+            state.lock
+        } else {
+            state.left || state.right
+        };
+        if self.lit == Some(lit) {
+            return;
+        }
+        self.lit = Some(lit);
+
+        let time = get_current_time();
+        let events: [input_event; 2] = [
+            InputEvent::from(LedEvent::new(time, self.led, lit as i32))
+                .as_raw()
+                .to_owned(),
+            InputEvent::from(SynchronizeEvent::report(time))
+                .as_raw()
+                .to_owned(),
+        ];
+        for device in &self.devices {
+            _ = device.handler.write(&events);
+        }
+    }
+}
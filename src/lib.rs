@@ -1,18 +1,83 @@
+mod afk;
 mod args;
+mod backend;
+mod beep;
+mod config;
+mod control;
+mod daemon;
 mod device;
-
-pub use args::{Args, Config, ConfigCommand};
+mod dryrun;
+mod engine;
+mod hotplug;
+mod jiggler;
+mod haptic;
+mod led;
+mod macros;
+mod overrides;
+mod panic_bind;
+mod pause;
+mod plugin;
+mod raw;
+mod recover;
+mod regex;
+mod relay;
+mod rng;
+mod schema;
+mod script;
+mod session;
+mod split;
+mod text;
+mod timing;
+mod toml;
+mod wayland;
+mod window;
+
+pub use args::{
+    Args, Backend, BeepSound, Bind, ConfigAction, BindMode, Binding, BindingAction, Bus, ClickOptions,
+    DeviceId, Led, LedShows, MacroBind, OverrideLost, ReactBind, StartActive,
+};
+pub use config::{Config, ConfigCommand};
 
 use std::{
-    io::{stdout, BufRead, IsTerminal, Write},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    io::{self, stdout, BufRead, IsTerminal, Write},
     os::fd::AsRawFd,
-    path::PathBuf,
-    sync::{mpsc, Arc},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+pub use afk::AntiAfk;
+pub use backend::OutputBackend;
+pub use beep::Beeper;
+pub use control::ControlSocket;
+use device::{is_key_repeat, is_keyboard_key};
 pub use device::{DeviceType, InputDevice, OutputDevice};
+pub use dryrun::DryRunOutput;
+pub use engine::Engine;
+pub use hotplug::{Hotplug, Identity};
+pub use jiggler::{Activity, Jiggler};
+pub use haptic::Haptic;
+pub use led::StatusLed;
+pub use macros::{Macro, MacroAction, MacroEvent};
+pub use overrides::{OverrideKeys, Overrides};
+pub use panic_bind::PanicBind;
+pub use pause::PauseSource;
+pub use plugin::Plugin;
+use regex::DeviceRegex;
+pub use raw::RawRecorder;
+pub use recover::{CreateOutput, RecoveringOutput};
+pub use relay::Relay;
+pub use script::{Hook, Script};
+pub use session::SessionWatch;
+pub use split::SplitOutput;
+pub use timing::Timing;
+pub use wayland::WaylandOutput;
+pub use window::WindowRules;
 use input_linux::{sys::input_event, Key, KeyState};
 
 const WAIT_KEY_RELEASE: std::time::Duration = std::time::Duration::from_millis(100);
@@ -40,48 +105,548 @@ pub struct AutoclickerState {
 
     // If is locked
     lock: bool,
+
+    // If the duty cycle is resting, only set by the engine
+    resting: bool,
+
+    // If a pause source like the focused window pauses the clicking, only set by the engine
+    paused: bool,
+
+    // How many times the pause sources paused the clicking, only set by the engine
+    pauses: u32,
+
+    // Milliseconds added to the cooldown by the faster and slower binds, only set by the engine
+    cooldown_adjust: i64,
 }
 
-pub struct StateNormal {
-    left_bind: u16,
-    right_bind: u16,
-    lock_unlock_bind: Option<u16>,
+impl AutoclickerState {
+    /// Not resting and not paused
+    fn clicking(&self) -> bool {
+        !self.resting && !self.paused
+    }
+
+    /// Applies a press or release of the left or right bind, `hold` is the mode of each bind
+    fn press_bind(&mut self, action: BindAction, hold: [bool; 2], pressed: bool) {
+        let (state, hold) = if action == BindAction::Left {
+            (&mut self.left, hold[0])
+        } else {
+            (&mut self.right, hold[1])
+        };
+        if hold {
+            if pressed != *state {
+                *state = pressed;
+            }
+        } else if pressed {
+            *state = !*state;
+        }
+    }
+
+    /// Fires the action of a bind that triggers on the release, once per release
+    fn release_bind(&mut self, action: BindAction, transmitter: &Sender<Message>) {
+        match action {
+            BindAction::Left | BindAction::Right => self.press_bind(action, [false; 2], true),
+            BindAction::Lock => self.lock = !self.lock,
+            // Only works while held
+            BindAction::Enable => {}
+            // Switched by the input thread
+            BindAction::Profile => {}
+            BindAction::Macro(index) => {
+                _ = transmitter.send(Message::Macro {
+                    index,
+                    pressed: true,
+                });
+                _ = transmitter.send(Message::Macro {
+                    index,
+                    pressed: false,
+                });
+            }
+            BindAction::React(index) => _ = transmitter.send(Message::React { index }),
+            BindAction::Click(index) => _ = transmitter.send(Message::Click { index }),
+            BindAction::Cooldown { faster } => _ = transmitter.send(Message::Cooldown { faster }),
+            // Never triggered on the release
+            BindAction::Echo(_) | BindAction::Plugin(_) => {}
+        }
+    }
+}
+
+/// What the engine receives
+pub enum Message {
+    /// New state from the input thread
+    State(AutoclickerState),
+    /// A pause source started or stopped pausing the clicking, `keys` when it is override keys
+    /// being held
+    Override {
+        source: String,
+        paused: bool,
+        keys: bool,
+    },
+    /// Deactivates the left and right autoclickers, like the binds would
+    Deactivate,
+    /// The bind of the macro with this index was pressed or released
+    Macro { index: usize, pressed: bool },
+    /// The key of the reaction with this index was pressed
+    React { index: usize },
+    /// A physical click to multiply, 0 is the left button and 1 the right
+    Echo { index: usize },
+    /// The key of the click or burst bind with this index was pressed
+    Click { index: usize },
+    /// The faster or slower bind was pressed
+    Cooldown { faster: bool },
+    /// The profile bind was pressed or the control socket asked for a profile, the engine
+    /// switches this many profiles forward
+    Profile { steps: usize },
+}
+
+/// What a bind does with its key
+#[derive(Clone, Copy, PartialEq)]
+enum BindAction {
+    Left,
+    Right,
+    Lock,
+    /// Unlocks while held
+    Enable,
+    /// Switches to the next profile
+    Profile,
+    /// Plays the macro with this index in the engine
+    Macro(usize),
+    /// Schedules the reaction click with this index in the engine
+    React(usize),
+    /// Sends the clicks of the click bind with this index in the engine
+    Click(usize),
+    /// A physical click multiplied by the engine, unless a bind already used the key
+    Echo(usize),
+    /// Handled by the plugin with this index
+    Plugin(usize),
+    /// Shortens or lengthens the cooldown
+    Cooldown { faster: bool },
+}
+
+impl BindAction {
+    /// The lock and enable binds lock the other binds
+    fn locks(&self) -> bool {
+        matches!(self, BindAction::Lock | BindAction::Enable)
+    }
+}
 
-    hold: bool,
+/// A bind of the input thread
+#[derive(Clone)]
+struct InputBind {
+    bind: Bind,
+    action: BindAction,
+    /// Fires the action once when the key is released, the press does nothing
+    on_release: bool,
+}
+
+impl InputBind {
+    fn new(bind: Bind, action: BindAction) -> Self {
+        Self {
+            bind,
+            action,
+            on_release: false,
+        }
+    }
+}
+
+/// Detects a sharp movement from the relative events of the device
+struct Gesture {
+    x: i32,
+    y: i32,
+    since: Instant,
+}
+
+impl Default for Gesture {
+    fn default() -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            since: Instant::now(),
+        }
+    }
+}
+
+impl Gesture {
+    /// Distance to move within `TIME` for a gesture
+    const DISTANCE: i32 = 300;
+    const TIME: Duration = Duration::from_millis(150);
+
+    /// Adds a movement, and gives the gesture key when the movement is far enough
+    fn push(&mut self, axis: u16, value: i32) -> Option<u16> {
+        if self.since.elapsed() > Self::TIME {
+            *self = Self::default();
+        }
+        match axis as i32 {
+            input_linux::sys::REL_X => self.x += value,
+            input_linux::sys::REL_Y => self.y += value,
+            _ => return None,
+        }
+        let key = if self.x.abs() >= self.y.abs() {
+            match self.x {
+                x if x >= Self::DISTANCE => args::GESTURE_RIGHT,
+                x if x <= -Self::DISTANCE => args::GESTURE_LEFT,
+                _ => return None,
+            }
+        } else {
+            match self.y {
+                y if y >= Self::DISTANCE => args::GESTURE_DOWN,
+                y if y <= -Self::DISTANCE => args::GESTURE_UP,
+                _ => return None,
+            }
+        };
+        *self = Self::default();
+        Some(key)
+    }
+}
+
+pub struct StateNormal {
+    binds: Vec<InputBind>,
+    record_raw: Option<RawRecorder>,
+    /// Exits right away, even when locked
+    panic_bind: Option<PanicBind>,
+    /// Another device for the binds, with its query to open it again
+    bind_device: Option<(String, InputDevice)>,
+    /// Read like the input device
+    extra_inputs: Vec<(String, InputDevice)>,
+    /// The devices that come back are not grabbed again
+    no_regrab: bool,
+    /// If the left and right autoclickers are active on launch
+    start_active: [bool; 2],
+    /// Countdown before the binds work
+    start_delay: Option<Duration>,
+    /// Pause while one of these is held on the input device, empty without `--override-main`
+    override_keys: Vec<Bind>,
+    /// Multiply the physical clicks
+    multiply: bool,
+    /// Window for the second tap of the left and right binds
+    double_tap: Option<Duration>,
+    /// How long the left and right binds have to be held to trigger
+    long_press: Option<Duration>,
+
+    /// Hold mode of the left and right binds
+    hold: [bool; 2],
+    /// The binds, hold modes and grab of the other profiles, in the order they are switched to
+    profiles: Vec<ProfileBinds>,
     grab: bool,
+    /// Switches the profiles without the profile bind
+    control: Option<ControlSocket>,
 
-    cooldown: Duration,
-    cooldown_pr: Duration,
+    engine: Engine,
 }
 
 impl StateNormal {
-    pub fn run(self, shared: Shared) {
-        let (transmitter, receiver) = mpsc::channel::<AutoclickerState>();
+    pub fn run(self, shared: Shared, transmitter: Sender<Message>, receiver: Receiver<Message>) {
+        let (feedback_tx, feedback_rx) = mpsc::channel::<AutoclickerState>();
 
-        let mut events: [input_event; 1] = unsafe { std::mem::zeroed() };
-        let input = shared.input;
+        let mut events: [input_event; 64] = unsafe { std::mem::zeroed() };
+        let mut input = shared.input;
+        let input_query = shared.input_query;
         let output = shared.output.clone();
-
-        let left_bind = self.left_bind;
-        let right_bind = self.right_bind;
+        let activity = shared.activity.clone();
+        let script = shared.script;
+        let mut plugins = shared.plugins;
+
+        let mut extra_binds = Vec::new();
+        if self.multiply {
+            extra_binds.extend([
+                InputBind::new(Bind::from(Key::ButtonLeft as u16), BindAction::Echo(0)),
+                InputBind::new(Bind::from(Key::ButtonRight as u16), BindAction::Echo(1)),
+            ]);
+        }
+        for (index, plugin) in plugins.iter().enumerate() {
+            extra_binds.extend(
+                plugin
+                    .binds()
+                    .into_iter()
+                    .map(|bind| InputBind::new(Bind::from(bind), BindAction::Plugin(index))),
+            );
+        }
+        let with_extra_binds = |mut binds: Vec<InputBind>| {
+            binds.extend(extra_binds.iter().cloned());
+            // The lock is last, so the other binds see the lock from before the event
+            binds.sort_by_key(|bind| bind.action.locks());
+            binds
+        };
+        let mut profiles: VecDeque<_> = self
+            .profiles
+            .into_iter()
+            .map(|profile| ProfileBinds {
+                binds: with_extra_binds(profile.binds),
+                ..profile
+            })
+            .collect();
+        // The binds, hold modes and grab of the profile in use, switched with the profiles
+        let mut current = ProfileBinds {
+            name: String::from("default"),
+            binds: with_extra_binds(self.binds),
+            hold: self.hold,
+            grab: self.grab,
+        };
+        let mut control = self.control;
 
         let debug = shared.debug;
-        let grab = self.grab;
+        let no_regrab = self.no_regrab;
 
         let mut state = AutoclickerState::default();
-        let hold = self.hold;
+        let mut record_raw = self.record_raw;
+        let panic_bind = self.panic_bind;
+        let override_keys = self.override_keys;
+        let mut override_held = OverrideKeys::default();
+        let mut override_pause = PauseSource::keys("override main", transmitter.clone());
+
+        state.lock = current.binds.iter().any(|bind| bind.action.locks());
+        // With the countdown the autoclickers start when it ends
+        let start_active = self.start_active;
+        let mut arm_at = self.start_delay.map(|delay| Instant::now() + delay);
+        let mut countdown_shown = 0;
+        if arm_at.is_none() {
+            [state.left, state.right] = start_active;
+        }
+        _ = transmitter.send(Message::State(state));
+
+        // Keys held on the device for the chords, and the keys that triggered a bind,
+        // so their release goes to the same binds even when the chord was let go first
+        let mut held = HashSet::new();
+        let mut triggered = HashSet::new();
+
+        // When the keys were last tapped, and the keys pressed as a second tap
+        let double_tap = self.double_tap;
+        let mut last_tap = HashMap::new();
+        let mut second_taps = HashSet::new();
+
+        // The binds pressed but not held long enough yet, and the ones held long enough
+        let long_press = self.long_press;
+        let mut long_pending: HashMap<u16, (Instant, BindAction)> = HashMap::new();
+        let mut long_held = HashSet::new();
+        let mut fd = input.handler.as_inner().as_raw_fd();
+        // The bind device first then the extra input devices, `None` while they are gone, and
+        // their queries to open them again
+        let (bind_query, bind_device) = self.bind_device.unzip();
+        let (mut queries, mut others): (Vec<_>, Vec<_>) = self
+            .extra_inputs
+            .into_iter()
+            .map(|(query, device)| (query, Some(device)))
+            .unzip();
+        queries.insert(0, bind_query.unwrap_or_default());
+        others.insert(0, bind_device);
+        // The devices found again, by their source like the relays, the input device is 0
+        let (reopened_tx, reopened_rx) = mpsc::channel::<(usize, InputDevice)>();
+        if let Some(panic_bind) = &panic_bind {
+            for device in std::iter::once(&input).chain(others.iter().flatten()) {
+                panic_bind.watch(device);
+            }
+        }
+
+        let has_gestures = current
+            .binds
+            .iter()
+            .any(|bind| bind.bind.0.iter().any(|key| args::GESTURES.contains(key)));
+        let mut gesture = Gesture::default();
 
-        state.lock = self.lock_unlock_bind.is_some();
-        _ = transmitter.send(state);
+        // The frames being relayed, of the input device then of the other devices
+        let mut relays: Vec<Relay> = std::iter::repeat_with(Relay::default)
+            .take(others.len() + 1)
+            .collect();
 
         thread::spawn(move || loop {
-            input.read(&mut events).unwrap();
+            if let Some(at) = arm_at {
+                let remaining = at.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    arm_at = None;
+                    while let Ok(new_state) = feedback_rx.try_recv() {
+                        state = new_state;
+                    }
+                    [state.left, state.right] = start_active;
+                    transmitter.send(Message::State(state)).unwrap();
+                } else if countdown_shown != remaining.as_secs() + 1 {
+                    countdown_shown = remaining.as_secs() + 1;
+                    print_countdown(countdown_shown);
+                }
+            }
+
+            while let Ok((source, device)) = reopened_rx.try_recv() {
+                if let Some(panic_bind) = &panic_bind {
+                    panic_bind.watch(&device);
+                }
+                // The bind device is not grabbed
+                if source != 1 && current.grab && !no_regrab {
+                    if let Err(err) = device.grab(true) {
+                        eprintln!("\x1b[0KCannot grab {} again: {err}", device.name);
+                    }
+                }
+                if source == 0 {
+                    eprintln!("\x1b[0KThe input device is back: {}", device.name);
+                    fd = device.handler.as_inner().as_raw_fd();
+                    input = device;
+                } else {
+                    eprintln!("\x1b[0KThe device is back: {}", device.name);
+                    others[source - 1] = Some(device);
+                }
+            }
+            // The input device has no fd while it is gone
+            let reopening = fd < 0
+                || others
+                    .iter()
+                    .zip(&queries)
+                    .any(|(device, query)| device.is_none() && !query.is_empty());
+
+            // Wake up when a pending bind becomes a long press and for the countdown, even if
+            // the devices are quiet, and to take the devices that came back
+            let now = Instant::now();
+            let timeout = long_pending
+                .values()
+                .map(|(at, _)| *at + long_press.unwrap_or_default())
+                .chain(arm_at.map(|at| {
+                    let remaining = at.saturating_duration_since(now);
+                    now + (remaining - Duration::from_secs(remaining.as_secs()))
+                }))
+                .chain(reopening.then(|| now + Duration::from_secs(1)))
+                .min()
+                .map_or(-1, |due| {
+                    due.saturating_duration_since(Instant::now()).as_millis() as i32 + 1
+                });
+            // poll skips the negative fds, like when there is no bind device
+            let other_fds = others.iter().map(|device| {
+                device
+                    .as_ref()
+                    .map_or(-1, |device| device.handler.as_inner().as_raw_fd())
+            });
+            let mut polls: Vec<_> = std::iter::once(fd)
+                .chain(other_fds)
+                .chain(control.as_ref().map(ControlSocket::fd))
+                .map(|fd| libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
+            let ready = unsafe { libc::poll(polls.as_mut_ptr(), polls.len() as _, timeout) };
+            if ready < 0 {
+                continue;
+            }
+            if ready == 0 {
+                while let Ok(new_state) = feedback_rx.try_recv() {
+                    state = new_state;
+                }
+                let old_state = state;
+                let now = Instant::now();
+                long_pending.retain(|code, (at, action)| {
+                    if now < *at + long_press.unwrap_or_default() {
+                        return true;
+                    }
+                    state.press_bind(*action, current.hold, true);
+                    long_held.insert(*code);
+                    false
+                });
+                if old_state != state {
+                    transmitter.send(Message::State(state)).unwrap();
+                }
+                continue;
+            }
+
+            // The control socket is polled last
+            if let Some(control) = control
+                .as_mut()
+                .filter(|_| polls.last().is_some_and(|poll| poll.revents != 0))
+            {
+                for request in control.take() {
+                    let steps = match request {
+                        None => 1.min(profiles.len()),
+                        Some(name) if name == current.name => 0,
+                        Some(name) => profiles
+                            .iter()
+                            .position(|next| next.name == name)
+                            .map_or(0, |index| index + 1),
+                    };
+                    if steps == 0 {
+                        continue;
+                    }
+                    current.switch(&mut profiles, steps, &transmitter, |grab| {
+                        switch_grab(grab, &input, &others[1..], &mut relays, &*output)
+                    });
+                }
+                continue;
+            }
+
+            // The events of the bind device are only used for the binds, the extra input devices
+            // are read like the input device
+            let source = polls.iter().position(|poll| poll.revents != 0).unwrap_or(0);
+            let from_bind_device = source == 1;
+            let read = if source > 0 {
+                others[source - 1].as_ref().unwrap().read(&mut events)
+            } else {
+                input.read(&mut events)
+            };
+            let count = match read {
+                Ok(count) => count,
+                Err(err) if source > 0 => {
+                    let index = source - 1;
+                    let device = others[index].as_ref().unwrap();
+                    eprintln!("\x1b[0KLost {}, waiting for it: {err}", device.name);
+                    relays[source].release(&*output);
+                    let was = Identity::of(device);
+                    others[index] = None;
+                    let query = queries[index].clone();
+                    let reopened = reopened_tx.clone();
+                    thread::spawn(move || {
+                        _ = reopened.send((source, Hotplug::new().wait_for(&query, &was)));
+                    });
+                    continue;
+                }
+                Err(err) => {
+                    eprintln!(
+                        "\x1b[0KLost the input device {}, waiting for it: {err}",
+                        input.name
+                    );
+                    // Nothing that was held can be released anymore, the hold binds stop
+                    for code in held.drain() {
+                        match Key::from_code(code) {
+                            Ok(key) if current.grab => output.send_key(key, KeyState::RELEASED),
+                            _ => {}
+                        }
+                    }
+                    triggered.clear();
+                    long_pending.clear();
+                    long_held.clear();
+                    relays[0] = Relay::default();
+                    while let Ok(new_state) = feedback_rx.try_recv() {
+                        state = new_state;
+                    }
+                    let old_state = state;
+                    state.left &= !current.hold[0];
+                    state.right &= !current.hold[1];
+                    if old_state != state {
+                        transmitter.send(Message::State(state)).unwrap();
+                    }
+
+                    // The other devices and the control socket are still read while it is
+                    // found again
+                    fd = -1;
+                    let query = input_query.clone();
+                    let was = Identity::of(&input);
+                    let reopened = reopened_tx.clone();
+                    thread::spawn(move || {
+                        _ = reopened.send((0, Hotplug::new().wait_for(&query, &was)));
+                    });
+                    continue;
+                }
+            };
+            activity.touch();
+
+            while let Ok(new_state) = feedback_rx.try_recv() {
+                state = new_state;
+            }
 
-            for event in events.iter() {
+            for event in &events[..count] {
                 if debug {
                     println!("Event: {:?}", event);
                 }
 
+                if let Some(recorder) = record_raw.as_mut().filter(|_| !from_bind_device) {
+                    if let Err(err) = recorder.write(event) {
+                        eprintln!("Cannot write the raw recording, stopping it: {err}");
+                        record_raw = None;
+                    }
+                }
+
                 // Enhanced logging for mouse movement events
                 if debug && event.type_ as i32 == input_linux::sys::EV_REL {
                     match event.code as i32 {
@@ -94,38 +659,245 @@ impl StateNormal {
                 }
 
                 let mut used = false;
+                let mut next_profile = false;
                 let old_state = state;
 
                 let pressed = matches!(event.value, 1 | 2);
 
-                if !state.lock {
-                    for (bind, state) in
-                        [(left_bind, &mut state.left), (right_bind, &mut state.right)]
-                    {
-                        if event.code == bind {
-                            if hold {
-                                if pressed != *state {
-                                    *state = pressed;
+                let is_key = event.type_ as i32 == input_linux::sys::EV_KEY;
+                if let Some(script) = script.as_ref().filter(|_| is_key && event.value != 2) {
+                    _ = script.send(Hook::BindPress {
+                        key: event.code,
+                        pressed,
+                    });
+                }
+                if let Some(panic_bind) = panic_bind.as_ref() {
+                    if is_key && event.value == 1 {
+                        panic_bind.check(event.code, &held);
+                    }
+                }
+                if is_key && !override_keys.is_empty() {
+                    override_pause.set(override_held.update(&override_keys, event));
+                }
+
+                // The events go through until the countdown ends, a bind press ends it
+                if arm_at.is_some() {
+                    let bind_pressed = is_key
+                        && event.value == 1
+                        && current.binds.iter().any(|bind| bind.bind.matches(event.code, &held));
+                    if bind_pressed {
+                        arm_at = Some(Instant::now());
+                        triggered.insert(event.code);
+                    } else if current.grab && !from_bind_device {
+                        relayed_or_exit(relays[source].event(&*output, event, false));
+                    }
+                    if is_key {
+                        match event.value {
+                            0 => _ = held.remove(&event.code),
+                            1 => _ = held.insert(event.code),
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+
+                // A notch of the wheel or a gesture is a tap, its binds fire once like the
+                // release binds
+                let is_rel = event.type_ as i32 == input_linux::sys::EV_REL;
+                let is_wheel =
+                    is_rel && event.code as i32 == input_linux::sys::REL_WHEEL && event.value != 0;
+                let tap = if is_wheel {
+                    Some(if event.value > 0 {
+                        args::WHEEL_UP
+                    } else {
+                        args::WHEEL_DOWN
+                    })
+                } else if is_rel && has_gestures {
+                    gesture.push(event.code, event.value)
+                } else {
+                    None
+                };
+                if let Some(code) = tap {
+                    if debug && !is_wheel {
+                        println!("  -> Gesture: {}", Bind::from(code));
+                    }
+                    let longest = longest_chord(&current.binds, code, &held);
+                    for InputBind { bind, action, .. } in &current.binds {
+                        if bind.matches(code, &held)
+                            && Some(bind.0.len()) == longest
+                            && (!state.lock || *action == BindAction::Lock)
+                        {
+                            state.release_bind(*action, &transmitter);
+                            next_profile |= *action == BindAction::Profile;
+                            // The movement of a gesture still goes through
+                            used |= is_wheel && !matches!(action, BindAction::React(_));
+                        }
+                    }
+                }
+
+                // Only the longest chords that match, so Ctrl+F6 does not trigger the F6 binds
+                let longest = longest_chord(&current.binds, event.code, &held);
+                // With the double tap the first tap goes through, only the second one toggles
+                let second_tap = match double_tap {
+                    Some(window) if is_key && event.value == 1 => {
+                        let now = Instant::now();
+                        let second = last_tap
+                            .insert(event.code, now)
+                            .is_some_and(|last| now - last <= window);
+                        if second {
+                            last_tap.remove(&event.code);
+                            second_taps.insert(event.code);
+                        }
+                        second
+                    }
+                    Some(_) => second_taps.contains(&event.code),
+                    None => true,
+                };
+                let mut matched = false;
+                for InputBind {
+                    bind: _,
+                    action,
+                    on_release,
+                } in current.binds.iter().filter(|InputBind { bind, .. }| {
+                    is_key
+                        && if event.value == 0 {
+                            bind.key() == event.code && triggered.contains(&event.code)
+                        } else {
+                            bind.matches(event.code, &held) && Some(bind.0.len()) == longest
+                        }
+                }) {
+                    matched = true;
+                    if *on_release {
+                        if !state.lock || *action == BindAction::Lock {
+                            if event.value == 0 {
+                                state.release_bind(*action, &transmitter);
+                                next_profile |= *action == BindAction::Profile;
+                            }
+                            // The reactions only observe the key
+                            used |= !matches!(action, BindAction::React(_));
+                        }
+                        continue;
+                    }
+                    match *action {
+                        BindAction::Left | BindAction::Right
+                            if !state.lock && second_tap && long_press.is_some() =>
+                        {
+                            let threshold = long_press.unwrap();
+                            match event.value {
+                                1 => {
+                                    long_pending.insert(event.code, (Instant::now(), *action));
+                                }
+                                // The autorepeat of a keyboard may come before the timeout
+                                2 => {
+                                    if long_pending
+                                        .get(&event.code)
+                                        .is_some_and(|(at, _)| at.elapsed() >= threshold)
+                                    {
+                                        long_pending.remove(&event.code);
+                                        long_held.insert(event.code);
+                                        state.press_bind(*action, current.hold, true);
+                                    }
+                                }
+                                _ => {
+                                    if let Some((at, _)) = long_pending.remove(&event.code) {
+                                        if at.elapsed() >= threshold {
+                                            state.press_bind(*action, current.hold, true);
+                                            state.press_bind(*action, current.hold, false);
+                                        } else if current.grab && !from_bind_device {
+                                            // Too short, the press that was held back goes through
+                                            // and the release after it
+                                            if let Ok(key) = Key::from_code(event.code) {
+                                                output.send_key(key, KeyState::PRESSED);
+                                            }
+                                            continue;
+                                        }
+                                    } else if long_held.remove(&event.code) {
+                                        state.press_bind(*action, current.hold, false);
+                                    }
                                 }
-                            } else if pressed {
-                                *state = !*state;
                             }
                             used = true;
                         }
+                        BindAction::Left | BindAction::Right if !state.lock && second_tap => {
+                            state.press_bind(*action, current.hold, pressed);
+                            used = true;
+                        }
+                        BindAction::Macro(index) if !state.lock => {
+                            // The autorepeat does not restart the macro
+                            if event.value != 2 {
+                                _ = transmitter.send(Message::Macro { index, pressed });
+                            }
+                            used = true;
+                        }
+                        BindAction::Click(index) if !state.lock => {
+                            if event.value == 1 {
+                                _ = transmitter.send(Message::Click { index });
+                            }
+                            used = true;
+                        }
+                        // The key still goes through, it is only observed
+                        BindAction::React(index) if !state.lock && event.value == 1 => {
+                            _ = transmitter.send(Message::React { index });
+                        }
+                        BindAction::Echo(index) if !used => {
+                            if event.value == 1 {
+                                _ = transmitter.send(Message::Echo { index });
+                            }
+                            used = true;
+                        }
+                        BindAction::Plugin(index) if !state.lock => {
+                            if event.value != 2 {
+                                plugins[index].on_bind(event.code, pressed, &*output);
+                            }
+                            used = true;
+                        }
+                        // The autorepeat keeps changing it
+                        BindAction::Cooldown { faster } if !state.lock => {
+                            if pressed {
+                                _ = transmitter.send(Message::Cooldown { faster });
+                            }
+                            used = true;
+                        }
+                        BindAction::Profile if !state.lock => {
+                            next_profile |= event.value == 1;
+                            used = true;
+                        }
+                        BindAction::Lock if pressed => state.lock = !state.lock,
+                        BindAction::Enable => state.lock = !pressed,
+                        _ => {}
                     }
                 }
 
-                if let Some(bind) = self.lock_unlock_bind {
-                    if event.code == bind && pressed {
-                        state.lock = !state.lock;
+                if next_profile && !profiles.is_empty() {
+                    current.switch(&mut profiles, 1, &transmitter, |grab| {
+                        switch_grab(grab, &input, &others[1..], &mut relays, &*output)
+                    });
+                }
+
+                if is_key {
+                    match event.value {
+                        0 => {
+                            held.remove(&event.code);
+                            triggered.remove(&event.code);
+                            second_taps.remove(&event.code);
+                        }
+                        1 => {
+                            held.insert(event.code);
+                            if matched {
+                                triggered.insert(event.code);
+                            }
+                        }
+                        _ => {}
                     }
                 }
 
                 if old_state != state {
-                    transmitter.send(state).unwrap();
+                    transmitter.send(Message::State(state)).unwrap();
                 }
 
-                if grab && !used {
+                // The relay drops the scan code with a consumed key and the frames left empty,
+                // the applications do not see the bind keys at all while the typing goes through
+                if current.grab && !used && !from_bind_device {
                     // Use smooth linear interpolation for gradual scaling
                     let mut scaled_event = *event;
                     if event.type_ as i32 == input_linux::sys::EV_REL {
@@ -173,60 +945,71 @@ impl StateNormal {
                         println!("  -> Forwarding event to virtual device: type={}, code={}, value={}", 
                                 scaled_event.type_, scaled_event.code, scaled_event.value);
                     }
-                    let scaled_events = [scaled_event];
-                    output
-                        .write(&scaled_events)
-                        .expect("Cannot write to virtual device!");
-                } else if grab && used && debug {
-                    println!("  -> Event consumed by autoclicker (not forwarded): type={}, code={}, value={}", 
+                    relayed_or_exit(relays[source].event(&*output, &scaled_event, false));
+                } else if current.grab && used && !from_bind_device {
+                    relayed_or_exit(relays[source].event(&*output, event, true));
+                    if debug {
+                        println!("  -> Event consumed by autoclicker (not forwarded): type={}, code={}, value={}", 
                             event.type_, event.code, event.value);
-                } else if !grab && debug {
+                    }
+                } else if !current.grab && debug {
                     println!("  -> Grab disabled, event handled by system");
                 }
             }
         });
 
-        autoclicker(
-            shared.beep,
-            receiver,
-            &shared.output,
-            self.cooldown,
-            self.cooldown_pr,
-        );
+        self.engine
+            .run(shared.beep.as_ref(), receiver, feedback_tx, &*shared.output);
     }
 }
 
 pub struct StateLegacy {
-    cooldown: Duration,
-    cooldown_pr: Duration,
+    engine: Engine,
 }
 
 impl StateLegacy {
-    fn run(self, shared: Shared) {
-        let (transmitter, receiver) = mpsc::channel::<AutoclickerState>();
+    fn run(self, shared: Shared, transmitter: Sender<Message>, receiver: Receiver<Message>) {
+        let (feedback_tx, feedback_rx) = mpsc::channel::<AutoclickerState>();
 
-        let input = shared.input;
+        let mut input = shared.input;
+        let input_query = shared.input_query;
+        let activity = shared.activity.clone();
 
-        let fd = input.handler.as_inner().as_raw_fd();
+        let mut fd = input.handler.as_inner().as_raw_fd();
         let mut data: [u8; 3] = [0; 3];
         let mut state = AutoclickerState {
             lock: true,
             ..Default::default()
         };
-        transmitter.send(state).unwrap();
+        transmitter.send(Message::State(state)).unwrap();
 
         let mut old_left = 0;
         let mut old_right = 0;
         let mut old_middle = 0;
 
         std::thread::spawn(move || loop {
-            let Ok(len) = nix::unistd::read(fd, &mut data) else {
-                panic!("Cannot read from input device!");
+            let len = match nix::unistd::read(fd, &mut data) {
+                Ok(len) => len,
+                Err(err) => {
+                    eprintln!(
+                        "\x1b[0KLost the input device {}, waiting for it: {err}",
+                        input.name
+                    );
+                    input = Hotplug::new().wait_for(&input_query, &Identity::of(&input));
+                    fd = input.handler.as_inner().as_raw_fd();
+                    eprintln!("\x1b[0KThe input device is back: {}", input.name);
+                    continue;
+                }
             };
 
             if len != 3 {
                 continue;
             }
+            activity.touch();
+
+            while let Ok(new_state) = feedback_rx.try_recv() {
+                state = new_state;
+            }
 
             let left = data[0] & 1;
             let right = (data[0] >> 1) & 1;
@@ -254,88 +1037,45 @@ impl StateLegacy {
             old_middle = middle;
 
             if old_state != state {
-                transmitter.send(state).unwrap();
+                transmitter.send(Message::State(state)).unwrap();
             }
         });
 
-        autoclicker(
-            shared.beep,
-            receiver,
-            &shared.output,
-            self.cooldown,
-            self.cooldown_pr,
-        );
-    }
-}
-
-fn autoclicker(
-    beep: bool,
-    receiver: std::sync::mpsc::Receiver<AutoclickerState>,
-    output: &OutputDevice,
-    cooldown: Duration,
-    cooldown_pr: Duration,
-) {
-    let mut toggle = AutoclickerState::default();
-    println!();
-    print_active(&toggle);
-
-    loop {
-        if let Some(recv) = if toggle.left | toggle.right {
-            receiver.try_recv().ok()
-        } else {
-            receiver.recv().ok()
-        } {
-            toggle = recv;
-
-            if beep {
-                // ansi beep sound
-                print!("\x07");
-            }
-
-            print_active(&toggle);
-        }
-
-        if toggle.left {
-            output.send_key(Key::ButtonLeft, KeyState::PRESSED);
-        }
-        if toggle.right {
-            output.send_key(Key::ButtonRight, KeyState::PRESSED);
-        }
-
-        if !cooldown_pr.is_zero() {
-            thread::sleep(cooldown_pr);
-        }
-
-        if toggle.left {
-            output.send_key(Key::ButtonLeft, KeyState::RELEASED);
-        }
-
-        if toggle.right {
-            output.send_key(Key::ButtonRight, KeyState::RELEASED);
-        }
-        thread::sleep(cooldown);
+        self.engine
+            .run(shared.beep.as_ref(), receiver, feedback_tx, &*shared.output);
     }
 }
 
 pub enum Variant {
-    Normal(StateNormal),
-    Legacy(StateLegacy),
+    Normal(Box<StateNormal>),
+    Legacy(Box<StateLegacy>),
 }
 
 impl Variant {
-    pub fn run(self, shared: Shared) {
+    pub fn run(self, shared: Shared, transmitter: Sender<Message>, receiver: Receiver<Message>) {
         match self {
-            Variant::Normal(state_normal) => state_normal.run(shared),
-            Variant::Legacy(state_legacy) => state_legacy.run(shared),
+            Variant::Normal(state_normal) => state_normal.run(shared, transmitter, receiver),
+            Variant::Legacy(state_legacy) => state_legacy.run(shared, transmitter, receiver),
         }
     }
 }
 
 pub struct Shared {
     debug: bool,
-    beep: bool,
+    beep: Option<Beeper>,
     input: InputDevice,
-    output: Arc<OutputDevice>,
+    /// To open the input device again when it comes back
+    input_query: String,
+    output: Arc<dyn OutputBackend>,
+    /// Updated by the input thread on every real event
+    activity: Activity,
+    jiggler: Option<Jiggler>,
+    anti_afk: Option<AntiAfk>,
+    window_rules: Option<WindowRules>,
+    session: Option<SessionWatch>,
+    overrides: Option<Overrides>,
+    script: Option<Script>,
+    plugins: Vec<Box<dyn Plugin>>,
 }
 
 pub struct TheClicker {
@@ -348,15 +1088,66 @@ impl TheClicker {
         Args {
             debug,
             beep,
+            beep_sound,
+            backend,
             command,
-            config: _,
+            config,
             default: _,
+            no_config: _,
+            save_config,
+            export_effective_config,
+            named_profiles,
+            blacklist,
+            no_blacklist,
         }: Args,
     ) -> Self {
-        let output = OutputDevice::uinput_open(PathBuf::from("/dev/uinput"), "TheClicker").unwrap();
-        output.add_mouse_attributes();
+        let backend = backend.unwrap_or_default();
+        let beep_sound = beep_sound.unwrap_or_default();
+        let blacklist = if no_blacklist { Vec::new() } else { blacklist };
+        let command = command.unwrap_or_else(|| {
+            // `--save-config` already tells where to save it
+            if save_config.is_some() {
+                setup_from_user_input(&blacklist)
+            } else {
+                command_from_user_input(backend, &blacklist)
+            }
+        });
 
-        let command = command.unwrap_or_else(command_from_user_input);
+        if save_config.is_some() || export_effective_config {
+            let command = ConfigCommand::try_from(command.clone()).unwrap_or_else(|err| {
+                eprintln!("Cannot save the settings: {err}");
+                std::process::exit(1);
+            });
+            let config = Config {
+                version: config::VERSION,
+                debug,
+                beep,
+                beep_sound,
+                backend,
+                command,
+                profiles: named_profiles.clone(),
+            };
+            if let Some(path) = &save_config {
+                if let Err(err) = config.save_to_file(path) {
+                    eprintln!("\x1B[1;31mError saving configuration: {err}\x1B[0;39m");
+                    std::process::exit(1);
+                }
+                // The export is the only output
+                if !export_effective_config {
+                    println!("\x1B[1;32mConfiguration saved to {}\x1B[0;39m", path.display());
+                }
+            }
+            if export_effective_config {
+                match serde_json::to_string_pretty(&config) {
+                    Ok(json) => println!("{json}"),
+                    Err(err) => {
+                        eprintln!("Cannot export the settings: {err}");
+                        std::process::exit(1);
+                    }
+                }
+                std::process::exit(0);
+            }
+        }
 
         print!("Using args: `");
         if debug {
@@ -365,6 +1156,14 @@ impl TheClicker {
         if beep {
             print!("--beep ")
         }
+        if beep_sound != BeepSound::Auto {
+            print!("--beep-sound {beep_sound} ")
+        }
+        let beep = beep.then(|| Beeper::new(beep_sound));
+        if backend != Backend::Uinput {
+            print!("--backend {backend} ")
+        }
+        let bindings = command.bindings();
         match command {
             args::Command::Run {
                 device_query,
@@ -373,11 +1172,39 @@ impl TheClicker {
                 lock_unlock_bind,
                 hold,
                 grab,
-                cooldown,
-                cooldown_press_release,
+                macro_binds,
+                react_binds,
+                record_raw,
+                multiply,
+                double_tap,
+                long_press,
+                left_mode,
+                right_mode,
+                faster_bind,
+                slower_bind,
+                binds,
+                enable_bind,
+                profiles,
+                profile_bind,
+                panic_bind,
+                bind_device,
+                start_active,
+                start_delay,
+                override_main,
+                extra_devices,
+                no_regrab,
+                control,
+                click,
             } => {
-                print!("run -d{device_query:?} -l{left_bind} -r{right_bind} -c{cooldown} -C{cooldown_press_release}");
-                if let Some(bind) = lock_unlock_bind {
+                print!("run -d{device_query:?}");
+                if let Some(bind) = &left_bind {
+                    print!(" -l{bind}")
+                }
+                if let Some(bind) = &right_bind {
+                    print!(" -r{bind}")
+                }
+                print!(" {click}");
+                if let Some(bind) = &lock_unlock_bind {
                     print!(" -T{bind}")
                 }
                 if hold {
@@ -386,110 +1213,897 @@ impl TheClicker {
                 if grab {
                     print!(" --grab")
                 }
+                for bind in &macro_binds {
+                    print!(" --macro-bind {bind}")
+                }
+                for bind in &react_binds {
+                    print!(" --react {bind}")
+                }
+                if let Some(path) = &record_raw {
+                    print!(" --record-raw {}", path.display())
+                }
+                if let Some(times) = multiply {
+                    print!(" --multiply {times}")
+                }
+                if let Some(window) = double_tap {
+                    print!(" --double-tap {window}")
+                }
+                if let Some(threshold) = long_press {
+                    print!(" --long-press {threshold}")
+                }
+                if let Some(mode) = left_mode {
+                    print!(" --left-mode {mode}")
+                }
+                if let Some(mode) = right_mode {
+                    print!(" --right-mode {mode}")
+                }
+                if let Some(bind) = &faster_bind {
+                    print!(" --faster-bind {bind}")
+                }
+                if let Some(bind) = &slower_bind {
+                    print!(" --slower-bind {bind}")
+                }
+                for bind in &binds {
+                    print!(" --bind {bind}")
+                }
+                if let Some(bind) = &enable_bind {
+                    print!(" --enable-bind {bind}")
+                }
+                for path in &profiles {
+                    print!(" --profile {}", path.display())
+                }
+                if let Some(bind) = &profile_bind {
+                    print!(" --profile-bind {bind}")
+                }
+                if let Some(bind) = &panic_bind {
+                    print!(" --panic-bind {bind}")
+                }
+                if let Some(query) = &bind_device {
+                    print!(" --bind-device {query:?}")
+                }
+                if let Some(start) = start_active {
+                    print!(" --start-active {start}")
+                }
+                if let Some(delay) = start_delay {
+                    print!(" --start-delay {delay}")
+                }
+                if override_main {
+                    print!(" --override-main")
+                }
+                for query in &extra_devices {
+                    print!(" --extra-device {query:?}")
+                }
+                if no_regrab {
+                    print!(" --no-regrab")
+                }
+                if let Some(path) = &control {
+                    print!(" --control {}", path.display())
+                }
                 println!("`");
+                // Without the grab the physical click goes through besides the multiplied ones,
+                // clap only checks it on the command line
+                if multiply.is_some() && !grab {
+                    eprintln!("`multiply` needs `grab`, the physical clicks would also go through");
+                    std::process::exit(1);
+                }
+                // The relayed events would only be printed
+                let grab = grab && !click.dry_run;
+
+                let (bind_actions, macros, reactions, clicks) = bind_actions(&bindings);
+                let unlock = bind_actions.iter().any(|bind| bind.action.locks());
+                // The profile binds of the command line switch between all the profiles
+                let profile_binds: Vec<_> = bind_actions
+                    .iter()
+                    .filter(|bind| bind.action == BindAction::Profile)
+                    .cloned()
+                    .collect();
+                let mut profiles: Vec<_> = profiles
+                    .iter()
+                    .map(|path| load_profile(path, &named_profiles, &profile_binds, multiply))
+                    .collect();
+                for profile in &mut profiles {
+                    profile.grab &= !click.dry_run;
+                }
 
-                let input = input_device_from_query(device_query);
+                let input = input_device_from_query(device_query.clone());
                 if input.filename.starts_with("mouse") && input.filename.as_str() == "mice" {
                     eprintln!("Use the run-legacy for legacy devices");
                     std::process::exit(4);
                 }
-
-                if grab {
-                    if debug {
-                        println!("Setting up virtual device with grab mode enabled");
-                        println!("Copying attributes from input device: {}", input.name);
-                    }
-                    output.copy_attributes(debug, &input);
+                let extra_inputs: Vec<_> = extra_devices
+                    .into_iter()
+                    .map(|query| (query.clone(), input_device_from_query(query)))
+                    .collect();
+                let bind_input = bind_device.map(|query| {
+                    let device = input_device_from_query(query.clone());
+                    (query, device)
+                });
+                let bind_keys = bindings
+                    .iter()
+                    .map(|binding| &binding.key)
+                    .chain(&panic_bind)
+                    .chain(click.override_keys.iter().filter(|_| override_main));
+                let readers: Vec<_> = std::iter::once(&input)
+                    .chain(extra_inputs.iter().chain(&bind_input).map(|(_, device)| device))
+                    .collect();
+                warn_missing_keys(bind_keys, &readers);
+
+                // The virtual device relays the devices when a profile grabs them
+                let grabbed: Vec<_> = if grab || profiles.iter().any(|profile| profile.grab) {
+                    std::iter::once(&input)
+                        .chain(extra_inputs.iter().map(|(_, device)| device))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+
+                let record_raw = record_raw.map(|path| {
+                    RawRecorder::create(&path, &input.name).unwrap_or_else(|err| {
+                        eprintln!("Cannot create {}: {err}", path.display());
+                        std::process::exit(1);
+                    })
+                });
+
+                // The virtual device also needs what the macros of the profiles send
+                let all_macros: Vec<_> = macros
+                    .iter()
+                    .chain(profiles.iter().flat_map(|profile| &profile.macros))
+                    .cloned()
+                    .collect();
+                let overrides = Overrides::from_options(&click, debug);
+                let output = open_output(
+                    backend,
+                    debug,
+                    &click,
+                    &all_macros,
+                    &grabbed,
+                    overrides.as_ref(),
+                );
+                let panic_bind = panic_bind.map(|bind| PanicBind::new(bind, output.clone()));
+                let overrides =
+                    overrides.map(|overrides| overrides.with_panic_bind(panic_bind.clone()));
+                let script = Script::from_options(&click, output.clone(), debug);
+                let led_devices: Vec<_> = readers
+                    .iter()
+                    .copied()
+                    .chain(overrides.iter().flat_map(Overrides::devices))
+                    .collect();
+                let led = StatusLed::from_options(&click, &led_devices);
+                let haptic = Haptic::from_options(&click, &led_devices);
+                for input in grabbed.into_iter().filter(|_| grab) {
                     input.grab(true).expect("Cannot grab input device!");
                     if debug {
                         println!("Successfully grabbed input device: {}", input.path.display());
                     }
                 }
 
-                output.create();
-                if debug {
-                    println!("Virtual output device created");
-                }
+                let control = control.map(|path| {
+                    let names = std::iter::once("default".to_string())
+                        .chain(profiles.iter().map(|profile| profile.name.clone()))
+                        .collect();
+                    ControlSocket::bind(&path, names).unwrap_or_else(|err| {
+                        eprintln!("Cannot listen on {}: {err}", path.display());
+                        std::process::exit(1);
+                    })
+                });
+                let (profile_engines, profile_states) = profiles
+                    .into_iter()
+                    .map(|profile| {
+                        let binds = ProfileBinds {
+                            name: profile.name.clone(),
+                            binds: profile.binds,
+                            hold: profile.hold,
+                            grab: profile.grab,
+                        };
+                        ((profile.name, profile.engine), binds)
+                    })
+                    .unzip();
 
                 Self {
                     shared: Shared {
                         debug,
                         beep,
                         input,
-                        output: Arc::new(output),
+                        input_query: device_query,
+                        output,
+                        activity: Activity::new(),
+                        jiggler: Jiggler::from_options(&click),
+                        anti_afk: AntiAfk::from_options(&click),
+                        window_rules: WindowRules::from_options(&click, debug),
+                        session: SessionWatch::from_options(&click, debug),
+                        overrides,
+                        script: script.clone(),
+                        plugins: plugin::from_options(&click),
                     },
-                    variant: Variant::Normal(StateNormal {
-                        left_bind,
-                        right_bind,
-                        lock_unlock_bind,
-                        hold,
+                    variant: Variant::Normal(Box::new(StateNormal {
+                        binds: bind_actions,
+                        record_raw,
+                        panic_bind,
+                        bind_device: bind_input,
+                        extra_inputs,
+                        no_regrab,
+                        start_active: start_active.map_or([false; 2], StartActive::sides),
+                        start_delay: start_delay.map(|delay| delay.0),
+                        override_keys: if override_main {
+                            click.override_keys.clone()
+                        } else {
+                            Vec::new()
+                        },
+                        multiply: multiply.is_some(),
+                        double_tap: double_tap.map(Duration::from_millis),
+                        long_press: long_press.map(Duration::from_millis),
+                        hold: hold_modes(hold, left_mode, right_mode),
                         grab,
-                        cooldown: Duration::from_millis(cooldown),
-                        cooldown_pr: Duration::from_millis(cooldown_press_release),
-                    }),
+                        control,
+                        engine: Engine::from_options(&click)
+                            .with_macros(&macros)
+                            .with_reactions(&reactions)
+                            .with_clicks(&clicks)
+                            .with_multiplier(multiply, &click)
+                            .with_script(script)
+                            .with_led(led)
+                            .with_haptic(haptic)
+                            .with_unlock(unlock)
+                            .with_profiles(profile_engines),
+                        profiles: profile_states,
+                    })),
                 }
             }
             args::Command::RunLegacy {
                 device_query,
-                cooldown,
-                cooldown_press_release,
+                click,
             } => {
-                println!("run-legacy -d{device_query:?} -c{cooldown} -C{cooldown_press_release}`");
+                println!("run-legacy -d{device_query:?} {click}`");
 
-                let input = input_device_from_query(device_query);
+                let input = input_device_from_query(device_query.clone());
                 if input.filename.as_str() == "mice" {
                     eprintln!("You cannot use the /dev/input/mice, because receivers events from all other /dev/input/mouse{{N}}");
                     std::process::exit(5);
                 }
 
-                output.create();
+                let overrides = Overrides::from_options(&click, debug);
+                let output =
+                    open_output(backend, debug, &click, &[], &[], overrides.as_ref());
+                let script = Script::from_options(&click, output.clone(), debug);
+                let led_devices: Vec<_> = std::iter::once(&input)
+                    .chain(overrides.iter().flat_map(Overrides::devices))
+                    .collect();
+                let led = StatusLed::from_options(&click, &led_devices);
+                let haptic = Haptic::from_options(&click, &led_devices);
 
                 Self {
                     shared: Shared {
                         debug,
                         beep,
                         input,
-                        output: Arc::new(output),
+                        input_query: device_query,
+                        output,
+                        activity: Activity::new(),
+                        jiggler: Jiggler::from_options(&click),
+                        anti_afk: AntiAfk::from_options(&click),
+                        window_rules: WindowRules::from_options(&click, debug),
+                        session: SessionWatch::from_options(&click, debug),
+                        overrides,
+                        script: script.clone(),
+                        plugins: plugin::from_options(&click),
                     },
-                    variant: Variant::Legacy(StateLegacy {
-                        cooldown: Duration::from_millis(cooldown),
-                        cooldown_pr: Duration::from_millis(cooldown_press_release),
-                    }),
+                    variant: Variant::Legacy(Box::new(StateLegacy {
+                        engine: Engine::from_options(&click)
+                            .with_script(script)
+                            .with_led(led)
+                            .with_haptic(haptic),
+                    })),
+                }
+            }
+            args::Command::Record {
+                device_query,
+                output,
+                stop_key,
+            } => {
+                print!("record -d{device_query:?} -o{output:?}");
+                if let Some(key) = stop_key {
+                    print!(" --stop-key {key}")
+                }
+                println!("`");
+
+                let input = input_device_from_query(device_query);
+                macros::record(&input, &output, stop_key);
+                std::process::exit(0);
+            }
+            args::Command::Replay { recording } => {
+                println!("replay {recording:?}`");
+
+                let events = std::fs::read_to_string(&recording)
+                    .map_err(|err| err.to_string())
+                    .and_then(|text| raw::parse_evemu(&text))
+                    .unwrap_or_else(|err| {
+                        eprintln!("Cannot load the recording {}: {err}", recording.display());
+                        std::process::exit(1);
+                    });
+
+                let output = open_replay_output(backend, debug, &events);
+                if let Err(err) = raw::replay(&events, &*output) {
+                    eprintln!("Cannot write the events to the virtual device: {err}");
+                    std::process::exit(1);
+                }
+                println!("Replayed {} events", events.len());
+                std::process::exit(0);
+            }
+            args::Command::Import { recording, output } => {
+                println!("import {recording:?} -o{output:?}`");
+
+                Macro::load_or_exit(&recording).save_or_exit(&output);
+                std::process::exit(0);
+            }
+            args::Command::Config { action } => match action {
+                ConfigAction::Validate { file } => {
+                    println!("config validate {file:?}`");
+
+                    std::process::exit(if config::validate(&file) { 0 } else { 1 });
+                }
+                ConfigAction::Generate { path } => {
+                    let path = path.unwrap_or_else(Config::default_path);
+                    println!("config generate {path:?}`");
+
+                    let overwrite = format!("{} exists, overwrite it?", path.display());
+                    if path.exists() && !choose_yes(overwrite, false) {
+                        std::process::exit(1);
+                    }
+                    let command = setup_from_user_input(&blacklist);
+                    std::process::exit(if save_setup(&command, backend, &path) { 0 } else { 1 });
                 }
+                ConfigAction::Schema { output } => {
+                    println!("config schema -o{output:?}`");
+
+                    let schema = serde_json::to_string_pretty(&schema::schema()).unwrap();
+                    if let Err(err) = std::fs::write(&output, schema + "\n") {
+                        eprintln!("\x1B[1;31mError saving the schema: {err}\x1B[0;39m");
+                        std::process::exit(1);
+                    }
+                    println!("\x1B[1;32mSaved the schema to {}\x1B[0;39m", output.display());
+                    std::process::exit(0);
+                }
+            },
+            args::Command::Daemon => {
+                println!("daemon`");
+
+                let Some(config) = config else {
+                    eprintln!("The daemon runs the profiles of a config, give it with `--config`");
+                    std::process::exit(1);
+                };
+                daemon::run(&config, &named_profiles);
+            }
+        }
+    }
+
+    /// Adds a plugin that is not in the registry, for programs using TheClicker as a library
+    pub fn with_plugin(mut self, plugin: Box<dyn Plugin>) -> Self {
+        self.shared.plugins.push(plugin);
+        self
+    }
+
+    pub fn main_loop(mut self) {
+        if let Some(jiggler) = self.shared.jiggler.take() {
+            jiggler.spawn(self.shared.output.clone(), self.shared.activity.clone());
+        }
+        if let Some(anti_afk) = self.shared.anti_afk.take() {
+            anti_afk.spawn(self.shared.output.clone());
+        }
+
+        let (transmitter, receiver) = mpsc::channel::<Message>();
+        self.shared
+            .output
+            .watch(PauseSource::new("virtual device", transmitter.clone()));
+        if let Some(window_rules) = self.shared.window_rules.take() {
+            window_rules.spawn(PauseSource::new("window", transmitter.clone()));
+        }
+        if let Some(session) = self.shared.session.take() {
+            session.spawn(
+                PauseSource::new("session", transmitter.clone()),
+                transmitter.clone(),
+            );
+        }
+        if let Some(overrides) = self.shared.overrides.take() {
+            overrides.spawn(transmitter.clone(), self.shared.output.clone());
+        }
+        for plugin in self.shared.plugins.iter_mut() {
+            plugin.start(PauseSource::new(plugin.name(), transmitter.clone()));
+        }
+        self.variant.run(self.shared, transmitter, receiver);
+    }
+}
+
+/// Opens the backend that sends the clicks, `grab` are the input devices relayed through it, like
+/// the grabbed override devices
+fn open_output(
+    backend: Backend,
+    debug: bool,
+    click: &ClickOptions,
+    macro_binds: &[MacroBind],
+    grab: &[&InputDevice],
+    overrides: Option<&Overrides>,
+) -> Arc<dyn OutputBackend> {
+    let grabbed_overrides = overrides.map_or(Vec::new(), Overrides::grabbed);
+    if !click.positions.is_empty() && click.screen.is_none() {
+        eprintln!("The positions need the screen size, like `--screen 1920x1080`");
+        std::process::exit(1);
+    }
+    if click.dry_run {
+        println!("Dry run, nothing is sent and no device is grabbed");
+        return Arc::new(DryRunOutput::default());
+    }
+
+    match backend {
+        Backend::Uinput => {
+            // Own handles on the relayed devices, to set up the virtual device again
+            let relayed: Vec<InputDevice> = grab
+                .iter()
+                .chain(&grabbed_overrides)
+                .map(|device| device.try_clone().expect("Cannot open the relayed device again"))
+                .collect();
+            let click = click.clone();
+            let macro_binds = macro_binds.to_vec();
+            let create: CreateOutput = Box::new(move || {
+                let relayed: Vec<&InputDevice> = relayed.iter().collect();
+                create_uinput(debug, &click, &macro_binds, &relayed)
+            });
+            let output = create().unwrap_or_else(|err| {
+                println!("Error: {}", err);
+                println!("Not having access to create device, try as root!");
+                std::process::exit(1);
+            });
+            Arc::new(RecoveringOutput::new(output, create))
+        }
+        Backend::Wayland => {
+            // A grabbed keyboard is relayed through the virtual keyboard
+            let keyboard = click.needs_keyboard()
+                || macros_have_keys(macro_binds)
+                || !grabbed_overrides.is_empty()
+                || grab.iter().any(|input| {
+                    input
+                        .handler
+                        .key_bits()
+                        .is_ok_and(|bits| bits.get(Key::A))
+                });
+            let output = WaylandOutput::connect(keyboard, click.screen).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+            if debug {
+                println!("Wayland virtual pointer created");
             }
+            Arc::new(output)
+        }
+    }
+}
+
+/// Creates the uinput virtual devices, `relayed` are the grabbed devices relayed through them
+fn create_uinput(
+    debug: bool,
+    click: &ClickOptions,
+    macro_binds: &[MacroBind],
+    relayed: &[&InputDevice],
+) -> io::Result<Arc<dyn OutputBackend>> {
+    let uinput = PathBuf::from("/dev/uinput");
+    if click.split_devices {
+        let mouse =
+            OutputDevice::uinput_open(uinput.clone(), &click.device_name, click.device_identity())?;
+        let keyboard = OutputDevice::uinput_open(
+            uinput,
+            &format!("{} Keyboard", click.device_name),
+            click.device_identity(),
+        )?;
+        mouse.add_mouse_attributes();
+        keyboard.add_keyboard_attributes();
+
+        // The keyboards only need their repeat rate and LEDs, the keyboard has every key
+        let (keyboards, others): (Vec<&InputDevice>, Vec<&InputDevice>) =
+            relayed.iter().copied().partition(|device| device.repeats());
+        for device in &keyboards {
+            keyboard.add_repeat_attributes(device);
+            keyboard.add_led_attributes(device);
+        }
+        for device in &others {
+            if debug {
+                println!("Copying attributes from input device: {}", device.name);
+            }
+            mouse.copy_attributes(debug, device);
+        }
+        add_click_attributes(&mouse, &keyboard, click, macro_binds);
+
+        mouse.create()?;
+        keyboard.create()?;
+        mouse.forward_feedback(debug, &others);
+        keyboard.forward_feedback(debug, &keyboards);
+        if debug {
+            println!("Virtual mouse and keyboard created");
+        }
+        return Ok(Arc::new(SplitOutput::new(mouse, keyboard)));
+    }
+
+    let output = OutputDevice::uinput_open(uinput, &click.device_name, click.device_identity())?;
+    output.add_mouse_attributes();
+
+    for device in relayed {
+        if debug {
+            println!("Setting up virtual device with grab mode enabled");
+            println!("Copying attributes from input device: {}", device.name);
         }
+        output.copy_attributes(debug, device);
     }
+    // The relayed keyboards can send any key, and keep their repeat rate
+    for device in relayed {
+        if device.is_full_keyboard() {
+            output.add_keyboard_attributes();
+        }
+        if device.repeats() {
+            output.add_repeat_attributes(device);
+        }
+    }
+
+    add_click_attributes(&output, &output, click, macro_binds);
 
-    pub fn main_loop(self) {
-        self.variant.run(self.shared);
+    output.create()?;
+    output.forward_feedback(debug, relayed);
+    if debug {
+        println!("Virtual output device created");
     }
+    Ok(Arc::new(output))
 }
 
-fn input_device_from_query(device_query: String) -> InputDevice {
-    'try_set_input: {
-        if device_query.is_empty() {
-            eprintln!("Device query is empty!");
-            std::process::exit(1);
+/// Opens the backend for replaying the recorded events
+fn open_replay_output(
+    backend: Backend,
+    debug: bool,
+    events: &[input_event],
+) -> Arc<dyn OutputBackend> {
+    match backend {
+        Backend::Uinput => {
+            let default = ClickOptions::default();
+            let output = OutputDevice::uinput_open(
+                PathBuf::from("/dev/uinput"),
+                &default.device_name,
+                default.device_identity(),
+            )
+            .and_then(|output| {
+                output.add_event_attributes(events);
+                output.create()?;
+                Ok(output)
+            })
+            .unwrap_or_else(|err| {
+                println!("Error: {}", err);
+                println!("Not having access to create device, try as root!");
+                std::process::exit(1);
+            });
+            if debug {
+                println!("Virtual output device created");
+            }
+            // Give the compositor time to pick up the new device
+            thread::sleep(std::time::Duration::from_millis(500));
+            Arc::new(output)
+        }
+        Backend::Wayland => {
+            let keyboard = events.iter().any(|event| {
+                event.type_ as i32 == input_linux::sys::EV_KEY
+                    && event.code < input_linux::sys::BTN_MISC as u16
+            });
+            let output = WaylandOutput::connect(keyboard, None).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+            Arc::new(output)
         }
+    }
+}
 
-        if device_query.starts_with('/') {
-            let Ok(device) = InputDevice::dev_open(PathBuf::from(&device_query)) else {
-                eprintln!("Cannot open device: {device_query}");
-                std::process::exit(2);
-            };
-            break 'try_set_input device;
-        } else {
-            let Some(device) = InputDevice::find_device(&device_query) else {
-                eprintln!("Cannot find device: {device_query}");
+/// The binds and the clicking of a profile from `--profile`
+struct Profile {
+    name: String,
+    binds: Vec<InputBind>,
+    hold: [bool; 2],
+    grab: bool,
+    macros: Vec<MacroBind>,
+    engine: Engine,
+}
 
-                std::process::exit(3);
+/// What the input thread switches with the profiles, the engine switches the clicking
+struct ProfileBinds {
+    name: String,
+    binds: Vec<InputBind>,
+    hold: [bool; 2],
+    grab: bool,
+}
+
+impl ProfileBinds {
+    /// Moves `steps` profiles forward in `queue`, the profiles left go to its back. Calls
+    /// `switch_grab` when the new profile changes the grab, then tells the engine to switch
+    fn switch(
+        &mut self,
+        queue: &mut VecDeque<ProfileBinds>,
+        steps: usize,
+        transmitter: &Sender<Message>,
+        switch_grab: impl FnOnce(bool),
+    ) {
+        let was_grabbing = self.grab;
+        for _ in 0..steps {
+            let Some(next) = queue.pop_front() else {
+                break;
             };
-            break 'try_set_input device;
+            queue.push_back(std::mem::replace(self, next));
+        }
+        if self.grab != was_grabbing {
+            switch_grab(self.grab);
         }
+        _ = transmitter.send(Message::Profile { steps });
+    }
+}
+
+/// Exits when the relayed events cannot be written, the uinput devices are created again instead of
+/// failing so this is a Wayland compositor that went away
+fn relayed_or_exit(result: io::Result<()>) {
+    if let Err(err) = result {
+        eprintln!("\x1b[0KCannot write to the virtual device, exiting: {err}");
+        std::process::exit(1);
     }
 }
 
-fn print_active(toggle: &AutoclickerState) {
+/// Grabs or gives back the input and extra input devices when the profile changes the grab, the
+/// relayed keys still down are released before
+fn switch_grab(
+    grab: bool,
+    input: &InputDevice,
+    extra_inputs: &[Option<InputDevice>],
+    relays: &mut [Relay],
+    output: &dyn OutputBackend,
+) {
+    if !grab {
+        for relay in relays {
+            relay.release(output);
+        }
+    }
+    for device in std::iter::once(input).chain(extra_inputs.iter().flatten()) {
+        if let Err(err) = device.grab(grab) {
+            eprintln!("\x1b[0KCannot grab {}: {err}", device.name);
+        }
+    }
+}
+
+/// Loads the `Run` command of a named profile of the config or of a config file, or exits if it
+/// cannot be loaded. The profile binds of the command line are added to it, and the multiplier
+/// applies to every profile
+fn load_profile(
+    path: &Path,
+    named: &BTreeMap<String, ConfigCommand>,
+    profile_binds: &[InputBind],
+    multiply: Option<u32>,
+) -> Profile {
+    let command = match named.get(&*path.to_string_lossy()) {
+        Some(command) => command.clone(),
+        None => {
+            let config = Config::load(path).unwrap_or_else(|err| {
+                eprintln!("Cannot load the profile {}: {err}", path.display());
+                std::process::exit(1);
+            });
+            config.command
+        }
+    };
+    let command = args::Command::from(command);
+    let bindings = command.bindings();
+    let args::Command::Run {
+        hold,
+        grab,
+        left_mode,
+        right_mode,
+        click,
+        ..
+    } = command
+    else {
+        eprintln!("The profile {} has to be a `Run` command", path.display());
+        std::process::exit(1);
+    };
+    if multiply.is_some() && !grab {
+        eprintln!(
+            "The profile {} has to grab, `multiply` applies to it",
+            path.display()
+        );
+        std::process::exit(1);
+    }
+
+    let (mut binds, macros, reactions, clicks) = bind_actions(&bindings);
+    binds.extend(profile_binds.iter().cloned());
+    let engine = Engine::from_options(&click)
+        .with_macros(&macros)
+        .with_reactions(&reactions)
+        .with_clicks(&clicks)
+        .with_multiplier(multiply, &click);
+    Profile {
+        name: path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+        binds,
+        hold: hold_modes(hold, left_mode, right_mode),
+        grab,
+        macros,
+        engine,
+    }
+}
+
+/// Hold mode of the left and right binds, the modes of the binds replace `hold`
+fn hold_modes(hold: bool, left_mode: Option<BindMode>, right_mode: Option<BindMode>) -> [bool; 2] {
+    [left_mode, right_mode].map(|mode| mode.map_or(hold, |mode| mode == BindMode::Hold))
+}
+
+/// The length of the longest chords that `code` triggers while the `held` keys are down
+fn longest_chord(binds: &[InputBind], code: u16, held: &HashSet<u16>) -> Option<usize> {
+    binds
+        .iter()
+        .filter(|bind| bind.bind.matches(code, held))
+        .map(|bind| bind.bind.0.len())
+        .max()
+}
+
+/// The binds of the input thread, with the macros, reactions and clicks of the engine
+type BindActions = (
+    Vec<InputBind>,
+    Vec<MacroBind>,
+    Vec<ReactBind>,
+    Vec<(Key, u32, Duration)>,
+);
+
+/// Resolves the binds into what the input thread matches, with the macros, reactions and
+/// clicks they play in the engine
+fn bind_actions(bindings: &[Binding]) -> BindActions {
+    let mut binds = Vec::new();
+    let mut macros = Vec::new();
+    let mut reactions = Vec::new();
+    let mut clicks = Vec::new();
+    for binding in bindings {
+        let action = match &binding.action {
+            BindingAction::Left => BindAction::Left,
+            BindingAction::Right => BindAction::Right,
+            BindingAction::Lock => BindAction::Lock,
+            BindingAction::Enable => BindAction::Enable,
+            BindingAction::Profile => BindAction::Profile,
+            BindingAction::Faster => BindAction::Cooldown { faster: true },
+            BindingAction::Slower => BindAction::Cooldown { faster: false },
+            BindingAction::Macro {
+                path,
+                times,
+                while_held,
+            } => {
+                macros.push(MacroBind {
+                    key: binding.key.key(),
+                    path: path.clone(),
+                    times: *times,
+                    while_held: *while_held,
+                });
+                BindAction::Macro(macros.len() - 1)
+            }
+            BindingAction::React { delay, jitter } => {
+                reactions.push(ReactBind {
+                    key: binding.key.key(),
+                    delay: *delay,
+                    jitter: *jitter,
+                });
+                BindAction::React(reactions.len() - 1)
+            }
+            BindingAction::Click { button } => {
+                clicks.push((button.key(), 1, Duration::ZERO));
+                BindAction::Click(clicks.len() - 1)
+            }
+            BindingAction::Burst {
+                clicks: times,
+                interval,
+                button,
+            } => {
+                clicks.push((button.key(), *times, interval.0));
+                BindAction::Click(clicks.len() - 1)
+            }
+        };
+        binds.push(InputBind {
+            bind: binding.key.clone(),
+            action,
+            on_release: binding.on_release,
+        });
+    }
+    (binds, macros, reactions, clicks)
+}
+
+fn macros_have_keys(macro_binds: &[MacroBind]) -> bool {
+    macro_binds
+        .iter()
+        .any(|bind| Macro::load_or_exit(&bind.path).has_keys())
+}
+
+/// Adds what the virtual devices need to send the configured clicks and macros, the keyboard
+/// is the same device unless they are split
+fn add_click_attributes(
+    output: &OutputDevice,
+    keyboard: &OutputDevice,
+    click: &ClickOptions,
+    macro_binds: &[MacroBind],
+) {
+    if click.needs_keyboard() || macros_have_keys(macro_binds) {
+        keyboard.add_keyboard_attributes();
+    }
+    if click.needs_gamepad() {
+        output.add_gamepad_attributes();
+    }
+    if click.needs_wheel() || !macro_binds.is_empty() {
+        output.add_wheel_attributes();
+    }
+    if click.needs_pointer() || !macro_binds.is_empty() {
+        output.add_pointer_attributes();
+    }
+    if let Some(screen) = click.screen.filter(|_| !click.positions.is_empty()) {
+        output.add_absolute_attributes(screen.width, screen.height);
+    }
+}
+
+/// Warns about the keys of the binds that none of the devices sends, the binds would never work
+fn warn_missing_keys<'a>(binds: impl Iterator<Item = &'a Bind>, devices: &[&InputDevice]) {
+    let mut missing: Vec<u16> = binds
+        .flat_map(|bind| bind.0.iter().copied())
+        .filter(|code| !devices.iter().any(|device| device.has_key(*code)))
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+    for code in missing {
+        eprintln!(
+            "\x1B[1;33mWarning: no input device sends this bind key: {}\x1B[0;39m",
+            KeyCode(code)
+        );
+    }
+}
+
+fn input_device_from_query(device_query: String) -> InputDevice {
+    let device = resolve_device_query(&device_query).unwrap_or_else(|(code, err)| {
+        eprintln!("{err}");
+        std::process::exit(code);
+    });
+    if device_query == "auto" {
+        println!("Found the mouse: {}", device.name);
+    }
+    device
+}
+
+/// Finds the device of the query, or the exit code and the message when there is none
+fn resolve_device_query(device_query: &str) -> Result<InputDevice, (i32, String)> {
+    if device_query.is_empty() {
+        return Err((1, "Device query is empty!".to_string()));
+    }
+
+    if device_query.starts_with('/') {
+        let path = PathBuf::from(device_query);
+        if !path.exists() {
+            return Err((2, format!("Cannot open device: {device_query}")));
+        }
+        InputDevice::dev_open(path).map_err(|_| (2, format!("Cannot open device: {device_query}")))
+    } else if let Some(pattern) = device_query.strip_prefix("re:") {
+        let pattern = DeviceRegex::new(pattern).map_err(|err| (1, err))?;
+        let mut devices = InputDevice::find_matching(&pattern);
+        match devices.len() {
+            0 => Err((3, format!("No device matches: {pattern}"))),
+            1 => Ok(devices.remove(0)),
+            _ => {
+                let mut err = format!("Several devices match {pattern}, make it more precise:");
+                for device in devices {
+                    err.push_str(&format!("\n\t{}", device.name));
+                }
+                Err((3, err))
+            }
+        }
+    } else {
+        InputDevice::find_device(device_query).ok_or_else(|| {
+            if device_query == "auto" {
+                (3, "Cannot find a mouse with a left button and relative axes".to_string())
+            } else {
+                (3, format!("Cannot find device: {device_query}"))
+            }
+        })
+    }
+}
+
+/// `paused_by` lists the pause sources, shown while paused
+fn print_active(toggle: &AutoclickerState, paused_by: &str) {
     let is_terminal = stdout().is_terminal();
 
     if is_terminal {
@@ -500,6 +2114,15 @@ fn print_active(toggle: &AutoclickerState) {
     if toggle.lock {
         print!("LOCKED: ")
     }
+    if toggle.resting {
+        print!("RESTING: ")
+    }
+    if toggle.paused {
+        print!("PAUSED by {paused_by} ({} pauses): ", toggle.pauses)
+    }
+    if toggle.cooldown_adjust != 0 {
+        print!("cooldown {:+}ms: ", toggle.cooldown_adjust)
+    }
     if toggle.left {
         print!("left ")
     }
@@ -516,7 +2139,37 @@ fn print_active(toggle: &AutoclickerState) {
     }
 }
 
-fn command_from_user_input() -> args::Command {
+/// Shown on the status line until the countdown of `--start-delay` ends
+fn print_countdown(seconds: u64) {
+    let is_terminal = stdout().is_terminal();
+
+    if is_terminal {
+        print!("\x1b[0K");
+    }
+    println!("Starting in {seconds}s, press a bind to start now");
+    if is_terminal {
+        print!("\x1b[1F");
+    }
+}
+
+/// The interactive setup, offers to save the command in a config
+fn command_from_user_input(backend: Backend, blacklist: &[u16]) -> args::Command {
+    let command = setup_from_user_input(blacklist);
+
+    // Offer to save configuration
+    if choose_yes("Do you want to save this configuration to a file?", false) {
+        let config_path = choose_string(
+            "Enter the path for the config file",
+            Some(Config::default_path().to_string_lossy().into_owned()),
+        );
+        save_setup(&command, backend, Path::new(&config_path));
+    }
+
+    command
+}
+
+/// The questions of the interactive setup
+fn setup_from_user_input(blacklist: &[u16]) -> args::Command {
     let input_device = InputDevice::select_device();
 
     println!("Device name: {}", input_device.name);
@@ -530,18 +2183,21 @@ fn command_from_user_input() -> args::Command {
             choose_usize("Choose cooldown between press and release", Some(0)) as u64;
 
         args::Command::RunLegacy {
-            device_query: input_device.path.to_str().unwrap().to_owned(),
-            cooldown,
-            cooldown_press_release,
+            device_query: input_device.stable_path().to_string_lossy().into_owned(),
+            click: ClickOptions {
+                cooldown,
+                cooldown_press_release,
+                ..Default::default()
+            },
         }
     } else {
         let lock_unlock_bind = choose_yes(
             "Lock Unlock mode, useful for mouse without side buttons",
             false,
         )
-        .then(|| choose_key(&input_device, "lock_unlock_bind"));
-        let left_bind = choose_key(&input_device, "left_bind");
-        let right_bind = choose_key(&input_device, "right_bind");
+        .then(|| choose_key(&input_device, "lock_unlock_bind", blacklist).into());
+        let left_bind = Some(choose_key(&input_device, "left_bind", blacklist).into());
+        let right_bind = Some(choose_key(&input_device, "right_bind", blacklist).into());
         let hold = choose_yes("You want to hold the bind / active hold_mode?", true);
         println!("\x1B[1;33mWarning: if you enable grab mode you can get softlocked\x1B[0;39m, if the compositor will not use TheClicker device.");
         println!("If the device input is grabbed, the input device will be emulated by TheClicker, and when you press a binding that will not be sent");
@@ -565,60 +2221,67 @@ fn command_from_user_input() -> args::Command {
             hold,
             grab,
             lock_unlock_bind,
-            cooldown,
-            cooldown_press_release,
-            device_query: input_device.path.to_str().unwrap().to_owned(),
+            macro_binds: Vec::new(),
+            react_binds: Vec::new(),
+            record_raw: None,
+            multiply: None,
+            double_tap: None,
+            long_press: None,
+            left_mode: None,
+            right_mode: None,
+            faster_bind: None,
+            slower_bind: None,
+            binds: Vec::new(),
+            enable_bind: None,
+            profiles: Vec::new(),
+            profile_bind: None,
+            panic_bind: None,
+            bind_device: None,
+            start_active: None,
+            start_delay: None,
+            override_main: false,
+            extra_devices: Vec::new(),
+            no_regrab: false,
+            control: None,
+            click: ClickOptions {
+                cooldown,
+                cooldown_press_release,
+                ..Default::default()
+            },
+            device_query: input_device.stable_path().to_string_lossy().into_owned(),
         }
     };
 
-    // Offer to save configuration
-    if choose_yes("Do you want to save this configuration to a file?", false) {
-        let config_path = choose_string("Enter the path for the config file", Some("config.json".to_string()));
-        let config = Config {
-            debug: false,
-            beep: false,
-            command: match &command {
-                args::Command::Run {
-                    device_query,
-                    left_bind,
-                    right_bind,
-                    lock_unlock_bind,
-                    hold,
-                    grab,
-                    cooldown,
-                    cooldown_press_release,
-                } => ConfigCommand::Run {
-                    device_query: device_query.clone(),
-                    left_bind: *left_bind,
-                    right_bind: *right_bind,
-                    lock_unlock_bind: *lock_unlock_bind,
-                    hold: *hold,
-                    grab: *grab,
-                    cooldown: *cooldown,
-                    cooldown_press_release: *cooldown_press_release,
-                },
-                args::Command::RunLegacy {
-                    device_query,
-                    cooldown,
-                    cooldown_press_release,
-                } => ConfigCommand::RunLegacy {
-                    device_query: device_query.clone(),
-                    cooldown: *cooldown,
-                    cooldown_press_release: *cooldown_press_release,
-                },
-            },
-        };
+    command
+}
 
-        match config.save_to_file(&PathBuf::from(&config_path)) {
-            Ok(()) => println!("\x1B[1;32mConfiguration saved to {}\x1B[0;39m", config_path),
-            Err(e) => eprintln!("\x1B[1;31mError saving configuration: {}\x1B[0;39m", e),
+/// Saves the command of the setup in a config, returns if it is saved
+fn save_setup(command: &args::Command, backend: Backend, path: &Path) -> bool {
+    let config = Config {
+        version: config::VERSION,
+        debug: false,
+        beep: false,
+        beep_sound: BeepSound::default(),
+        backend,
+        command: ConfigCommand::try_from(command.clone())
+            .expect("the setup only makes run commands"),
+        profiles: BTreeMap::new(),
+    };
+
+    match config.save_to_file(&path.to_path_buf()) {
+        Ok(()) => {
+            println!("\x1B[1;32mConfiguration saved to {}\x1B[0;39m", path.display());
+            true
+        }
+        Err(e) => {
+            eprintln!("\x1B[1;31mError saving configuration: {}\x1B[0;39m", e);
+            false
         }
     }
-
-    command
 }
 
-fn choose_key(input_device: &InputDevice, name: &str) -> u16 {
+/// Asks for the key again when it is blacklisted, the blacklist keeps Ctrl+C working
+fn choose_key(input_device: &InputDevice, name: &str, blacklist: &[u16]) -> u16 {
     let mut events: [input_linux::sys::input_event; 1] = unsafe { std::mem::zeroed() };
     std::thread::sleep(WAIT_KEY_RELEASE);
     println!("\x1B[1;33mWaiting for key presses from the selected device\x1B[22;39m");
@@ -637,12 +2300,11 @@ fn choose_key(input_device: &InputDevice, name: &str) -> u16 {
 
         println!("\t{}", KeyCode(events[0].code));
 
-        if matches!(
-            events[0].code as i32,
-            input_linux::sys::KEY_LEFTCTRL | input_linux::sys::KEY_C
-        ) {
-            println!("\x1B[1;31mThis key is blacklisted\x1B[22;39m");
-            std::process::exit(10);
+        if blacklist.contains(&events[0].code) {
+            println!("\x1B[1;31mThis key is blacklisted, choose another one\x1B[22;39m");
+            std::thread::sleep(WAIT_KEY_RELEASE);
+            _ = input_device.grab(true);
+            continue;
         }
 
         if choose_yes("You want to choose this", true) {
@@ -0,0 +1,207 @@
+use std::{
+    io,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use input_linux::{sys::input_event, Key};
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use serde::{Deserialize, Serialize};
+
+use crate::{raw, InputDevice};
+
+/// What a recorded event does
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum MacroAction {
+    Key { key: u16, pressed: bool },
+    /// Relative pointer movement
+    Move { x: i32, y: i32 },
+    /// Wheel notches, positive is up
+    Scroll { scroll: i32 },
+}
+
+/// `at` is in milliseconds from the start of the macro
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MacroEvent {
+    pub at: u64,
+    #[serde(flatten)]
+    pub action: MacroAction,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Macro {
+    pub events: Vec<MacroEvent>,
+}
+
+impl Macro {
+    /// Loads a macro, or an `evemu-record` recording
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        let recorded: Self = if text.starts_with("# EVEMU") {
+            Self::from_evemu(&text)?
+        } else {
+            serde_json::from_str(&text)?
+        };
+        recorded.check_keys()?;
+        Ok(recorded)
+    }
+
+    /// The keys have to be ones the virtual device can send, so playing never fails
+    fn check_keys(&self) -> Result<(), String> {
+        for event in &self.events {
+            if let MacroAction::Key { key, .. } = event.action {
+                if Key::from_code(key).is_err() {
+                    return Err(format!("invalid keycode {key} at {}ms", event.at));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the `E:` lines of an `evemu-record` recording
+    pub fn from_evemu(text: &str) -> Result<Self, String> {
+        let mut recorder = Recorder::default();
+        for event in raw::parse_evemu(text)? {
+            recorder.push(&event);
+        }
+        Ok(Self {
+            events: recorder.events,
+        })
+    }
+
+    /// Exits when the macro cannot be loaded
+    pub fn load_or_exit(path: &Path) -> Self {
+        Self::load(path).unwrap_or_else(|err| {
+            eprintln!("Cannot load the macro {}: {err}", path.display());
+            std::process::exit(1);
+        })
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Exits when the macro cannot be saved
+    pub fn save_or_exit(&self, path: &Path) {
+        match self.save_to_file(path) {
+            Ok(()) => println!(
+                "\x1B[1;32mSaved {} events to {}\x1B[0;39m",
+                self.events.len(),
+                path.display()
+            ),
+            Err(err) => {
+                eprintln!("\x1B[1;31mError saving the macro: {err}\x1B[0;39m");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    pub fn has_keys(&self) -> bool {
+        self.events.iter().any(|event| {
+            matches!(event.action, MacroAction::Key { key, .. } if !is_mouse_button(key))
+        })
+    }
+}
+
+fn is_mouse_button(code: u16) -> bool {
+    (input_linux::sys::BTN_MOUSE..=input_linux::sys::BTN_TASK).contains(&(code as i32))
+}
+
+/// Turns the raw events into macro events, the pointer movement is summed for every frame
+#[derive(Default)]
+struct Recorder {
+    start: Option<Duration>,
+    events: Vec<MacroEvent>,
+    x: i32,
+    y: i32,
+}
+
+impl Recorder {
+    fn push(&mut self, event: &input_event) {
+        let time = Duration::new(event.time.tv_sec as u64, event.time.tv_usec as u32 * 1000);
+        let start = *self.start.get_or_insert(time);
+        let at = time.saturating_sub(start).as_millis() as u64;
+
+        let action = match (event.type_ as i32, event.code as i32) {
+            // The autorepeat is not recorded
+            (input_linux::sys::EV_KEY, _) if event.value != 2 => MacroAction::Key {
+                key: event.code,
+                pressed: event.value == 1,
+            },
+            (input_linux::sys::EV_REL, input_linux::sys::REL_X) => {
+                self.x += event.value;
+                return;
+            }
+            (input_linux::sys::EV_REL, input_linux::sys::REL_Y) => {
+                self.y += event.value;
+                return;
+            }
+            (input_linux::sys::EV_REL, input_linux::sys::REL_WHEEL) => MacroAction::Scroll {
+                scroll: event.value,
+            },
+            (input_linux::sys::EV_SYN, input_linux::sys::SYN_REPORT)
+                if self.x != 0 || self.y != 0 =>
+            {
+                let (x, y) = (self.x, self.y);
+                (self.x, self.y) = (0, 0);
+                MacroAction::Move { x, y }
+            }
+            _ => return,
+        };
+        self.events.push(MacroEvent { at, action });
+    }
+}
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: i32) {
+    STOP.store(true, Ordering::Relaxed);
+}
+
+/// Records the events of the input device until Ctrl+C or the stop key, then saves the macro
+pub fn record(input: &InputDevice, path: &Path, stop_key: Option<u16>) {
+    // Without SA_RESTART the blocked read returns when Ctrl+C is pressed
+    let action = SigAction::new(
+        SigHandler::Handler(on_interrupt),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    unsafe { sigaction(Signal::SIGINT, &action) }.expect("Cannot handle Ctrl+C");
+
+    print!("Recording {}, stop with Ctrl+C", input.name);
+    match stop_key {
+        Some(key) => println!(" or keycode {key}"),
+        None => println!(),
+    }
+
+    let mut recorder = Recorder::default();
+    let mut events: [input_event; 16] = unsafe { std::mem::zeroed() };
+    'record: while !STOP.load(Ordering::Relaxed) {
+        let len = match input.read(&mut events) {
+            Ok(len) => len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                eprintln!("Cannot read from input device: {err}");
+                break;
+            }
+        };
+
+        for event in &events[..len] {
+            let is_stop_key = event.type_ as i32 == input_linux::sys::EV_KEY
+                && stop_key.is_some_and(|key| event.code == key);
+            if is_stop_key {
+                break 'record;
+            }
+            recorder.push(event);
+        }
+    }
+
+    Macro {
+        events: recorder.events,
+    }
+    .save_or_exit(path);
+}
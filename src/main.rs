@@ -1,10 +1,11 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use theclicker::{Args, TheClicker};
 
 fn main() {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
     
-    let args = match args.load_from_config_or_default() {
+    let args = match args.load_from_config_or_default(&matches) {
         Ok(args) => args,
         Err(e) => {
             eprintln!("Error loading configuration: {}", e);
@@ -0,0 +1,259 @@
+use std::{
+    collections::HashSet,
+    os::fd::AsRawFd,
+    sync::{mpsc::Sender, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use input_linux::sys::input_event;
+
+use crate::{
+    args::{ClickOptions, OverrideLost},
+    input_device_from_query, Bind, Hotplug, Identity, InputDevice, KeyCode, Message, OutputBackend,
+    PanicBind, PauseSource, Relay,
+};
+
+/// Pauses the autoclicker while an override key is held, on other devices than the input one
+pub struct Overrides {
+    /// The queries are kept to find the devices again when they come back
+    devices: Vec<(String, InputDevice)>,
+    keys: Vec<Bind>,
+    /// Any key pauses, until no key was pressed for this long
+    any_quiet: Option<Duration>,
+    invert: bool,
+    /// Paused while a device is gone
+    lost_pause: bool,
+    /// Grab the devices, the override keys are swallowed and the rest is relayed
+    grab: bool,
+    /// Checked on the override devices too, they can be grabbed
+    panic_bind: Option<PanicBind>,
+    debug: bool,
+}
+
+impl Overrides {
+    pub fn from_options(options: &ClickOptions, debug: bool) -> Option<Self> {
+        let mut queries = options.override_devices.clone();
+        if options.override_auto {
+            let Some(keyboard) = InputDevice::find_keyboard() else {
+                eprintln!("Cannot find a keyboard for the override");
+                std::process::exit(3);
+            };
+            println!("Override keyboard: {}", keyboard.name);
+            queries.push(keyboard.path.to_string_lossy().into_owned());
+        }
+        if queries.is_empty() {
+            return None;
+        }
+        if options.override_keys.is_empty() && !options.override_any {
+            eprintln!("The override devices need `--override-key` or `--override-any`");
+            std::process::exit(1);
+        }
+
+        let devices: Vec<_> = queries
+            .into_iter()
+            .map(|query| (query.clone(), input_device_from_query(query)))
+            .collect();
+        let mut codes: Vec<u16> = options
+            .override_keys
+            .iter()
+            .flat_map(|bind| bind.0.iter().copied())
+            .collect();
+        codes.sort_unstable();
+        codes.dedup();
+        for code in codes {
+            if !devices.iter().any(|(_, device)| device.has_key(code)) {
+                eprintln!(
+                    "\x1B[1;33mWarning: no override device sends this key: {}\x1B[0;39m",
+                    KeyCode(code)
+                );
+            }
+        }
+
+        Some(Self {
+            devices,
+            keys: options.override_keys.clone(),
+            any_quiet: options.override_any.then_some(options.override_quiet.0),
+            invert: options.override_invert,
+            lost_pause: options.override_lost == OverrideLost::Pause,
+            grab: options.override_grab && !options.dry_run,
+            panic_bind: None,
+            debug,
+        })
+    }
+
+    pub fn with_panic_bind(self, panic_bind: Option<PanicBind>) -> Self {
+        Self { panic_bind, ..self }
+    }
+
+    pub fn devices(&self) -> Vec<&InputDevice> {
+        self.devices.iter().map(|(_, device)| device).collect()
+    }
+
+    /// The devices relayed through the virtual device
+    pub fn grabbed(&self) -> Vec<&InputDevice> {
+        if self.grab {
+            self.devices.iter().map(|(_, device)| device).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// One thread for each device, with its own pause source so they do not resume each other
+    pub fn spawn(self, sender: Sender<Message>, output: Arc<dyn OutputBackend>) {
+        for (query, mut device) in self.devices {
+            let mut pause = PauseSource::keys(format!("override {}", device.name), sender.clone());
+            let keys = self.keys.clone();
+            let any_quiet = self.any_quiet;
+            // The held keys enable the clicking when inverted
+            let lost_held = self.lost_pause != self.invert;
+            let debug = self.debug;
+            if debug {
+                println!("Override device: {}", device.name);
+            }
+            let output = self.grab.then(|| output.clone());
+            let panic_bind = self.panic_bind.clone();
+            if let Some(panic_bind) = &panic_bind {
+                panic_bind.watch(&device);
+            }
+            if output.is_some() {
+                if let Err(err) = device.grab(true) {
+                    eprintln!("Cannot grab the override device {}: {err}", device.name);
+                    std::process::exit(1);
+                }
+            }
+
+            thread::spawn(move || {
+                let mut held = OverrideKeys::default();
+                // The keys down for the chords of the panic bind
+                let mut down = HashSet::new();
+                let mut quiet_at: Option<Instant> = None;
+                let mut relay = Relay::default();
+                let mut events: [input_event; 64] = unsafe { std::mem::zeroed() };
+                loop {
+                    if let Some(at) = quiet_at {
+                        if !readable(&device, at.saturating_duration_since(Instant::now())) {
+                            if debug {
+                                println!("Quiet on the override device {}", device.name);
+                            }
+                            quiet_at = None;
+                            pause.set(false);
+                            continue;
+                        }
+                    }
+                    let count = match device.read(&mut events) {
+                        Ok(count) => count,
+                        Err(err) => {
+                            eprintln!(
+                                "\x1b[0KLost the override device {}, waiting for it: {err}",
+                                device.name
+                            );
+                            held = OverrideKeys::default();
+                            down.clear();
+                            quiet_at = None;
+                            pause.set(lost_held);
+                            if let Some(output) = output.as_deref() {
+                                relay.release(output);
+                            }
+                            device = Hotplug::new().wait_for(&query, &Identity::of(&device));
+                            eprintln!("\x1b[0KThe override device is back: {}", device.name);
+                            if let Some(panic_bind) = &panic_bind {
+                                panic_bind.watch(&device);
+                            }
+                            if output.is_some() {
+                                if let Err(err) = device.grab(true) {
+                                    eprintln!(
+                                        "\x1b[0KCannot grab the override device again: {err}"
+                                    );
+                                }
+                            }
+                            pause.set(false);
+                            continue;
+                        }
+                    };
+
+                    for event in &events[..count] {
+                        let mut consumed = false;
+                        if event.type_ as i32 == input_linux::sys::EV_KEY {
+                            match event.value {
+                                0 => _ = down.remove(&event.code),
+                                1 => {
+                                    if let Some(panic_bind) = &panic_bind {
+                                        panic_bind.check(event.code, &down);
+                                    }
+                                    down.insert(event.code);
+                                }
+                                _ => {}
+                            }
+                            // Typing, every key event pushes the quiet period back
+                            if let Some(quiet) = any_quiet {
+                                if event.value == 1 || quiet_at.is_some() {
+                                    quiet_at = Some(Instant::now() + quiet);
+                                    pause.set(true);
+                                }
+                            } else {
+                                let was_active = held.consumes(event.code);
+                                let paused = held.update(&keys, event);
+                                consumed = was_active || held.consumes(event.code);
+                                if debug && event.value != 2 {
+                                    println!("Override combo held on {}: {paused}", device.name);
+                                }
+                                pause.set(paused);
+                            }
+                        }
+                        if let Some(output) = output.as_deref() {
+                            _ = relay.event(output, event, consumed);
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// The combos held on a device, a combo holds from the press of its last key with the others
+/// down, until that key is released
+#[derive(Default)]
+pub struct OverrideKeys {
+    held: HashSet<u16>,
+    active: HashSet<u16>,
+}
+
+impl OverrideKeys {
+    /// Follows a key event, true while an override combo is held
+    pub fn update(&mut self, binds: &[Bind], event: &input_event) -> bool {
+        match event.value {
+            1 => {
+                if binds
+                    .iter()
+                    .any(|bind| bind.matches(event.code, &self.held))
+                {
+                    self.active.insert(event.code);
+                }
+                self.held.insert(event.code);
+            }
+            0 => {
+                self.held.remove(&event.code);
+                self.active.remove(&event.code);
+            }
+            _ => {}
+        }
+        !self.active.is_empty()
+    }
+
+    /// If the events of the key are swallowed, it triggered a combo that is still held
+    pub fn consumes(&self, code: u16) -> bool {
+        self.active.contains(&code)
+    }
+}
+
+/// Waits up to `timeout` for an event of the device
+fn readable(device: &InputDevice, timeout: Duration) -> bool {
+    let mut poll = libc::pollfd {
+        fd: device.handler.as_inner().as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis() as i32 + 1;
+    unsafe { libc::poll(&mut poll, 1, timeout) > 0 }
+}
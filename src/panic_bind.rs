@@ -0,0 +1,68 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use input_linux::{Key, KeyState};
+
+use crate::{Bind, InputDevice, OutputBackend};
+
+/// Exits right away, even when locked, checked on every device that is read so a grab never
+/// leaves the user without a way out
+#[derive(Clone)]
+pub struct PanicBind {
+    bind: Bind,
+    output: Arc<dyn OutputBackend>,
+    /// Handles on the devices that are read, by their path, so any thread ungrabs all of them
+    devices: Arc<Mutex<HashMap<PathBuf, InputDevice>>>,
+}
+
+impl PanicBind {
+    pub fn new(bind: Bind, output: Arc<dyn OutputBackend>) -> Self {
+        Self {
+            bind,
+            output,
+            devices: Arc::default(),
+        }
+    }
+
+    /// Ungrabs the device on exit, again when it is found again
+    pub fn watch(&self, device: &InputDevice) {
+        if let Ok(handle) = device.try_clone() {
+            let mut devices = self.devices.lock().unwrap();
+            devices.insert(device.path.clone(), handle);
+        }
+    }
+
+    /// Exits if the press of `code` with the `held` keys is the panic bind
+    pub fn check(&self, code: u16, held: &HashSet<u16>) {
+        if self.bind.matches(code, held) {
+            self.exit(held);
+        }
+    }
+
+    /// Releases the buttons and the relayed keys of the virtual device, ungrabs every device and
+    /// exits
+    fn exit(&self, held: &HashSet<u16>) -> ! {
+        let buttons = [
+            Key::ButtonLeft,
+            Key::ButtonRight,
+            Key::ButtonMiddle,
+            Key::ButtonSide,
+            Key::ButtonExtra,
+        ];
+        let keys = held.iter().filter_map(|code| Key::from_code(*code).ok());
+        for key in buttons.into_iter().chain(keys) {
+            self.output.send_key(key, KeyState::RELEASED);
+        }
+        // The devices are still usable when a thread panicked with the lock
+        let devices = self.devices.lock().unwrap_or_else(|err| err.into_inner());
+        for device in devices.values() {
+            _ = device.grab(false);
+        }
+        self.output.destroy();
+        println!("\x1b[0KThe panic bind was pressed, exiting");
+        std::process::exit(0);
+    }
+}
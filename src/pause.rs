@@ -0,0 +1,42 @@
+use std::sync::mpsc::Sender;
+
+use crate::Message;
+
+/// Pauses the clicking from outside of the binds, through the engine channel
+pub struct PauseSource {
+    name: String,
+    sender: Sender<Message>,
+    paused: bool,
+    keys: bool,
+}
+
+impl PauseSource {
+    pub fn new(name: impl Into<String>, sender: Sender<Message>) -> Self {
+        Self {
+            name: name.into(),
+            sender,
+            paused: false,
+            keys: false,
+        }
+    }
+
+    /// Held override keys, that enable the clicking instead with `--override-invert`
+    pub fn keys(name: impl Into<String>, sender: Sender<Message>) -> Self {
+        Self {
+            keys: true,
+            ..Self::new(name, sender)
+        }
+    }
+
+    /// Only tells the engine when it changes
+    pub fn set(&mut self, paused: bool) {
+        if paused != self.paused {
+            self.paused = paused;
+            _ = self.sender.send(Message::Override {
+                source: self.name.clone(),
+                paused,
+                keys: self.keys,
+            });
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::{env, path::PathBuf, thread, time::Duration};
+
+use crate::{args::ClickOptions, OutputBackend, PauseSource};
+
+/// Adds bind actions and pause sources without patching lib.rs,
+/// the plugins are compiled in and listed in `registry`
+pub trait Plugin: Send {
+    /// Used by `--plugin` and as the name of its pause source
+    fn name(&self) -> &'static str;
+
+    /// Keycodes handled by `on_bind`, they are not relayed in grab mode
+    fn binds(&self) -> Vec<u16> {
+        Vec::new()
+    }
+
+    /// One of the `binds` was pressed or released, called from the input thread
+    fn on_bind(&mut self, _key: u16, _pressed: bool, _output: &dyn OutputBackend) {}
+
+    /// Starts the plugin, the pause source pauses the clicking like the window rules
+    fn start(&mut self, _pause: PauseSource) {}
+}
+
+/// The plugins that can be enabled with `--plugin`
+pub fn registry() -> Vec<Box<dyn Plugin>> {
+    vec![Box::new(PauseFile)]
+}
+
+/// The plugins enabled in the options, or exits if one is unknown
+pub fn from_options(options: &ClickOptions) -> Vec<Box<dyn Plugin>> {
+    options
+        .plugins
+        .iter()
+        .map(|name| {
+            registry()
+                .into_iter()
+                .find(|plugin| plugin.name() == name)
+                .unwrap_or_else(|| {
+                    let names: Vec<_> = registry().iter().map(|plugin| plugin.name()).collect();
+                    eprintln!("Unknown plugin {name:?}, the plugins are: {}", names.join(", "));
+                    std::process::exit(1);
+                })
+        })
+        .collect()
+}
+
+/// Pauses while `$XDG_RUNTIME_DIR/theclicker.pause` exists, so other programs can pause
+/// the clicking with `touch`
+struct PauseFile;
+
+impl Plugin for PauseFile {
+    fn name(&self) -> &'static str {
+        "pause-file"
+    }
+
+    fn start(&mut self, mut pause: PauseSource) {
+        let path = env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(env::temp_dir)
+            .join("theclicker.pause");
+        thread::spawn(move || loop {
+            pause.set(path.exists());
+            thread::sleep(Duration::from_millis(500));
+        });
+    }
+}
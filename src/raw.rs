@@ -0,0 +1,100 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use input_linux::sys::{input_event, timeval};
+
+use crate::OutputBackend;
+
+/// Writes every event read from the input device, in the `evemu-record` format
+pub struct RawRecorder {
+    file: BufWriter<File>,
+    start: Option<Duration>,
+}
+
+impl RawRecorder {
+    pub fn create(path: &Path, device_name: &str) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "# EVEMU 1.3")?;
+        writeln!(file, "# Recorded by TheClicker")?;
+        writeln!(file, "N: {device_name}")?;
+        Ok(Self { file, start: None })
+    }
+
+    /// Flushed on every `SYN_REPORT`, so the recording survives Ctrl+C
+    pub fn write(&mut self, event: &input_event) -> io::Result<()> {
+        let time = event_time(event);
+        let time = time.saturating_sub(*self.start.get_or_insert(time));
+        writeln!(
+            self.file,
+            "E: {}.{:06} {:04x} {:04x} {:04}",
+            time.as_secs(),
+            time.subsec_micros(),
+            event.type_,
+            event.code,
+            event.value
+        )?;
+        if event.type_ as i32 == input_linux::sys::EV_SYN {
+            self.file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn event_time(event: &input_event) -> Duration {
+    Duration::new(event.time.tv_sec as u64, event.time.tv_usec as u32 * 1000)
+}
+
+/// Reads the `E:` lines of an `evemu-record` recording
+pub fn parse_evemu(text: &str) -> Result<Vec<input_event>, String> {
+    let mut events = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        // E: 1.234567 0001 0110 0001	# EV_KEY / BTN_LEFT             1
+        let Some(event) = line.strip_prefix("E:") else {
+            continue;
+        };
+        let error = || format!("Invalid evemu event on line {}: {line}", number + 1);
+        let mut fields = event.split('#').next().unwrap().split_whitespace();
+        let mut next = || fields.next().ok_or_else(error);
+
+        let (secs, usecs) = next()?.split_once('.').ok_or_else(error)?;
+        let hex = |field: &str| u16::from_str_radix(field, 16).map_err(|_| error());
+        let type_ = hex(next()?)?;
+        let code = hex(next()?)?;
+        let value = next()?.parse().map_err(|_| error())?;
+
+        events.push(input_event {
+            time: timeval {
+                tv_sec: secs.parse().map_err(|_| error())?,
+                tv_usec: usecs.parse().map_err(|_| error())?,
+            },
+            type_,
+            code,
+            value,
+        });
+    }
+    Ok(events)
+}
+
+/// Sends the events with their original timing, one frame at a time
+pub fn replay(events: &[input_event], output: &dyn OutputBackend) -> io::Result<()> {
+    let Some(first) = events.first() else {
+        return Ok(());
+    };
+    let first = event_time(first);
+    let start = Instant::now();
+
+    for frame in events.split_inclusive(|event| {
+        event.type_ as i32 == input_linux::sys::EV_SYN
+            && event.code as i32 == input_linux::sys::SYN_REPORT
+    }) {
+        let at = start + event_time(&frame[0]).saturating_sub(first);
+        thread::sleep(at.saturating_duration_since(Instant::now()));
+        output.write(frame)?;
+    }
+    Ok(())
+}
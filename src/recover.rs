@@ -0,0 +1,171 @@
+use std::{
+    collections::HashSet,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use input_linux::{sys::input_event, Key, KeyState, RelativeAxis};
+
+use crate::{
+    device::{key_events, position_events, relative_events, scroll_axes},
+    OutputBackend, PauseSource,
+};
+
+/// Writes retried on `EAGAIN` before the virtual device is given up
+const RETRIES: u32 = 5;
+
+/// Waits between the attempts to create the virtual device again, doubled up to `MAX_BACKOFF`
+const BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+pub type CreateOutput = Box<dyn Fn() -> io::Result<Arc<dyn OutputBackend>> + Send + Sync>;
+
+/// Creates the virtual devices again in the background when writing to them fails, like when the
+/// uinput node went away, the clicking is paused and the events are dropped until they are back
+pub struct RecoveringOutput(Arc<Inner>);
+
+struct Inner {
+    output: RwLock<Arc<dyn OutputBackend>>,
+    create: CreateOutput,
+    recovering: AtomicBool,
+    /// The keys pressed through it, released before the device is replaced
+    down: Mutex<HashSet<u16>>,
+    pause: Mutex<Option<PauseSource>>,
+}
+
+impl RecoveringOutput {
+    pub fn new(output: Arc<dyn OutputBackend>, create: CreateOutput) -> Self {
+        Self(Arc::new(Inner {
+            output: RwLock::new(output),
+            create,
+            recovering: AtomicBool::new(false),
+            down: Mutex::new(HashSet::new()),
+            pause: Mutex::new(None),
+        }))
+    }
+
+    fn current(&self) -> Arc<dyn OutputBackend> {
+        self.0.output.read().unwrap().clone()
+    }
+
+    /// Replaces the failed device, unless it was already replaced or is being replaced
+    fn recover(&self, failed: &Arc<dyn OutputBackend>, err: io::Error) {
+        if !Arc::ptr_eq(&self.current(), failed) || self.0.recovering.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        eprintln!("\x1b[0KCannot write to the virtual device, creating it again: {err}");
+        self.0.set_paused(true);
+
+        for code in self.0.down.lock().unwrap().drain() {
+            if let Ok(key) = Key::from_code(code) {
+                _ = failed.write(&key_events(key, KeyState::RELEASED));
+            }
+        }
+        failed.destroy();
+
+        let inner = self.0.clone();
+        thread::spawn(move || {
+            let mut backoff = BACKOFF;
+            let output = loop {
+                match (inner.create)() {
+                    Ok(output) => break output,
+                    Err(err) => {
+                        eprintln!(
+                            "\x1b[0KCannot create the virtual device, retrying in {backoff:?}: {err}"
+                        );
+                        thread::sleep(backoff);
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                }
+            };
+            *inner.output.write().unwrap() = output;
+            inner.recovering.store(false, Ordering::SeqCst);
+            eprintln!("\x1b[0KThe virtual device is back");
+            inner.set_paused(false);
+        });
+    }
+}
+
+impl Inner {
+    fn set_paused(&self, paused: bool) {
+        if let Some(pause) = self.pause.lock().unwrap().as_mut() {
+            pause.set(paused);
+        }
+    }
+
+    fn track(&self, events: &[input_event]) {
+        let mut down = self.down.lock().unwrap();
+        for event in events {
+            if event.type_ as i32 == input_linux::sys::EV_KEY {
+                match event.value {
+                    0 => _ = down.remove(&event.code),
+                    1 => _ = down.insert(event.code),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl OutputBackend for RecoveringOutput {
+    /// Retries the busy device a few times before replacing it, nothing is written while it is
+    /// being replaced. Never fails, the error is shown and the events that could not be written
+    /// are dropped with `Ok(0)`
+    fn write(&self, events: &[input_event]) -> io::Result<usize> {
+        if self.0.recovering.load(Ordering::SeqCst) {
+            return Ok(0);
+        }
+        let output = self.current();
+        let mut delay = Duration::from_millis(1);
+        let mut result = output.write(events);
+        for _ in 0..RETRIES {
+            match &result {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(delay);
+                    delay *= 2;
+                    result = output.write(events);
+                }
+                _ => break,
+            }
+        }
+        match result {
+            Ok(written) => {
+                self.0.track(events);
+                Ok(written)
+            }
+            Err(err) => {
+                self.recover(&output, err);
+                Ok(0)
+            }
+        }
+    }
+
+    fn send_key(&self, key: Key, state: KeyState) {
+        _ = self.write(&key_events(key, state));
+    }
+
+    fn send_relative(&self, axes: &[(RelativeAxis, i32)]) {
+        _ = self.write(&relative_events(axes));
+    }
+
+    fn send_position(&self, x: i32, y: i32) {
+        _ = self.write(&position_events(x, y));
+    }
+
+    fn send_scroll(&self, notches: i32) {
+        self.send_relative(&scroll_axes(notches));
+    }
+
+    fn destroy(&self) {
+        self.current().destroy();
+    }
+
+    fn watch(&self, pause: PauseSource) {
+        *self.0.pause.lock().unwrap() = Some(pause);
+    }
+}
@@ -0,0 +1,264 @@
+/// A small regular expression for the `re:` device queries: literals, `.`, classes like `[a-z]`
+/// or `[^0-9]`, `\d`, `\w`, `\s`, the `*`, `+` and `?` repetitions, the `^` and `$` anchors and
+/// `|` between whole alternatives, without groups
+#[derive(Debug, Clone)]
+pub(crate) struct DeviceRegex {
+    source: String,
+    branches: Vec<Branch>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Branch {
+    start: bool,
+    end: bool,
+    pieces: Vec<Piece>,
+}
+
+#[derive(Debug, Clone)]
+struct Piece {
+    atom: Atom,
+    min: usize,
+    max: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Atom {
+    Char(char),
+    Any,
+    /// Inclusive ranges, single characters are ranges of one
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Char(expected) => c == *expected,
+            Atom::Any => true,
+            Atom::Class { negated, ranges } => {
+                ranges.iter().any(|(from, to)| (*from..=*to).contains(&c)) != *negated
+            }
+        }
+    }
+}
+
+/// The atom of `\d`, `\w` and `\s`, or the escaped character itself
+fn escaped(c: char) -> Atom {
+    let ranges = match c {
+        'd' => vec![('0', '9')],
+        'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+        's' => vec![(' ', ' '), ('\t', '\r')],
+        c => return Atom::Char(c),
+    };
+    Atom::Class {
+        negated: false,
+        ranges,
+    }
+}
+
+impl DeviceRegex {
+    pub fn new(source: &str) -> Result<Self, String> {
+        let branches = alternatives(source)
+            .into_iter()
+            .map(parse_branch)
+            .collect::<Result<_, _>>()
+            .map_err(|err| format!("{source:?} is not a supported pattern: {err}"))?;
+        Ok(Self {
+            source: source.to_owned(),
+            branches,
+        })
+    }
+
+    /// If the pattern is found anywhere in `text`, unless it is anchored
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        self.branches.iter().any(|branch| {
+            let starts = if branch.start { 0..=0 } else { 0..=text.len() };
+            starts
+                .into_iter()
+                .any(|start| match_here(&branch.pieces, &text[start..], branch.end))
+        })
+    }
+}
+
+impl std::fmt::Display for DeviceRegex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// The alternatives between the `|` that are not escaped or in a class
+fn alternatives(source: &str) -> Vec<&str> {
+    let mut alternatives = Vec::new();
+    let mut start = 0;
+    let mut in_class = false;
+    let mut chars = source.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => _ = chars.next(),
+            '[' if !in_class => {
+                in_class = true;
+                // A `]` right after the opening one is in the class
+                let rest = &source[index + 1..];
+                let skip = if rest.starts_with("^]") {
+                    2
+                } else {
+                    usize::from(rest.starts_with(']'))
+                };
+                for _ in 0..skip {
+                    chars.next();
+                }
+            }
+            ']' if in_class => in_class = false,
+            '|' if !in_class => {
+                alternatives.push(&source[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&source[start..]);
+    alternatives
+}
+
+fn parse_branch(source: &str) -> Result<Branch, String> {
+    let mut branch = Branch::default();
+    let mut chars = source.chars().peekable();
+    if chars.peek() == Some(&'^') {
+        chars.next();
+        branch.start = true;
+    }
+
+    while let Some(c) = chars.next() {
+        let atom = match c {
+            '$' if chars.peek().is_none() => {
+                branch.end = true;
+                break;
+            }
+            '.' => Atom::Any,
+            '\\' => escaped(chars.next().ok_or("nothing after `\\`")?),
+            '[' => {
+                let negated = chars.next_if_eq(&'^').is_some();
+                let mut ranges = Vec::new();
+                loop {
+                    let from = match chars.next().ok_or("unclosed `[`")? {
+                        ']' if !ranges.is_empty() => break,
+                        '\\' => chars.next().ok_or("nothing after `\\`")?,
+                        c => c,
+                    };
+                    let to = match chars.next_if_eq(&'-') {
+                        Some(_) if chars.peek().is_some_and(|c| *c != ']') => chars.next().unwrap(),
+                        Some(_) => {
+                            ranges.push(('-', '-'));
+                            from
+                        }
+                        None => from,
+                    };
+                    ranges.push((from, to));
+                }
+                Atom::Class { negated, ranges }
+            }
+            '(' | ')' => return Err("groups are not supported".into()),
+            '*' | '+' | '?' => return Err(format!("nothing to repeat before `{c}`")),
+            c => Atom::Char(c),
+        };
+        let (min, max) = match chars.next_if(|c| matches!(c, '*' | '+' | '?')) {
+            Some('*') => (0, usize::MAX),
+            Some('+') => (1, usize::MAX),
+            Some('?') => (0, 1),
+            _ => (1, 1),
+        };
+        branch.pieces.push(Piece { atom, min, max });
+    }
+    Ok(branch)
+}
+
+/// Backtracks over the greedy repetitions
+fn match_here(pieces: &[Piece], text: &[char], end: bool) -> bool {
+    let Some((piece, rest)) = pieces.split_first() else {
+        return !end || text.is_empty();
+    };
+    let count = text
+        .iter()
+        .take(piece.max)
+        .take_while(|c| piece.atom.matches(**c))
+        .count();
+    count >= piece.min
+        && (piece.min..=count)
+            .rev()
+            .any(|taken| match_here(rest, &text[taken..], end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        DeviceRegex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn literals_and_any() {
+        assert!(matches("Logitech", "Logitech G502 HERO"));
+        assert!(matches("G5.2", "Logitech G502 HERO"));
+        assert!(!matches("G5.2", "Logitech G5 HERO"));
+        assert!(matches("a\\.b", "a.b"));
+        assert!(!matches("a\\.b", "axb"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("G[0-9][0-9]2", "G502"));
+        assert!(!matches("G[0-9]2", "Gx2"));
+        assert!(matches("[^a-z ]", "mouse 1"));
+        assert!(!matches("^[^0-9]+$", "mouse 1"));
+        assert!(matches("^[a-]+$", "a-a"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("^\\d+$", "2048"));
+        assert!(!matches("^\\w+$", "two words"));
+        assert!(matches("two\\swords", "two words"));
+        assert!(matches("[\\]]", "]"));
+    }
+
+    #[test]
+    fn anchors() {
+        assert!(matches("^Razer", "Razer Viper"));
+        assert!(!matches("^Viper", "Razer Viper"));
+        assert!(matches("Viper$", "Razer Viper"));
+        assert!(!matches("Razer$", "Razer Viper"));
+        assert!(matches("^Razer Viper$", "Razer Viper"));
+        assert!(matches("a$b", "xa$b"));
+    }
+
+    #[test]
+    fn alternation() {
+        assert!(matches("Razer|Logitech", "Logitech G502"));
+        assert!(matches("^Razer|G502$", "Logitech G502"));
+        assert!(!matches("^Razer|^G502", "Logitech G502"));
+        assert!(matches("a\\|b", "a|b"));
+        assert!(!matches("a\\|b", "a"));
+        assert!(matches("^[|]$", "|"));
+    }
+
+    #[test]
+    fn repetition() {
+        assert!(matches("^ab*c$", "ac"));
+        assert!(matches("^ab*c$", "abbbc"));
+        assert!(!matches("^ab+c$", "ac"));
+        assert!(matches("^ab+c$", "abc"));
+        assert!(matches("^ab?c$", "ac"));
+        assert!(!matches("^ab?c$", "abbc"));
+        // The greedy repetitions give back what the rest needs
+        assert!(matches("^.*Mouse$", "USB Optical Mouse"));
+        assert!(matches("^a.*b.*c$", "aXbYbZc"));
+    }
+
+    #[test]
+    fn unsupported_patterns() {
+        for pattern in ["(a|b)", "*a", "a|+", "[a-z", "a\\"] {
+            assert!(DeviceRegex::new(pattern).is_err(), "{pattern}");
+        }
+    }
+}
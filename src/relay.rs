@@ -0,0 +1,73 @@
+use std::{collections::HashSet, io};
+
+use input_linux::{sys::input_event, Key, KeyState};
+
+use crate::{is_key_repeat, OutputBackend};
+
+/// Relays a grabbed device a whole frame at a time, the scan code of a key is held back until
+/// the key is relayed and the frames without relayed events are dropped
+#[derive(Default)]
+pub struct Relay {
+    frame: Vec<input_event>,
+    scan: Option<input_event>,
+    /// The relayed keys that are still down
+    down: HashSet<u16>,
+}
+
+impl Relay {
+    /// Follows an event of the device, the consumed ones are not relayed and the key repeats are
+    /// left to the virtual device
+    pub fn event(
+        &mut self,
+        output: &dyn OutputBackend,
+        event: &input_event,
+        consumed: bool,
+    ) -> io::Result<()> {
+        let kind = event.type_ as i32;
+        if kind == input_linux::sys::EV_MSC && event.code as i32 == input_linux::sys::MSC_SCAN {
+            self.scan = Some(*event);
+            return Ok(());
+        }
+        if kind == input_linux::sys::EV_SYN {
+            self.scan = None;
+            // The events since the last report are incomplete after a dropped one
+            if event.code as i32 != input_linux::sys::SYN_REPORT {
+                self.frame.clear();
+                return Ok(());
+            }
+            if self.frame.is_empty() {
+                return Ok(());
+            }
+            self.frame.push(*event);
+            let written = output.write(&self.frame);
+            self.frame.clear();
+            return written.map(|_| ());
+        }
+        if consumed || is_key_repeat(event) {
+            self.scan = None;
+            return Ok(());
+        }
+
+        self.frame.extend(self.scan.take());
+        self.frame.push(*event);
+        if kind == input_linux::sys::EV_KEY {
+            match event.value {
+                0 => _ = self.down.remove(&event.code),
+                1 => _ = self.down.insert(event.code),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Releases the relayed keys, when the device is gone before their release
+    pub fn release(&mut self, output: &dyn OutputBackend) {
+        for code in self.down.drain() {
+            if let Ok(key) = Key::from_code(code) {
+                output.send_key(key, KeyState::RELEASED);
+            }
+        }
+        self.frame.clear();
+        self.scan = None;
+    }
+}
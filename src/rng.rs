@@ -0,0 +1,109 @@
+use std::time::SystemTime;
+
+/// Small xorshift64* generator, good enough for timing noise
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        Self::new(nanos ^ ((std::process::id() as u64) << 32))
+    }
+
+    pub fn new(seed: u64) -> Self {
+        // xorshift cannot leave the zero state
+        Self(if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Normal distribution using the Box-Muller transform
+    pub fn normal(&mut self, mean: f64, stddev: f64) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+        mean + z * stddev
+    }
+
+    /// Uniform point inside a circle of `radius`
+    pub fn in_circle(&mut self, radius: f64) -> (f64, f64) {
+        let r = radius * self.next_f64().sqrt();
+        let angle = std::f64::consts::TAU * self.next_f64();
+        (r * angle.cos(), r * angle.sin())
+    }
+
+    /// Uniform in `[min, max]`
+    pub fn range_i64(&mut self, min: i64, max: i64) -> i64 {
+        if max <= min {
+            return min;
+        }
+        let span = (max - min) as u64 + 1;
+        min + (self.next_u64() % span) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_gives_the_same_numbers() {
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn a_zero_seed_still_gives_numbers() {
+        let mut rng = Rng::new(0);
+        assert!((0..10).any(|_| rng.next_u64() != 0));
+    }
+
+    #[test]
+    fn numbers_stay_in_their_ranges() {
+        let mut rng = Rng::new(7);
+        let mut seen = [false; 5];
+        for _ in 0..1000 {
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x), "{x}");
+
+            let n = rng.range_i64(-2, 2);
+            assert!((-2..=2).contains(&n), "{n}");
+            seen[(n + 2) as usize] = true;
+
+            let (x, y) = rng.in_circle(5.0);
+            assert!(x.hypot(y) <= 5.0, "({x}, {y})");
+        }
+        // Both ends are included
+        assert!(seen.iter().all(|seen| *seen));
+        assert_eq!(rng.range_i64(3, 3), 3);
+        assert_eq!(rng.range_i64(3, 1), 3);
+    }
+
+    #[test]
+    fn the_normal_distribution_is_around_the_mean() {
+        let mut rng = Rng::new(3);
+        let samples: Vec<f64> = (0..10_000).map(|_| rng.normal(50.0, 5.0)).collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!((mean - 50.0).abs() < 0.5, "{mean}");
+        assert!((variance.sqrt() - 5.0).abs() < 0.5, "{}", variance.sqrt());
+    }
+}
@@ -0,0 +1,651 @@
+use std::collections::HashMap;
+
+use clap::{CommandFactory, ValueEnum};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    args::{
+        Backend, BeepSound, BindMode, Bus, ClickMode, Led, LedShows, Modifier, MouseButton,
+        OverrideLost, StartActive,
+    },
+    config::{self, ConfigCommand},
+    Args,
+};
+
+/// The JSON Schema of the config format, for the completion and the checks of the editors,
+/// the descriptions are the help of the command line options
+pub fn schema() -> Value {
+    let args = Args::command();
+    let help = |command: &clap::Command| -> HashMap<String, String> {
+        command
+            .get_arguments()
+            .filter_map(|arg| {
+                let help = arg.get_long_help().or(arg.get_help())?;
+                Some((arg.get_id().to_string(), help.to_string()))
+            })
+            .collect()
+    };
+
+    let mut properties = Map::new();
+    properties.insert("$schema".to_string(), json!({ "type": "string" }));
+    properties.insert(
+        "version".to_string(),
+        json!({
+            "description": "Version of the config format, the older configs are migrated when loaded",
+            "type": "integer",
+            "minimum": 1,
+            "maximum": config::VERSION,
+        }),
+    );
+    let top = help(&args);
+    for (key, schema) in [
+        ("debug", json!({ "type": "boolean" })),
+        ("beep", json!({ "type": "boolean" })),
+        ("beep_sound", variants::<BeepSound>()),
+        ("backend", variants::<Backend>()),
+    ] {
+        properties.insert(key.to_string(), describe(schema, top.get(key)));
+    }
+    properties.insert("command".to_string(), json!({ "$ref": "#/$defs/command" }));
+    properties.insert(
+        "profiles".to_string(),
+        json!({
+            "description": "Commands chosen by their name with `run --profile NAME`",
+            "type": "object",
+            "additionalProperties": {
+                "anyOf": [{ "$ref": "#/$defs/command" }, { "$ref": "#/$defs/inheriting" }],
+            },
+        }),
+    );
+
+    let run = args.find_subcommand("run").map(help).unwrap_or_default();
+    let run_legacy = args
+        .find_subcommand("run-legacy")
+        .map(help)
+        .unwrap_or_default();
+    let base = |kind: &str, required: &[&str]| {
+        let mut base = Map::new();
+        base.insert("type".to_string(), Value::from(kind));
+        for key in required {
+            base.insert(key.to_string(), Value::from(false));
+        }
+        base
+    };
+    let run = command(base("Run", &["hold", "grab"]), &run);
+    let run_legacy = command(base("RunLegacy", &[]), &run_legacy);
+    let inheriting = inheriting(&run, &run_legacy);
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "TheClicker config",
+        "type": "object",
+        "properties": properties,
+        "required": ["debug", "beep", "command"],
+        "additionalProperties": false,
+        "$defs": {
+            "command": {
+                "oneOf": [
+                    { "$ref": "#/$defs/run" },
+                    { "$ref": "#/$defs/run_legacy" },
+                ],
+            },
+            "run": run,
+            "run_legacy": run_legacy,
+            "inheriting": inheriting,
+            "key": {
+                "description": "A keycode or a key name, like `275` or `\"BTN_SIDE\"`",
+                "type": ["integer", "string"],
+                "minimum": 0,
+            },
+            "bind": {
+                "description": "A keycode, a key name or keys pressed together, like `275`, `\"BTN_SIDE\"` or `\"KEY_LEFTCTRL+KEY_F6\"`",
+                "type": ["integer", "string"],
+                "minimum": 0,
+            },
+            "duration": {
+                "description": "A duration, like `150ms`, `5s` or `2m`",
+                "type": "string",
+            },
+            "point": {
+                "description": "A position on the screen in pixels, `[X, Y]`",
+                "type": "array",
+                "items": { "type": "integer" },
+                "minItems": 2,
+                "maxItems": 2,
+            },
+            "button": variants::<MouseButton>(),
+            "output": {
+                "description": "A mouse button by name, or a keycode or key name",
+                "anyOf": [{ "$ref": "#/$defs/button" }, { "$ref": "#/$defs/key" }],
+            },
+            "pattern_step": {
+                "description": "A step of a click pattern, like `{\"button\": \"left\", \"hold\": 50}`, `{\"key\": 18, \"hold\": 30}` or `{\"wait\": 200}`",
+                "type": "object",
+                "properties": {
+                    "button": { "$ref": "#/$defs/button" },
+                    "key": { "$ref": "#/$defs/key" },
+                    "hold": { "type": "integer", "minimum": 0 },
+                    "wait": { "type": "integer", "minimum": 0 },
+                },
+                "additionalProperties": false,
+            },
+            "window_rule": {
+                "type": "object",
+                "properties": {
+                    "action": { "enum": ["pause", "only"] },
+                    "class": { "type": "string" },
+                    "title": { "type": "string" },
+                },
+                "required": ["action"],
+                "additionalProperties": false,
+            },
+            "macro_bind": {
+                "type": "object",
+                "properties": {
+                    "key": { "$ref": "#/$defs/key" },
+                    "path": { "type": "string" },
+                    "times": { "type": "integer", "minimum": 0 },
+                    "while_held": { "type": "boolean" },
+                },
+                "required": ["key", "path"],
+                "additionalProperties": false,
+            },
+            "react_bind": {
+                "type": "object",
+                "properties": {
+                    "key": { "$ref": "#/$defs/key" },
+                    "delay": { "$ref": "#/$defs/duration" },
+                    "jitter": nullable(json!({ "$ref": "#/$defs/duration" })),
+                },
+                "required": ["key", "delay"],
+                "additionalProperties": false,
+            },
+            "binding": {
+                "type": "object",
+                "properties": {
+                    "key": { "$ref": "#/$defs/bind" },
+                    "action": {
+                        "enum": [
+                            "left", "right", "lock", "enable", "profile", "faster", "slower",
+                            "macro", "react", "click", "burst",
+                        ],
+                    },
+                    "on_release": { "type": "boolean" },
+                    "path": { "type": "string" },
+                    "times": { "type": "integer", "minimum": 0 },
+                    "while_held": { "type": "boolean" },
+                    "delay": { "$ref": "#/$defs/duration" },
+                    "jitter": nullable(json!({ "$ref": "#/$defs/duration" })),
+                    "button": { "$ref": "#/$defs/button" },
+                    "clicks": { "type": "integer", "minimum": 0 },
+                    "interval": { "$ref": "#/$defs/duration" },
+                },
+                "required": ["key", "action"],
+                "additionalProperties": false,
+            },
+        },
+    })
+}
+
+/// The schema of a command, its fields are the ones of the default command, `base` with the
+/// required fields
+fn command(base: Map<String, Value>, help: &HashMap<String, String>) -> Value {
+    let Some(Value::Object(fields)) =
+        serde_json::from_value::<ConfigCommand>(Value::Object(base.clone()))
+            .ok()
+            .and_then(|command| serde_json::to_value(command).ok())
+    else {
+        return json!({ "type": "object" });
+    };
+
+    let mut properties = Map::new();
+    for (key, default) in &fields {
+        let schema = if key == "type" {
+            json!({ "const": default })
+        } else {
+            field(key, default)
+        };
+        properties.insert(key.clone(), describe(schema, help.get(key)));
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": base.keys().collect::<Vec<_>>(),
+        "additionalProperties": false,
+    })
+}
+
+/// A profile with `inherits`, any field of the commands can be left to the inherited profile
+fn inheriting(run: &Value, run_legacy: &Value) -> Value {
+    let mut properties = Map::new();
+    for command in [run, run_legacy] {
+        if let Some(Value::Object(fields)) = command.get("properties") {
+            properties.extend(fields.clone());
+        }
+    }
+    properties.insert("type".to_string(), json!({ "enum": ["Run", "RunLegacy"] }));
+    properties.insert(
+        "inherits".to_string(),
+        json!({
+            "description": "The profile whose fields are taken when not given here, `default` is the `command`",
+            "type": "string",
+        }),
+    );
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": ["inherits"],
+        "additionalProperties": false,
+    })
+}
+
+/// The schema of a field of a command, told by its default value when it has no other type
+fn field(key: &str, default: &Value) -> Value {
+    let reference = |name: &str| json!({ "$ref": format!("#/$defs/{name}") });
+    let list = |items: Value| json!({ "type": "array", "items": items });
+    match key {
+        "left_bind" | "right_bind" | "lock_unlock_bind" | "faster_bind" | "slower_bind"
+        | "enable_bind" | "profile_bind" | "panic_bind" => nullable(reference("bind")),
+        "left_key" | "right_key" => nullable(reference("key")),
+        "anti_afk_key" => reference("key"),
+        "override_keys" => list(reference("bind")),
+        "macro_binds" => list(reference("macro_bind")),
+        "react_binds" => list(reference("react_bind")),
+        "binds" => list(reference("binding")),
+        "left_actions" | "right_actions" => list(reference("output")),
+        "left_drag" | "right_drag" | "positions" => list(reference("point")),
+        "left_pattern" | "right_pattern" => nullable(list(reference("pattern_step"))),
+        "window_rules" => list(reference("window_rule")),
+        "left_button" | "right_button" => reference("button"),
+        "left_mode" | "right_mode" => nullable(variants::<BindMode>()),
+        "start_active" => nullable(variants::<StartActive>()),
+        "click_mode" => variants::<ClickMode>(),
+        "modifiers" => list(variants::<Modifier>()),
+        "override_lost" => variants::<OverrideLost>(),
+        "device_bus" => variants::<Bus>(),
+        "led" => nullable(variants::<Led>()),
+        "led_shows" => variants::<LedShows>(),
+        "start_delay"
+        | "jiggle"
+        | "jiggle_idle"
+        | "duty_active"
+        | "duty_rest"
+        | "run_for"
+        | "override_resume_delay" => nullable(reference("duration")),
+        "override_quiet" | "anti_afk_interval" => reference("duration"),
+        "screen" => nullable(json!({ "type": "string", "pattern": "^[0-9]+x[0-9]+$" })),
+        "device_id" => json!({
+            "type": "string",
+            "pattern": "^[0-9a-fA-F]{1,4}:[0-9a-fA-F]{1,4}(:[0-9a-fA-F]{1,4})?$",
+        }),
+        "for_device" => json!({
+            "description": "USB ids of the device the command is for, like `046d:c08b`, `theclicker daemon` runs it while the device is plugged in",
+            "anyOf": [
+                { "type": "string", "pattern": "^[0-9a-fA-F]{4}:[0-9a-fA-F]{4}$" },
+                { "type": "null" },
+            ],
+        }),
+        "cps" => nullable(json!({ "type": "number", "exclusiveMinimum": 0 })),
+        "cooldown_left" | "cooldown_right" | "max_clicks" | "multiply" | "double_tap"
+        | "long_press" => nullable(json!({ "type": "integer", "minimum": 0 })),
+        "left_scroll" | "right_scroll" => nullable(json!({ "type": "integer" })),
+        "left_text" | "right_text" | "left_macro" | "right_macro" | "script" | "record_raw"
+        | "bind_device" | "control" => nullable(json!({ "type": "string" })),
+        "extra_devices" | "override_devices" | "plugins" | "profiles" => {
+            list(json!({ "type": "string" }))
+        }
+        _ => match default {
+            Value::Bool(_) => json!({ "type": "boolean" }),
+            Value::Number(number) if number.is_u64() => {
+                json!({ "type": "integer", "minimum": 0 })
+            }
+            Value::Number(number) if number.is_i64() => json!({ "type": "integer" }),
+            Value::Number(_) => json!({ "type": "number" }),
+            Value::String(_) => json!({ "type": "string" }),
+            Value::Array(_) => json!({ "type": "array" }),
+            _ => json!({}),
+        },
+    }
+}
+
+/// The names of the values of an enum, as they are written in the config
+fn variants<T: ValueEnum + Serialize>() -> Value {
+    let names: Vec<Value> = T::value_variants()
+        .iter()
+        .filter_map(|variant| serde_json::to_value(variant).ok())
+        .collect();
+    json!({ "enum": names })
+}
+
+fn nullable(schema: Value) -> Value {
+    json!({ "anyOf": [schema, { "type": "null" }] })
+}
+
+fn describe(mut schema: Value, help: Option<&String>) -> Value {
+    if let (Value::Object(schema), Some(help)) = (&mut schema, help) {
+        schema
+            .entry("description")
+            .or_insert_with(|| Value::from(help.as_str()));
+    }
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::{
+        args::{Binding, MacroBind, ReactBind, WindowRule},
+        Config,
+    };
+
+    /// The defaults of the `Run` and `RunLegacy` commands, every field of the commands
+    fn default_commands() -> Vec<Map<String, Value>> {
+        [
+            json!({ "type": "Run", "hold": false, "grab": false }),
+            json!({ "type": "RunLegacy" }),
+        ]
+        .into_iter()
+        .map(|base| {
+            let command: ConfigCommand = serde_json::from_value(base).unwrap();
+            match serde_json::to_value(command).unwrap() {
+                Value::Object(fields) => fields,
+                _ => unreachable!(),
+            }
+        })
+        .collect()
+    }
+
+    #[test]
+    fn every_field_has_a_type() {
+        for fields in default_commands() {
+            for (key, default) in fields.iter().filter(|(key, _)| *key != "type") {
+                let schema = field(key, default);
+                assert_ne!(
+                    schema,
+                    json!({}),
+                    "{key} accepts anything, add it to `field`"
+                );
+                if schema["type"] == "array" {
+                    assert!(
+                        schema.get("items").is_some(),
+                        "{key} has no items in `field`"
+                    );
+                }
+            }
+        }
+    }
+
+    /// The fields `T` serializes `value` with, after reading it like a config
+    fn serde_fields<T: serde::de::DeserializeOwned + Serialize>(value: Value) -> Vec<String> {
+        let value: T = serde_json::from_value(value).unwrap();
+        match serde_json::to_value(value).unwrap() {
+            Value::Object(fields) => fields.keys().cloned().collect(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn properties_are_the_serde_fields() {
+        let schema = schema();
+        let properties = |at: &Value| -> Vec<String> {
+            at["properties"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect()
+        };
+        let defs = &schema["$defs"];
+
+        // The commands are the same fields both ways, a field added to the options is in them
+        for (name, fields) in ["run", "run_legacy"].into_iter().zip(default_commands()) {
+            let mut fields: Vec<String> = fields.keys().cloned().collect();
+            let mut schema = properties(&defs[name]);
+            fields.sort();
+            schema.sort();
+            assert_eq!(schema, fields, "the fields of {name}");
+        }
+
+        let samples = [
+            (
+                &schema,
+                serde_fields::<Config>(json!({
+                    "debug": false,
+                    "beep": false,
+                    "beep_sound": "terminal",
+                    "backend": "uinput",
+                    "command": { "type": "RunLegacy" },
+                    "profiles": { "legacy": { "type": "RunLegacy" } },
+                })),
+            ),
+            (
+                &defs["macro_bind"],
+                serde_fields::<MacroBind>(json!({ "key": 275, "path": "a.json" })),
+            ),
+            (
+                &defs["react_bind"],
+                serde_fields::<ReactBind>(json!({ "key": 275, "delay": "90ms", "jitter": "9ms" })),
+            ),
+            (
+                &defs["window_rule"],
+                serde_fields::<WindowRule>(json!({ "action": "only", "class": "a", "title": "b" })),
+            ),
+        ];
+        for (at, fields) in samples {
+            let known = properties(at);
+            for field in fields {
+                assert!(known.contains(&field), "{field} is not in the schema");
+            }
+        }
+
+        let actions = defs["binding"]["properties"]["action"]["enum"]
+            .as_array()
+            .unwrap();
+        let known = properties(&defs["binding"]);
+        for action in [
+            json!({ "action": "left" }),
+            json!({ "action": "right" }),
+            json!({ "action": "lock" }),
+            json!({ "action": "enable" }),
+            json!({ "action": "profile" }),
+            json!({ "action": "faster" }),
+            json!({ "action": "slower" }),
+            json!({ "action": "macro", "path": "a.json" }),
+            json!({ "action": "react", "delay": "90ms", "jitter": "9ms" }),
+            json!({ "action": "click" }),
+            json!({ "action": "burst", "clicks": 3, "interval": "20ms" }),
+        ] {
+            assert!(
+                actions.contains(&action["action"]),
+                "{action} is not in the schema"
+            );
+            let mut binding = action;
+            binding["key"] = json!(275);
+            for field in serde_fields::<Binding>(binding) {
+                assert!(
+                    known.contains(&field),
+                    "{field} of a binding is not in the schema"
+                );
+            }
+        }
+    }
+
+    /// The errors of `value` against `schema`, for the keywords that `schema()` uses, the
+    /// patterns are left to the editors
+    fn check(root: &Value, schema: &Value, value: &Value, at: &str, errors: &mut Vec<String>) {
+        if let Some(Value::String(reference)) = schema.get("$ref") {
+            let name = reference.strip_prefix("#/$defs/").unwrap();
+            return check(root, &root["$defs"][name], value, at, errors);
+        }
+        let passes = |schema: &Value| {
+            let mut errors = Vec::new();
+            check(root, schema, value, at, &mut errors);
+            errors.is_empty()
+        };
+        if let Some(Value::Array(schemas)) = schema.get("anyOf") {
+            if !schemas.iter().any(passes) {
+                errors.push(format!("{at}: {value} matches none of anyOf"));
+            }
+        }
+        if let Some(Value::Array(schemas)) = schema.get("oneOf") {
+            let matching = schemas.iter().filter(|schema| passes(schema)).count();
+            if matching != 1 {
+                errors.push(format!("{at}: {value} matches {matching} of oneOf"));
+            }
+        }
+        if let Some(Value::Array(names)) = schema.get("enum") {
+            if !names.contains(value) {
+                errors.push(format!("{at}: {value} is not one of {names:?}"));
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            if constant != value {
+                errors.push(format!("{at}: {value} is not {constant}"));
+            }
+        }
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&Value> = match types {
+                Value::Array(types) => types.iter().collect(),
+                kind => vec![kind],
+            };
+            let has_type = |kind: &str| match kind {
+                "integer" => value.is_i64() || value.is_u64(),
+                "number" => value.is_number(),
+                "string" => value.is_string(),
+                "boolean" => value.is_boolean(),
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                "null" => value.is_null(),
+                kind => panic!("unknown type {kind}"),
+            };
+            if !types.iter().any(|kind| has_type(kind.as_str().unwrap())) {
+                errors.push(format!("{at}: {value} is not {types:?}"));
+            }
+        }
+        if let Some(number) = value.as_f64() {
+            let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+            if bound("minimum").is_some_and(|minimum| number < minimum)
+                || bound("maximum").is_some_and(|maximum| number > maximum)
+                || bound("exclusiveMinimum").is_some_and(|minimum| number <= minimum)
+            {
+                errors.push(format!("{at}: {value} is out of bounds"));
+            }
+        }
+        if let Value::Array(items) = value {
+            let count = |key: &str| schema.get(key).and_then(Value::as_u64);
+            if count("minItems").is_some_and(|min| (items.len() as u64) < min)
+                || count("maxItems").is_some_and(|max| items.len() as u64 > max)
+            {
+                errors.push(format!("{at}: {} items", items.len()));
+            }
+            if let Some(item) = schema.get("items") {
+                for (index, value) in items.iter().enumerate() {
+                    check(root, item, value, &format!("{at}[{index}]"), errors);
+                }
+            }
+        }
+        if let Value::Object(fields) = value {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for required in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                if !fields.contains_key(required.as_str().unwrap()) {
+                    errors.push(format!("{at}: {required} is missing"));
+                }
+            }
+            for (key, value) in fields {
+                let at = format!("{at}.{key}");
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(schema) => check(root, schema, value, &at, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => errors.push(format!("{at} is not allowed")),
+                        Some(additional @ Value::Object(_)) => {
+                            check(root, additional, value, &at, errors)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+    }
+
+    fn validate(config: &Value) -> Vec<String> {
+        let schema = schema();
+        let mut errors = Vec::new();
+        check(&schema, &schema, config, "config", &mut errors);
+        errors
+    }
+
+    fn saved(args: &[&str]) -> Value {
+        let args = Args::parse_from(std::iter::once("theclicker").chain(args.iter().copied()));
+        let command = ConfigCommand::try_from(args.command.unwrap()).unwrap();
+        let config: Config =
+            serde_json::from_value(json!({ "debug": false, "beep": true, "command": command }))
+                .unwrap();
+        serde_json::to_value(config).unwrap()
+    }
+
+    #[test]
+    fn saved_configs_match_the_schema() {
+        let configs = [
+            saved(&["run", "-d", "auto", "-l", "BTN_SIDE"]),
+            saved(&[
+                "run",
+                "-d",
+                "/dev/input/event3",
+                "-l",
+                "BTN_SIDE",
+                "-r",
+                "29+64",
+                "-T",
+                "274",
+                "--grab",
+                "-c",
+                "40",
+                "--left-key",
+                "KEY_F6",
+                "--bind",
+                "KEY_F7:macro:clicks.json",
+                "--position",
+                "960,540",
+                "--screen",
+                "1920x1080",
+                "--jiggle",
+                "30s",
+                "--multiply",
+                "2",
+                "--profile",
+                "fast",
+            ]),
+            saved(&["run-legacy", "-d", "/dev/input/mouse0", "-C", "10"]),
+        ];
+        for config in &configs {
+            assert_eq!(validate(config), Vec::<String>::new(), "{config}");
+        }
+
+        let mut with_profiles = configs[0].clone();
+        with_profiles["profiles"] = json!({
+            "fast": configs[1]["command"],
+            "legacy": configs[2]["command"],
+            "slow": { "inherits": "fast", "cooldown": 100 },
+        });
+        assert_eq!(validate(&with_profiles), Vec::<String>::new());
+    }
+
+    #[test]
+    fn wrong_configs_do_not_match_the_schema() {
+        let mut config = saved(&["run", "-d", "auto", "-l", "BTN_SIDE"]);
+        config["command"]["cooldown"] = json!("fast");
+        config["command"]["unknown"] = json!(1);
+        config["command"]["click_mode"] = json!("sideways");
+        let errors = validate(&config);
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(errors[0].contains("oneOf"), "{errors:?}");
+    }
+}
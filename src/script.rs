@@ -0,0 +1,166 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    process::{Command, Stdio},
+    sync::{
+        mpsc::{self, SyncSender, TrySendError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use input_linux::{Key, KeyState, RelativeAxis};
+use serde::Serialize;
+
+use crate::{args::ClickOptions, macros::MacroAction, AutoclickerState, OutputBackend};
+
+/// What the script is told, one JSON object per line on its stdin
+#[derive(Serialize)]
+#[serde(tag = "hook", rename_all = "snake_case")]
+pub enum Hook<'a> {
+    /// Sent every `--script-tick` milliseconds
+    Tick,
+    /// A key or button of the input device was pressed or released
+    BindPress { key: u16, pressed: bool },
+    /// The autoclicker state changed
+    State {
+        left: bool,
+        right: bool,
+        lock: bool,
+        paused: bool,
+        pauses: u32,
+    },
+    /// A pause source started or stopped pausing the clicking, `count` is how many times it
+    /// paused
+    Override {
+        source: &'a str,
+        paused: bool,
+        count: u32,
+    },
+}
+
+impl From<AutoclickerState> for Hook<'_> {
+    fn from(state: AutoclickerState) -> Self {
+        Hook::State {
+            left: state.left,
+            right: state.right,
+            lock: state.lock,
+            paused: state.paused,
+            pauses: state.pauses,
+        }
+    }
+}
+
+/// The hooks waiting for the script to read them, the next ones are dropped when it is full
+const QUEUED_HOOKS: usize = 64;
+
+/// An external program run as a hook that decides what to send, it reads the hooks on its stdin and
+/// writes the events to send on its stdout, in the macro format without `at`. It is not a sandbox,
+/// the program runs with the user of the clicker
+#[derive(Clone)]
+pub struct Script {
+    /// The lines for the thread that writes to the stdin of the script, the input thread never
+    /// waits for the script
+    hooks: SyncSender<String>,
+}
+
+impl Script {
+    /// Starts the script, or exits if it cannot be started
+    pub fn from_options(
+        options: &ClickOptions,
+        output: Arc<dyn OutputBackend>,
+        debug: bool,
+    ) -> Option<Self> {
+        let path = options.script.as_ref()?;
+        let tick = Duration::from_millis(options.script_tick.max(1));
+        Some(Self::spawn(path, tick, output, debug).unwrap_or_else(|err| {
+            eprintln!("Cannot start the script {}: {err}", path.display());
+            std::process::exit(1);
+        }))
+    }
+
+    fn spawn(
+        path: &Path,
+        tick: Duration,
+        output: Arc<dyn OutputBackend>,
+        debug: bool,
+    ) -> io::Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        let (hooks, lines) = mpsc::sync_channel::<String>(QUEUED_HOOKS);
+        let script = Self { hooks };
+
+        thread::spawn(move || {
+            for line in lines {
+                if stdin.write_all(line.as_bytes()).is_err() || stdin.flush().is_err() {
+                    break;
+                }
+            }
+        });
+
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if debug {
+                    println!("Script: {line}");
+                }
+                match serde_json::from_str(&line) {
+                    Ok(action) => send_action(&*output, action),
+                    Err(err) => eprintln!("The script sent an invalid event {line:?}: {err}"),
+                }
+            }
+            _ = child.wait();
+            eprintln!("\x1b[0KThe script exited");
+        });
+
+        let ticker = script.clone();
+        thread::spawn(move || loop {
+            thread::sleep(tick);
+            if ticker.send(Hook::Tick).is_err() {
+                break;
+            }
+        });
+
+        Ok(script)
+    }
+
+    /// Never waits, the hook is dropped when the script is behind, fails when the script does
+    /// not read anymore
+    pub fn send(&self, hook: Hook) -> io::Result<()> {
+        let mut line = serde_json::to_string(&hook).unwrap();
+        line.push('\n');
+        match self.hooks.try_send(line) {
+            Ok(()) | Err(TrySendError::Full(_)) => Ok(()),
+            Err(TrySendError::Disconnected(_)) => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+}
+
+/// Only keys, relative movements and the wheel can be sent, like in the macros
+fn send_action(output: &dyn OutputBackend, action: MacroAction) {
+    match action {
+        MacroAction::Key { key, pressed } => match Key::from_code(key) {
+            Ok(key) => {
+                let state = if pressed {
+                    KeyState::PRESSED
+                } else {
+                    KeyState::RELEASED
+                };
+                output.send_key(key, state);
+            }
+            Err(_) => eprintln!("The script sent an invalid keycode: {key}"),
+        },
+        MacroAction::Move { x, y } => {
+            output.send_relative(&[(RelativeAxis::X, x), (RelativeAxis::Y, y)])
+        }
+        MacroAction::Scroll { scroll } => output.send_scroll(scroll),
+    }
+}
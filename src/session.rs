@@ -0,0 +1,208 @@
+use std::{
+    env,
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+use crate::{args::ClickOptions, Message, PauseSource};
+
+/// Checked this often besides the signals of logind, in case one is missed
+const POLL: Duration = Duration::from_secs(1);
+/// Checked this often when the signals cannot be watched, `gdbus` is missing
+const FAST_POLL: Duration = Duration::from_millis(200);
+
+/// What the signals of the session tell
+enum Signal {
+    /// The screen locker is told to lock, before the session says it is locked
+    Lock,
+    Unlock,
+    /// The properties changed, the lock is told by the session once `LockedHint` is one of them
+    Changed {
+        locked_hint: bool,
+    },
+}
+
+/// Pauses the autoclicker while the logind session is locked or switched away
+pub struct SessionWatch {
+    /// Also deactivate the autoclicker, so it does not resume on unlock
+    deactivate: bool,
+    debug: bool,
+}
+
+impl SessionWatch {
+    pub fn from_options(options: &ClickOptions, debug: bool) -> Option<Self> {
+        options.session_pause.then_some(Self {
+            deactivate: options.session_deactivate,
+            debug,
+        })
+    }
+
+    pub fn spawn(self, mut pause: PauseSource, sender: Sender<Message>) {
+        let Some(session) = current_session() else {
+            eprintln!("Cannot find the logind session, set XDG_SESSION_ID");
+            std::process::exit(1);
+        };
+        if self.debug {
+            println!("Watching the logind session: {session}");
+        }
+        let mut signals = watch_signals(&session, self.debug);
+
+        thread::spawn(move || {
+            let mut away = false;
+            // Until the session says it is locked, or the unlock
+            let mut lock_signaled = false;
+            loop {
+                let now_away = session_away(&session).map(|away| away || lock_signaled);
+                match now_away {
+                    Some(now_away) if now_away != away => {
+                        away = now_away;
+                        if self.debug {
+                            println!("Session locked or inactive: {away}");
+                        }
+                        if away && self.deactivate {
+                            _ = sender.send(Message::Deactivate);
+                        }
+                        pause.set(away);
+                    }
+                    Some(_) => {}
+                    None => {
+                        eprintln!(
+                            "Cannot read the logind session {session}, not watching it anymore"
+                        );
+                        pause.set(false);
+                        return;
+                    }
+                }
+
+                let Some(receiver) = &signals else {
+                    thread::sleep(FAST_POLL);
+                    continue;
+                };
+                match receiver.recv_timeout(POLL) {
+                    Ok(Signal::Lock) => lock_signaled = true,
+                    Ok(Signal::Unlock | Signal::Changed { locked_hint: true }) => {
+                        lock_signaled = false
+                    }
+                    Ok(Signal::Changed { locked_hint: false }) => {}
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => {
+                        eprintln!("\x1b[0KThe logind signals stopped, checking the session often");
+                        signals = None;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Follows the signals of the session with `gdbus monitor`, so a lock pauses right away,
+/// `None` when it cannot be started
+fn watch_signals(session: &str, debug: bool) -> Option<Receiver<Signal>> {
+    let mut monitor = Command::new("gdbus")
+        .args(["monitor", "--system", "--dest", "org.freedesktop.login1"])
+        .args(["--object-path", &object_path(session)])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| {
+            if debug {
+                println!("Cannot watch the logind signals with gdbus: {err}");
+            }
+        })
+        .ok()?;
+    let stdout = monitor.stdout.take()?;
+    let (sender, signals) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let signal = if line.contains(".Session.Lock ") {
+                Signal::Lock
+            } else if line.contains(".Session.Unlock ") {
+                Signal::Unlock
+            } else if line.contains("PropertiesChanged") {
+                Signal::Changed {
+                    locked_hint: line.contains("'LockedHint'"),
+                }
+            } else {
+                continue;
+            };
+            if sender.send(signal).is_err() {
+                break;
+            }
+        }
+        _ = monitor.kill();
+        _ = monitor.wait();
+    });
+    Some(signals)
+}
+
+/// The D-Bus object of the session, its id escaped like logind does, `3` is `_33`
+fn object_path(session: &str) -> String {
+    let mut path = String::from("/org/freedesktop/login1/session/");
+    if session.is_empty() {
+        path.push('_');
+    }
+    for (index, byte) in session.bytes().enumerate() {
+        if byte.is_ascii_alphabetic() || (byte.is_ascii_digit() && index > 0) {
+            path.push(byte as char);
+        } else {
+            path.push_str(&format!("_{byte:02x}"));
+        }
+    }
+    path
+}
+
+/// From the environment, or the active session of seat0 when started with sudo
+fn current_session() -> Option<String> {
+    if let Ok(session) = env::var("XDG_SESSION_ID") {
+        return Some(session);
+    }
+    let output = Command::new("loginctl")
+        .args(["show-seat", "seat0", "--property=ActiveSession", "--value"])
+        .output()
+        .ok()?;
+    let session = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    (!session.is_empty()).then_some(session)
+}
+
+/// If the session is locked or not the active one on its seat
+fn session_away(session: &str) -> Option<bool> {
+    let output = Command::new("loginctl")
+        .args([
+            "show-session",
+            session,
+            "--property=LockedHint",
+            "--property=Active",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut away = false;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.split_once('=') {
+            Some(("LockedHint", "yes")) | Some(("Active", "no")) => away = true,
+            _ => {}
+        }
+    }
+    Some(away)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_object_paths() {
+        let path = |session| object_path(session).rsplit('/').next().unwrap().to_owned();
+        assert_eq!(path("3"), "_33");
+        assert_eq!(path("12"), "_312");
+        assert_eq!(path("c2"), "c2");
+        assert_eq!(path("a-b"), "a_2db");
+        assert_eq!(path(""), "_");
+    }
+}
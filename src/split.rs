@@ -0,0 +1,109 @@
+use std::{io, sync::Mutex};
+
+use input_linux::{sys::input_event, Key, KeyState, RelativeAxis};
+
+use crate::{is_keyboard_key, OutputBackend, OutputDevice};
+
+/// A virtual mouse and a virtual keyboard instead of one device with both, the keyboard keys go
+/// to the keyboard and everything else to the mouse
+pub struct SplitOutput {
+    mouse: OutputDevice,
+    keyboard: OutputDevice,
+    frame: Mutex<Frame>,
+}
+
+/// The frame being relayed, the devices that got events since the last report and the scan code
+/// held back until its key tells where it goes
+#[derive(Default)]
+struct Frame {
+    mouse: bool,
+    keyboard: bool,
+    scan: Option<input_event>,
+}
+
+impl SplitOutput {
+    pub fn new(mouse: OutputDevice, keyboard: OutputDevice) -> Self {
+        Self {
+            mouse,
+            keyboard,
+            frame: Mutex::new(Frame::default()),
+        }
+    }
+
+    fn is_keyboard_event(event: &input_event) -> bool {
+        match event.type_ as i32 {
+            input_linux::sys::EV_KEY => is_keyboard_key(event.code),
+            input_linux::sys::EV_REP | input_linux::sys::EV_LED => true,
+            _ => false,
+        }
+    }
+}
+
+impl OutputBackend for SplitOutput {
+    fn write(&self, events: &[input_event]) -> io::Result<usize> {
+        let mut frame = self.frame.lock().unwrap();
+        let mut mouse = Vec::new();
+        let mut keyboard = Vec::new();
+        for event in events {
+            let kind = event.type_ as i32;
+            if kind == input_linux::sys::EV_MSC && event.code as i32 == input_linux::sys::MSC_SCAN {
+                frame.scan = Some(*event);
+                continue;
+            }
+            if kind == input_linux::sys::EV_SYN {
+                if frame.mouse {
+                    mouse.push(*event);
+                }
+                if frame.keyboard {
+                    keyboard.push(*event);
+                }
+                if event.code as i32 == input_linux::sys::SYN_REPORT {
+                    *frame = Frame::default();
+                }
+                continue;
+            }
+
+            let (to, sent) = if Self::is_keyboard_event(event) {
+                (&mut keyboard, &mut frame.keyboard)
+            } else {
+                (&mut mouse, &mut frame.mouse)
+            };
+            *sent = true;
+            to.extend(frame.scan.take());
+            to.push(*event);
+        }
+
+        if !keyboard.is_empty() {
+            self.keyboard.write(&keyboard)?;
+        }
+        if !mouse.is_empty() {
+            self.mouse.write(&mouse)?;
+        }
+        Ok(events.len())
+    }
+
+    fn send_key(&self, key: Key, state: KeyState) {
+        if is_keyboard_key(key as u16) {
+            self.keyboard.send_key(key, state);
+        } else {
+            self.mouse.send_key(key, state);
+        }
+    }
+
+    fn send_relative(&self, axes: &[(RelativeAxis, i32)]) {
+        self.mouse.send_relative(axes);
+    }
+
+    fn send_position(&self, x: i32, y: i32) {
+        self.mouse.send_position(x, y);
+    }
+
+    fn send_scroll(&self, notches: i32) {
+        self.mouse.send_scroll(notches);
+    }
+
+    fn destroy(&self) {
+        self.keyboard.destroy();
+        self.mouse.destroy();
+    }
+}
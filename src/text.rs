@@ -0,0 +1,102 @@
+use input_linux::Key;
+
+/// Maps a character to the key that types it on a US layout, and if shift has to be held
+pub fn char_to_key(c: char) -> Option<(Key, bool)> {
+    let lower = c.to_ascii_lowercase();
+    let shift = c.is_ascii_uppercase();
+
+    let key = match lower {
+        'a' => Key::A,
+        'b' => Key::B,
+        'c' => Key::C,
+        'd' => Key::D,
+        'e' => Key::E,
+        'f' => Key::F,
+        'g' => Key::G,
+        'h' => Key::H,
+        'i' => Key::I,
+        'j' => Key::J,
+        'k' => Key::K,
+        'l' => Key::L,
+        'm' => Key::M,
+        'n' => Key::N,
+        'o' => Key::O,
+        'p' => Key::P,
+        'q' => Key::Q,
+        'r' => Key::R,
+        's' => Key::S,
+        't' => Key::T,
+        'u' => Key::U,
+        'v' => Key::V,
+        'w' => Key::W,
+        'x' => Key::X,
+        'y' => Key::Y,
+        'z' => Key::Z,
+        _ => return symbol_to_key(c),
+    };
+
+    Some((key, shift))
+}
+
+fn symbol_to_key(c: char) -> Option<(Key, bool)> {
+    Some(match c {
+        '1' => (Key::Num1, false),
+        '2' => (Key::Num2, false),
+        '3' => (Key::Num3, false),
+        '4' => (Key::Num4, false),
+        '5' => (Key::Num5, false),
+        '6' => (Key::Num6, false),
+        '7' => (Key::Num7, false),
+        '8' => (Key::Num8, false),
+        '9' => (Key::Num9, false),
+        '0' => (Key::Num0, false),
+        '!' => (Key::Num1, true),
+        '@' => (Key::Num2, true),
+        '#' => (Key::Num3, true),
+        '$' => (Key::Num4, true),
+        '%' => (Key::Num5, true),
+        '^' => (Key::Num6, true),
+        '&' => (Key::Num7, true),
+        '*' => (Key::Num8, true),
+        '(' => (Key::Num9, true),
+        ')' => (Key::Num0, true),
+        '-' => (Key::Minus, false),
+        '_' => (Key::Minus, true),
+        '=' => (Key::Equal, false),
+        '+' => (Key::Equal, true),
+        '[' => (Key::LeftBrace, false),
+        '{' => (Key::LeftBrace, true),
+        ']' => (Key::RightBrace, false),
+        '}' => (Key::RightBrace, true),
+        '\\' => (Key::Backslash, false),
+        '|' => (Key::Backslash, true),
+        ';' => (Key::Semicolon, false),
+        ':' => (Key::Semicolon, true),
+        '\'' => (Key::Apostrophe, false),
+        '"' => (Key::Apostrophe, true),
+        '`' => (Key::Grave, false),
+        '~' => (Key::Grave, true),
+        ',' => (Key::Comma, false),
+        '<' => (Key::Comma, true),
+        '.' => (Key::Dot, false),
+        '>' => (Key::Dot, true),
+        '/' => (Key::Slash, false),
+        '?' => (Key::Slash, true),
+        ' ' => (Key::Space, false),
+        '\t' => (Key::Tab, false),
+        '\n' => (Key::Enter, false),
+        _ => return None,
+    })
+}
+
+/// Converts the text to keys, exits on characters that cannot be typed
+pub fn text_to_keys(text: &str) -> Vec<(Key, bool)> {
+    text.chars()
+        .map(|c| {
+            char_to_key(c).unwrap_or_else(|| {
+                eprintln!("Cannot type {c:?}, only the US layout ASCII characters are supported");
+                std::process::exit(1);
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,302 @@
+use std::time::Duration;
+
+use crate::{args::ClickOptions, rng::Rng};
+
+/// Decides how long the autoclicker waits between clicks, or how long a click is held
+pub trait Timing: Send {
+    fn next_cooldown(&mut self, rng: &mut Rng) -> Duration;
+}
+
+/// Always the same cooldown
+pub struct Fixed(pub Duration);
+
+impl Timing for Fixed {
+    fn next_cooldown(&mut self, _: &mut Rng) -> Duration {
+        self.0
+    }
+}
+
+/// Cooldown picked uniformly from `base ± jitter`
+pub struct Uniform {
+    pub base: Duration,
+    pub jitter: Duration,
+}
+
+impl Timing for Uniform {
+    fn next_cooldown(&mut self, rng: &mut Rng) -> Duration {
+        let base = self.base.as_micros() as i64;
+        let jitter = self.jitter.as_micros() as i64;
+        let micros = rng.range_i64(base - jitter, base + jitter).max(0);
+        Duration::from_micros(micros as u64)
+    }
+}
+
+/// Normal distribution around `mean`, with the chance of a longer pause,
+/// close to how a human clicks
+pub struct Humanize {
+    pub mean: Duration,
+    pub stddev: Duration,
+    pub pause_chance: f64,
+    pub pause: Duration,
+}
+
+impl Timing for Humanize {
+    fn next_cooldown(&mut self, rng: &mut Rng) -> Duration {
+        let mean = self.mean.as_secs_f64();
+        let stddev = self.stddev.as_secs_f64();
+        // Keep away from the extremes of the bell curve
+        let mut secs = rng
+            .normal(mean, stddev)
+            .clamp(mean - 3.0 * stddev, mean + 3.0 * stddev)
+            .max(0.0);
+
+        if rng.next_f64() < self.pause_chance {
+            secs += self.pause.as_secs_f64() * (0.5 + rng.next_f64());
+        }
+
+        Duration::from_secs_f64(secs)
+    }
+}
+
+/// Bursts of 20 to 30 clicks per second with short breaks, close to drag clicking
+pub struct DragClick {
+    /// Time between press and release, the rate is for the whole click
+    pub press: Duration,
+    /// Clicks left in the current burst
+    pub left: u32,
+}
+
+impl Timing for DragClick {
+    fn next_cooldown(&mut self, rng: &mut Rng) -> Duration {
+        if self.left == 0 {
+            self.left = rng.range_i64(8, 25) as u32;
+        }
+        self.left -= 1;
+        if self.left == 0 {
+            // Break at the end of the burst
+            return Duration::from_millis(rng.range_i64(150, 400) as u64);
+        }
+
+        let cps = 20.0 + 10.0 * rng.next_f64();
+        Duration::from_secs_f64(1.0 / cps).saturating_sub(self.press)
+    }
+}
+
+/// Exits when `--cps` is invalid, and warns when it cannot be reached
+pub fn check_cps(options: &ClickOptions) {
+    if let Some(cps) = options.cps {
+        if !cps.is_finite() || cps <= 0.0 {
+            eprintln!("--cps must be a positive number, got {cps}");
+            std::process::exit(1);
+        }
+        let period = Duration::from_secs_f64(1.0 / cps);
+        if period < Duration::from_millis(options.cooldown_press_release) {
+            eprintln!(
+                "\x1B[1;33mWarning: --cps {cps} is not reachable with -C{}, clicking as fast as possible\x1B[0;39m",
+                options.cooldown_press_release
+            );
+        }
+    }
+}
+
+/// Longest `--humanize-stddev` in milliseconds, a minute is already far from a human click
+const MAX_STDDEV: f64 = 60_000.0;
+
+/// Exits when `--humanize-stddev` is not a number of milliseconds a cooldown can be
+pub fn check_humanize(options: &ClickOptions) {
+    let stddev = options.humanize_stddev;
+    if options.humanize && !(stddev.is_finite() && stddev <= MAX_STDDEV) {
+        eprintln!("--humanize-stddev must be at most {MAX_STDDEV}, got {stddev}");
+        std::process::exit(1);
+    }
+}
+
+/// Warns that the drag clicking goes over the 40 events per second of some kernels
+pub fn check_drag_click(options: &ClickOptions) {
+    if options.drag_click {
+        eprintln!("\x1B[1;33mWarning: --drag-click sends up to 30 clicks per second, over the 25ms cooldown the kernel may permit, some clicks can be dropped\x1B[0;39m");
+    }
+}
+
+/// Builds the strategy around the `cooldown` of a button
+pub fn from_options(options: &ClickOptions, cooldown: Duration) -> Box<dyn Timing> {
+    if options.drag_click {
+        Box::new(DragClick {
+            press: Duration::from_millis(options.cooldown_press_release),
+            left: 0,
+        })
+    } else if options.humanize {
+        Box::new(Humanize {
+            mean: cooldown,
+            stddev: Duration::from_secs_f64(options.humanize_stddev.max(0.0) / 1000.0),
+            pause_chance: options.humanize_pause_chance.clamp(0.0, 1.0),
+            pause: Duration::from_millis(options.humanize_pause),
+        })
+    } else if options.cooldown_jitter != 0 {
+        Box::new(Uniform {
+            base: cooldown,
+            jitter: Duration::from_millis(options.cooldown_jitter),
+        })
+    } else {
+        Box::new(Fixed(cooldown))
+    }
+}
+
+/// Builds the strategy for the time between press and release
+pub fn press_from_options(options: &ClickOptions) -> Box<dyn Timing> {
+    let press = Duration::from_millis(options.cooldown_press_release);
+
+    if options.press_jitter != 0 {
+        Box::new(Uniform {
+            base: press,
+            jitter: Duration::from_millis(options.press_jitter),
+        })
+    } else {
+        Box::new(Fixed(press))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cooldowns(timing: &mut dyn Timing, count: usize) -> Vec<Duration> {
+        let mut rng = Rng::new(11);
+        (0..count).map(|_| timing.next_cooldown(&mut rng)).collect()
+    }
+
+    #[test]
+    fn fixed_is_always_the_same() {
+        let cooldown = Duration::from_millis(40);
+        assert!(cooldowns(&mut Fixed(cooldown), 100)
+            .iter()
+            .all(|next| *next == cooldown));
+    }
+
+    #[test]
+    fn uniform_stays_within_the_jitter() {
+        let mut uniform = Uniform {
+            base: Duration::from_millis(50),
+            jitter: Duration::from_millis(10),
+        };
+        let next = cooldowns(&mut uniform, 1000);
+        let range = Duration::from_millis(40)..=Duration::from_millis(60);
+        assert!(next.iter().all(|next| range.contains(next)));
+        assert!(next.iter().any(|next| *next < Duration::from_millis(45)));
+        assert!(next.iter().any(|next| *next > Duration::from_millis(55)));
+    }
+
+    #[test]
+    fn uniform_does_not_go_below_zero() {
+        let mut uniform = Uniform {
+            base: Duration::from_millis(5),
+            jitter: Duration::from_millis(20),
+        };
+        let next = cooldowns(&mut uniform, 1000);
+        assert!(next.iter().all(|next| *next <= Duration::from_millis(25)));
+        assert!(next.contains(&Duration::ZERO));
+    }
+
+    #[test]
+    fn humanize_is_clamped_to_three_deviations() {
+        let mut humanize = Humanize {
+            mean: Duration::from_millis(100),
+            stddev: Duration::from_millis(10),
+            pause_chance: 0.0,
+            pause: Duration::from_millis(350),
+        };
+        let range = Duration::from_millis(70)..=Duration::from_millis(130);
+        assert!(cooldowns(&mut humanize, 10_000)
+            .iter()
+            .all(|next| range.contains(next)));
+
+        // And never below zero
+        humanize.mean = Duration::from_millis(10);
+        humanize.stddev = Duration::from_millis(20);
+        let next = cooldowns(&mut humanize, 1000);
+        assert!(next.iter().all(|next| *next <= Duration::from_millis(70)));
+        assert!(next.contains(&Duration::ZERO));
+    }
+
+    #[test]
+    fn humanize_pauses_are_half_to_one_and_a_half_the_pause() {
+        let mut humanize = Humanize {
+            mean: Duration::from_millis(100),
+            stddev: Duration::ZERO,
+            pause_chance: 1.0,
+            pause: Duration::from_millis(300),
+        };
+        let range = Duration::from_millis(250)..Duration::from_millis(550);
+        assert!(cooldowns(&mut humanize, 1000)
+            .iter()
+            .all(|next| range.contains(next)));
+
+        humanize.pause_chance = 0.0;
+        assert!(cooldowns(&mut humanize, 1000)
+            .iter()
+            .all(|next| next.as_secs_f64() == 0.1));
+    }
+
+    #[test]
+    fn the_longest_stddev_is_a_duration() {
+        let options = ClickOptions {
+            humanize: true,
+            humanize_stddev: MAX_STDDEV,
+            ..ClickOptions::default()
+        };
+        let mut humanize = from_options(&options, Duration::from_millis(25));
+        let longest = Duration::from_secs_f64(0.025 + 3.0 * MAX_STDDEV / 1000.0)
+            + Duration::from_millis(options.humanize_pause * 3 / 2);
+        assert!(cooldowns(&mut *humanize, 100)
+            .iter()
+            .all(|next| *next <= longest));
+    }
+
+    #[test]
+    fn drag_clicks_come_in_bursts() {
+        let press = Duration::from_millis(5);
+        let mut drag = DragClick { press, left: 0 };
+        let fast = Duration::from_secs_f64(1.0 / 30.0) - press
+            ..=Duration::from_secs_f64(1.0 / 20.0) - press;
+        let pause = Duration::from_millis(150)..=Duration::from_millis(400);
+
+        let mut burst = 0;
+        let mut bursts = 0;
+        for next in cooldowns(&mut drag, 5000) {
+            burst += 1;
+            if pause.contains(&next) {
+                // The break is the last cooldown of the burst
+                assert!((8..=25).contains(&burst), "a burst of {burst} clicks");
+                burst = 0;
+                bursts += 1;
+            } else {
+                assert!(fast.contains(&next), "{next:?}");
+            }
+        }
+        assert!(bursts > 100, "{bursts} bursts");
+    }
+
+    #[test]
+    fn cps_is_the_whole_click() {
+        let options = ClickOptions {
+            cps: Some(20.0),
+            cooldown_press_release: 10,
+            ..ClickOptions::default()
+        };
+        assert_eq!(options.effective_cooldown(), Duration::from_millis(40));
+
+        // The press alone takes longer than the rate
+        let options = ClickOptions {
+            cps: Some(200.0),
+            ..options
+        };
+        assert_eq!(options.effective_cooldown(), Duration::ZERO);
+
+        let options = ClickOptions {
+            cps: None,
+            cooldown: 33,
+            ..options
+        };
+        assert_eq!(options.effective_cooldown(), Duration::from_millis(33));
+    }
+}
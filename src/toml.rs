@@ -0,0 +1,731 @@
+//! TOML config files, read into and written from a `serde_json::Value` so the config types only
+//! need their JSON derives, without dates and with the nulls left out
+
+use std::collections::HashSet;
+
+use serde_json::{Map, Number, Value};
+
+/// Parses a TOML document into a JSON object
+pub fn from_str(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    parser
+        .document()
+        .map_err(|err| format!("line {}: {err}", parser.line()))
+}
+
+/// Writes a JSON object as a TOML document, the null values are left out
+pub fn to_string(value: &Value) -> Result<String, String> {
+    let Value::Object(map) = value else {
+        return Err("Only a table can be written as TOML".to_string());
+    };
+    let mut out = String::new();
+    write_table(&mut out, &mut Vec::new(), map)?;
+    Ok(out)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+/// How the tables were defined, by their path from the root, TOML lets a table be defined only
+/// once and an inline table never be extended
+#[derive(Default)]
+struct Defined {
+    /// With a `[table]` header
+    headers: HashSet<Vec<String>>,
+    /// By the dotted keys of `table.key = value`
+    dotted: HashSet<Vec<String>>,
+    /// `key = { ... }`, complete once written
+    inline: HashSet<Vec<String>>,
+    /// With `[[table]]` headers, the other arrays are values
+    arrays: HashSet<Vec<String>>,
+}
+
+impl Defined {
+    /// A new table of the array at `path`, its sub-tables are defined again for it
+    fn new_element(&mut self, path: &[String]) {
+        let below = |defined: &Vec<String>| defined.len() > path.len() && defined.starts_with(path);
+        for set in [
+            &mut self.headers,
+            &mut self.dotted,
+            &mut self.inline,
+            &mut self.arrays,
+        ] {
+            set.retain(|defined| !below(defined));
+        }
+    }
+
+    /// Fails when the table at `path` is in an inline table, or is one
+    fn check_inline(&self, path: &[String]) -> Result<(), String> {
+        match (1..=path.len()).find(|len| self.inline.contains(&path[..*len])) {
+            Some(len) => Err(format!(
+                "{:?} is an inline table, it cannot be extended",
+                path[..len].join(".")
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        1 + self.chars[..self.pos.min(self.chars.len())]
+            .iter()
+            .filter(|char| **char == '\n')
+            .count()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(index, char)| self.chars.get(self.pos + index) == Some(&char))
+    }
+
+    fn expect(&mut self, char: char) -> Result<(), String> {
+        if self.peek() != Some(char) {
+            return Err(match self.peek() {
+                Some(found) => format!("expected {char:?}, found {found:?}"),
+                None => format!("expected {char:?} before the end"),
+            });
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Skips the spaces, the comments and the new lines, between the array values
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n' | '\r') => self.pos += 1,
+                _ => return,
+            }
+        }
+    }
+
+    /// Only a comment can follow on the line
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.peek() == Some('\r') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(found) => Err(format!("unexpected {found:?} after the value")),
+        }
+    }
+
+    fn document(&mut self) -> Result<Value, String> {
+        let mut root = Map::new();
+        let mut current: Vec<String> = Vec::new();
+        let mut defined = Defined::default();
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                None => return Ok(Value::Object(root)),
+                Some('[') if self.starts_with("[[") => {
+                    self.pos += 2;
+                    let path = self.dotted_key()?;
+                    self.expect(']')?;
+                    self.expect(']')?;
+                    self.end_of_line()?;
+                    defined.check_inline(&path)?;
+                    let (last, parent) = path.split_last().unwrap();
+                    let parent = table_mut(&mut root, parent, &defined.arrays)?;
+                    let array = parent
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    let Value::Array(array) = array else {
+                        return Err(format!("{last:?} is already a value"));
+                    };
+                    if !array.is_empty() && !defined.arrays.contains(&path) {
+                        return Err(format!(
+                            "{last:?} is an array value, not an array of tables"
+                        ));
+                    }
+                    array.push(Value::Object(Map::new()));
+                    defined.arrays.insert(path.clone());
+                    defined.new_element(&path);
+                    current = path;
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    let path = self.dotted_key()?;
+                    self.expect(']')?;
+                    self.end_of_line()?;
+                    defined.check_inline(&path)?;
+                    let name = path.join(".");
+                    if defined.arrays.contains(&path) {
+                        return Err(format!("[{name}] is an array of tables"));
+                    }
+                    if defined.dotted.contains(&path) {
+                        return Err(format!("[{name}] is already defined by dotted keys"));
+                    }
+                    if !defined.headers.insert(path.clone()) {
+                        return Err(format!("[{name}] is defined twice"));
+                    }
+                    table_mut(&mut root, &path, &defined.arrays)?;
+                    current = path;
+                }
+                Some(_) => {
+                    let key = self.dotted_key()?;
+                    self.expect('=')?;
+                    self.skip_spaces();
+                    let value = self.value()?;
+                    self.end_of_line()?;
+                    let full: Vec<String> = current.iter().chain(&key).cloned().collect();
+                    defined.check_inline(&full[..full.len() - 1])?;
+                    for len in current.len() + 1..full.len() {
+                        let table = &full[..len];
+                        if defined.headers.contains(table) || defined.arrays.contains(table) {
+                            return Err(format!(
+                                "[{}] cannot be extended with dotted keys",
+                                table.join(".")
+                            ));
+                        }
+                        defined.dotted.insert(table.to_vec());
+                    }
+                    if value.is_object() {
+                        defined.inline.insert(full);
+                    }
+                    let table = table_mut(&mut root, &current, &defined.arrays)?;
+                    insert(table, &key, value)?;
+                }
+            }
+        }
+    }
+
+    fn dotted_key(&mut self) -> Result<Vec<String>, String> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_spaces();
+            keys.push(self.key()?);
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(keys);
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|char| char.is_ascii_alphanumeric() || char == '_' || char == '-')
+                {
+                    self.pos += 1;
+                }
+                if start == self.pos {
+                    return Err("expected a key".to_string());
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') if self.starts_with("\"\"\"") => self.multiline_string().map(Value::String),
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') if self.starts_with("'''") => {
+                self.multiline_literal_string().map(Value::String)
+            }
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some(_) if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(Value::Bool(true))
+            }
+            Some(_) if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(_) => self.number(),
+            None => Err("expected a value".to_string()),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_blank();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {}
+                _ => return Err("expected ',' or ']' in the array".to_string()),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut table = Map::new();
+        self.skip_spaces();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Object(table));
+        }
+        loop {
+            let key = self.dotted_key()?;
+            self.expect('=')?;
+            self.skip_spaces();
+            let value = self.value()?;
+            insert(&mut table, &key, value)?;
+            self.skip_spaces();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(table));
+                }
+                _ => return Err("expected ',' or '}' in the inline table".to_string()),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|char| {
+            char.is_ascii_alphanumeric() || matches!(char, '_' | '+' | '-' | '.')
+        }) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|char| **char != '_')
+            .collect();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(&text)),
+        };
+        let radix = match digits.get(..2) {
+            Some("0x") => Some(16),
+            Some("0o") => Some(8),
+            Some("0b") => Some(2),
+            _ => None,
+        };
+        let invalid = || format!("invalid value {text:?}");
+        if let Some(radix) = radix {
+            let value = i64::from_str_radix(&digits[2..], radix).map_err(|_| invalid())?;
+            return Ok(Value::from(if negative { -value } else { value }));
+        }
+        if digits.contains(['.', 'e', 'E']) {
+            let value: f64 = text.parse().map_err(|_| invalid())?;
+            return Number::from_f64(value)
+                .map(Value::Number)
+                .ok_or_else(invalid);
+        }
+        if !digits.starts_with(|char: char| char.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        if let Ok(value) = text.parse::<i64>() {
+            return Ok(Value::from(value));
+        }
+        text.parse::<u64>().map(Value::from).map_err(|_| invalid())
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err("unterminated string".to_string()),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(string);
+                }
+                Some('\\') => string.push(self.escape()?),
+                Some(char) => {
+                    string.push(char);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn multiline_string(&mut self) -> Result<String, String> {
+        self.pos += 3;
+        self.skip_newline();
+        let mut string = String::new();
+        loop {
+            if self.starts_with("\"\"\"") && !self.starts_with("\"\"\"\"") {
+                self.pos += 3;
+                return Ok(string);
+            }
+            match self.peek() {
+                None => return Err("unterminated string".to_string()),
+                Some('\\') if self.line_ending_backslash() => {
+                    self.pos += 1;
+                    while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                        self.pos += 1;
+                    }
+                }
+                Some('\\') => string.push(self.escape()?),
+                Some(char) => {
+                    string.push(char);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err("unterminated string".to_string()),
+                Some('\'') => {
+                    let string = self.chars[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    return Ok(string);
+                }
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    fn multiline_literal_string(&mut self) -> Result<String, String> {
+        self.pos += 3;
+        self.skip_newline();
+        let start = self.pos;
+        loop {
+            if self.starts_with("'''") && !self.starts_with("''''") {
+                let string = self.chars[start..self.pos].iter().collect();
+                self.pos += 3;
+                return Ok(string);
+            }
+            if self.peek().is_none() {
+                return Err("unterminated string".to_string());
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// The new line right after the opening quotes is not part of the string
+    fn skip_newline(&mut self) {
+        if self.starts_with("\r\n") {
+            self.pos += 2;
+        } else if self.peek() == Some('\n') {
+            self.pos += 1;
+        }
+    }
+
+    /// A backslash followed by only spaces until the end of the line
+    fn line_ending_backslash(&self) -> bool {
+        self.chars[self.pos + 1..]
+            .iter()
+            .find(|char| !matches!(char, ' ' | '\t' | '\r'))
+            .is_some_and(|char| *char == '\n')
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        self.expect('\\')?;
+        let char = self.peek().ok_or("unterminated string")?;
+        self.pos += 1;
+        let unicode = |parser: &mut Self, len: usize| -> Result<char, String> {
+            let hex: String = parser.chars[parser.pos..].iter().take(len).collect();
+            parser.pos += len;
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .filter(|_| hex.len() == len)
+                .and_then(char::from_u32)
+                .ok_or_else(|| format!("invalid unicode escape {hex:?}"))
+        };
+        Ok(match char {
+            'b' => '\u{8}',
+            't' => '\t',
+            'n' => '\n',
+            'f' => '\u{c}',
+            'r' => '\r',
+            'e' => '\u{1b}',
+            '"' => '"',
+            '\\' => '\\',
+            'u' => unicode(self, 4)?,
+            'U' => unicode(self, 8)?,
+            char => return Err(format!("invalid escape \\{char}")),
+        })
+    }
+}
+
+/// The table at the path, created when missing, the last one of an array of tables. `arrays`
+/// are the paths of the arrays of tables, the other arrays are values
+fn table_mut<'a>(
+    root: &'a mut Map<String, Value>,
+    path: &[String],
+    arrays: &HashSet<Vec<String>>,
+) -> Result<&'a mut Map<String, Value>, String> {
+    let mut table = root;
+    for (index, key) in path.iter().enumerate() {
+        let value = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        let value = match value {
+            Value::Array(_) if !arrays.contains(&path[..=index]) => {
+                return Err(format!("{key:?} is already a value"))
+            }
+            Value::Array(array) => array
+                .last_mut()
+                .ok_or_else(|| format!("{key:?} is an empty array"))?,
+            value => value,
+        };
+        let Value::Object(next) = value else {
+            return Err(format!("{key:?} is already a value"));
+        };
+        table = next;
+    }
+    Ok(table)
+}
+
+fn insert(table: &mut Map<String, Value>, key: &[String], value: Value) -> Result<(), String> {
+    let (last, parent) = key.split_last().unwrap();
+    // The dotted keys never go into an array of tables
+    let table = table_mut(table, parent, &HashSet::new())?;
+    if table.contains_key(last) {
+        return Err(format!("{last:?} is defined twice"));
+    }
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+fn is_table_array(value: &Value) -> bool {
+    matches!(value, Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object))
+}
+
+fn write_table(
+    out: &mut String,
+    path: &mut Vec<String>,
+    table: &Map<String, Value>,
+) -> Result<(), String> {
+    // The values go before the sub-tables, after a header they would belong to it
+    for (key, value) in table {
+        if value.is_null() || value.is_object() || is_table_array(value) {
+            continue;
+        }
+        out.push_str(&format!("{} = {}\n", write_key(key), write_value(value)?));
+    }
+    for (key, value) in table {
+        path.push(write_key(key));
+        match value {
+            Value::Object(table) => {
+                out.push_str(&format!("\n[{}]\n", path.join(".")));
+                write_table(out, path, table)?;
+            }
+            Value::Array(tables) if is_table_array(value) => {
+                for table in tables {
+                    out.push_str(&format!("\n[[{}]]\n", path.join(".")));
+                    write_table(out, path, table.as_object().unwrap())?;
+                }
+            }
+            _ => {}
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+fn write_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '-')
+    {
+        key.to_string()
+    } else {
+        write_string(key)
+    }
+}
+
+fn write_string(string: &str) -> String {
+    let mut out = String::from("\"");
+    for char in string.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            char if char.is_control() => out.push_str(&format!("\\u{:04x}", char as u32)),
+            char => out.push(char),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn write_value(value: &Value) -> Result<String, String> {
+    Ok(match value {
+        Value::Null => return Err("TOML has no null for the arrays".to_string()),
+        Value::Bool(value) => value.to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::String(string) => write_string(string),
+        Value::Array(values) => {
+            let values: Result<Vec<_>, _> = values.iter().map(write_value).collect();
+            format!("[{}]", values?.join(", "))
+        }
+        Value::Object(table) => {
+            let entries: Result<Vec<_>, String> = table
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| Ok(format!("{} = {}", write_key(key), write_value(value)?)))
+                .collect();
+            format!("{{ {} }}", entries?.join(", "))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse(text: &str) -> Value {
+        from_str(text).unwrap_or_else(|err| panic!("{err} in {text:?}"))
+    }
+
+    fn error(text: &str) -> String {
+        from_str(text).expect_err(text)
+    }
+
+    #[test]
+    fn escapes() {
+        let value = parse(r#"s = "tab\tquote\"slash\\nl\nuni\u00e9\U0001F600esc\e""#);
+        assert_eq!(
+            value["s"],
+            "tab\tquote\"slash\\nl\nuni\u{e9}\u{1F600}esc\u{1b}"
+        );
+        assert_eq!(parse(r"s = 'C:\no\escape'")["s"], r"C:\no\escape");
+        assert!(error(r#"s = "\q""#).contains("invalid escape"));
+        assert!(error(r#"s = "\u12""#).contains("unicode"));
+        assert!(error("s = \"open\nx = 1").contains("unterminated"));
+    }
+
+    #[test]
+    fn multiline_strings() {
+        let value = parse("s = \"\"\"\nfirst\nsecond \\\n    joined\"\"\"\n");
+        assert_eq!(value["s"], "first\nsecond joined");
+        let value = parse("s = '''\nraw \\n\n''it'' '''\n");
+        assert_eq!(value["s"], "raw \\n\n''it'' ");
+        let value = parse("s = \"\"\"a \"quoted\" \"\"\"\"\n");
+        assert_eq!(value["s"], "a \"quoted\" \"");
+        assert!(error("s = \"\"\"never closed\n").contains("unterminated"));
+    }
+
+    #[test]
+    fn arrays_of_tables() {
+        let value = parse(
+            "[[binds]]\nkey = 1\n[binds.extra]\nx = 1\n\n[[binds]]\nkey = 2\n[binds.extra]\nx = 2\n",
+        );
+        assert_eq!(
+            value["binds"],
+            json!([
+                { "key": 1, "extra": { "x": 1 } },
+                { "key": 2, "extra": { "x": 2 } },
+            ])
+        );
+        assert!(error("a = [1]\n[[a]]\n").contains("array value"));
+        assert!(error("[[a]]\n[a]\n").contains("array of tables"));
+        assert!(error("[a]\n[[a]]\n").contains("already a value"));
+    }
+
+    #[test]
+    fn inline_tables() {
+        let value = parse("point = { x = 1, y.z = 'two', list = [1, 2] }\nempty = {}\n");
+        assert_eq!(
+            value["point"],
+            json!({ "x": 1, "y": { "z": "two" }, "list": [1, 2] })
+        );
+        assert_eq!(value["empty"], json!({}));
+        assert!(error("a = { x = 1 }\n[a]\n").contains("inline table"));
+        assert!(error("a = { x = 1 }\n[a.b]\n").contains("inline table"));
+        assert!(error("a = { x = 1 }\na.y = 2\n").contains("inline table"));
+        assert!(error("a = { x = 1, x = 2 }\n").contains("twice"));
+    }
+
+    #[test]
+    fn numbers() {
+        let value = parse(
+            "a = 1_000\nb = -17\nc = +5\nd = 0xff\ne = 0o17\nf = 0b101\ng = 1.5\nh = -2e3\ni = 18446744073709551615\n",
+        );
+        assert_eq!(value["a"], 1000);
+        assert_eq!(value["b"], -17);
+        assert_eq!(value["c"], 5);
+        assert_eq!(value["d"], 255);
+        assert_eq!(value["e"], 15);
+        assert_eq!(value["f"], 5);
+        assert_eq!(value["g"], 1.5);
+        assert_eq!(value["h"], -2000.0);
+        assert_eq!(value["i"], u64::MAX);
+        assert!(error("a = 12abc\n").contains("invalid value"));
+        assert!(error("a = nope\n").contains("invalid value"));
+    }
+
+    #[test]
+    fn duplicates_are_refused() {
+        assert!(error("[a]\nx = 1\n[a]\ny = 2\n").contains("defined twice"));
+        assert!(error("x = 1\nx = 2\n").contains("twice"));
+        assert!(error("a.b = 1\n[a.b]\n").contains("already a value"));
+        assert!(error("a.b.c = 1\n[a.b]\n").contains("dotted keys"));
+        assert!(error("[a.b]\n[a]\nb.c = 1\n").contains("dotted keys"));
+        // A parent table can be defined after its sub-table, once
+        let value = parse("[a.b]\nx = 1\n[a]\ny = 2\n");
+        assert_eq!(value["a"], json!({ "b": { "x": 1 }, "y": 2 }));
+    }
+
+    #[test]
+    fn written_documents_parse_back() {
+        let value = json!({
+            "name": "with \"quotes\"\nand lines",
+            "n": 3,
+            "none": null,
+            "list": [1, 2],
+            "table": { "a": true, "b": { "c": -1 } },
+            "tables": [{ "x": 1 }, { "x": 2 }],
+        });
+        let mut expected = value.clone();
+        expected.as_object_mut().unwrap().remove("none");
+        assert_eq!(parse(&to_string(&value).unwrap()), expected);
+    }
+}
@@ -0,0 +1,599 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs::File,
+    io::{self, Read, Write},
+    mem,
+    os::{
+        fd::{AsRawFd, FromRawFd, RawFd},
+        unix::net::UnixStream,
+    },
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::Instant,
+};
+
+use input_linux::{sys::input_event, Key, KeyState, RelativeAxis};
+
+use crate::{args::ScreenSize, backend::OutputBackend};
+
+const DISPLAY: u32 = 1;
+const POINTER_MANAGER: &str = "zwlr_virtual_pointer_manager_v1";
+const KEYBOARD_MANAGER: &str = "zwp_virtual_keyboard_manager_v1";
+
+/// The compositor compiles it with its own xkb data, the evdev keycodes are used as they are
+const KEYMAP: &str = "xkb_keymap {
+    xkb_keycodes { include \"evdev\" };
+    xkb_types { include \"complete\" };
+    xkb_compat { include \"complete\" };
+    xkb_symbols { include \"pc+us+inet(evdev)\" };
+};
+";
+
+enum Arg<'a> {
+    Uint(u32),
+    Int(i32),
+    Fixed(f64),
+    Str(&'a str),
+}
+
+struct Event {
+    object: u32,
+    opcode: u16,
+    body: Vec<u8>,
+}
+
+impl Event {
+    fn uint(&self, at: &mut usize) -> u32 {
+        let value = self
+            .body
+            .get(*at..*at + 4)
+            .map_or(0, |bytes| u32::from_ne_bytes(bytes.try_into().unwrap()));
+        *at += 4;
+        value
+    }
+
+    fn string(&self, at: &mut usize) -> String {
+        let len = self.uint(at) as usize;
+        let bytes = self.body.get(*at..*at + len).unwrap_or_default();
+        *at += len.next_multiple_of(4);
+        String::from_utf8_lossy(bytes.strip_suffix(&[0]).unwrap_or(bytes)).into_owned()
+    }
+
+    /// `wl_display.error`
+    fn is_error(&self) -> bool {
+        self.object == DISPLAY && self.opcode == 0
+    }
+
+    fn error(&self) -> String {
+        let mut at = 0;
+        let object = self.uint(&mut at);
+        let code = self.uint(&mut at);
+        let message = self.string(&mut at);
+        format!("Wayland protocol error on object {object}, code {code}: {message}")
+    }
+}
+
+fn read_event(stream: &mut impl Read) -> io::Result<Event> {
+    let mut header = [0; 8];
+    stream.read_exact(&mut header)?;
+    let object = u32::from_ne_bytes(header[..4].try_into().unwrap());
+    let size_opcode = u32::from_ne_bytes(header[4..].try_into().unwrap());
+    let mut body = vec![0; ((size_opcode >> 16) as usize).saturating_sub(8)];
+    stream.read_exact(&mut body)?;
+    Ok(Event {
+        object,
+        opcode: size_opcode as u16,
+        body,
+    })
+}
+
+/// Just enough of the client side of the Wayland wire protocol for the virtual devices
+struct Connection {
+    stream: UnixStream,
+    next_id: u32,
+}
+
+impl Connection {
+    fn connect() -> io::Result<Self> {
+        let display = env::var_os("WAYLAND_DISPLAY").unwrap_or_else(|| OsString::from("wayland-0"));
+        let mut path = PathBuf::from(display);
+        if path.is_relative() {
+            let Some(runtime_dir) = env::var_os("XDG_RUNTIME_DIR") else {
+                return Err(io::Error::other("XDG_RUNTIME_DIR is not set"));
+            };
+            path = PathBuf::from(runtime_dir).join(path);
+        }
+
+        Ok(Self {
+            stream: UnixStream::connect(path)?,
+            next_id: DISPLAY + 1,
+        })
+    }
+
+    fn new_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn message(object: u32, opcode: u16, args: &[Arg]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for arg in args {
+            match arg {
+                Arg::Uint(value) => body.extend(value.to_ne_bytes()),
+                Arg::Int(value) => body.extend(value.to_ne_bytes()),
+                Arg::Fixed(value) => body.extend(((value * 256.0) as i32).to_ne_bytes()),
+                Arg::Str(value) => {
+                    body.extend((value.len() as u32 + 1).to_ne_bytes());
+                    body.extend(value.as_bytes());
+                    body.push(0);
+                    body.resize(body.len().next_multiple_of(4), 0);
+                }
+            }
+        }
+
+        let mut message = Vec::with_capacity(8 + body.len());
+        message.extend(object.to_ne_bytes());
+        message.extend((((8 + body.len() as u32) << 16) | opcode as u32).to_ne_bytes());
+        message.extend(body);
+        message
+    }
+
+    fn send(&mut self, object: u32, opcode: u16, args: &[Arg]) -> io::Result<()> {
+        self.stream.write_all(&Self::message(object, opcode, args))
+    }
+
+    /// Sends the file descriptor with the message, as `SCM_RIGHTS`
+    fn send_with_fd(
+        &mut self,
+        object: u32,
+        opcode: u16,
+        args: &[Arg],
+        fd: RawFd,
+    ) -> io::Result<()> {
+        let message = Self::message(object, opcode, args);
+        let mut iov = libc::iovec {
+            iov_base: message.as_ptr() as *mut _,
+            iov_len: message.len(),
+        };
+
+        let space = unsafe { libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) } as usize;
+        // u64 keeps the buffer aligned for cmsghdr
+        let mut control = vec![0u64; space.div_ceil(8)];
+
+        unsafe {
+            let mut msg: libc::msghdr = mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr() as *mut _;
+            msg.msg_controllen = space as _;
+
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as _;
+            std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+
+            if libc::sendmsg(self.stream.as_raw_fd(), &msg, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits until the compositor handled every request, the other events are given to `on_event`
+    fn roundtrip(&mut self, mut on_event: impl FnMut(&Event)) -> Result<(), String> {
+        let callback = self.new_id();
+        // wl_display.sync
+        self.send(DISPLAY, 0, &[Arg::Uint(callback)])
+            .map_err(|err| format!("Cannot write to the Wayland compositor: {err}"))?;
+
+        loop {
+            let event = read_event(&mut self.stream)
+                .map_err(|err| format!("Cannot read from the Wayland compositor: {err}"))?;
+            if event.is_error() {
+                return Err(event.error());
+            }
+            if event.object == callback {
+                return Ok(());
+            }
+            on_event(&event);
+        }
+    }
+
+    /// `wl_registry.bind`
+    fn bind(&mut self, registry: u32, name: u32, interface: &str, version: u32) -> io::Result<u32> {
+        let id = self.new_id();
+        self.send(
+            registry,
+            0,
+            &[
+                Arg::Uint(name),
+                Arg::Str(interface),
+                Arg::Uint(version),
+                Arg::Uint(id),
+            ],
+        )?;
+        Ok(id)
+    }
+}
+
+/// Motion and scroll of the relayed events, sent together on `SYN_REPORT`
+#[derive(Default)]
+struct Pending {
+    x: i32,
+    y: i32,
+    wheel: i32,
+    hwheel: i32,
+}
+
+/// Sends the clicks through the wlr virtual pointer and virtual keyboard protocols,
+/// works on wlroots compositors without access to /dev/uinput
+pub struct WaylandOutput {
+    connection: Mutex<Connection>,
+    pointer: u32,
+    keyboard: Option<u32>,
+    /// Extents for the absolute positions
+    screen: Option<ScreenSize>,
+    start: Instant,
+    pending: Mutex<Pending>,
+}
+
+impl WaylandOutput {
+    /// The virtual keyboard is only created with `keyboard`, not every compositor allows it
+    pub fn connect(keyboard: bool, screen: Option<ScreenSize>) -> Result<Self, String> {
+        let mut connection = Connection::connect()
+            .map_err(|err| format!("Cannot connect to the Wayland compositor: {err}"))?;
+        let write_error = |err: io::Error| format!("Cannot write to the Wayland compositor: {err}");
+
+        let registry = connection.new_id();
+        // wl_display.get_registry
+        connection
+            .send(DISPLAY, 1, &[Arg::Uint(registry)])
+            .map_err(write_error)?;
+
+        let mut globals = Vec::new();
+        connection.roundtrip(|event| {
+            // wl_registry.global
+            if event.object == registry && event.opcode == 0 {
+                let mut at = 0;
+                let name = event.uint(&mut at);
+                globals.push((name, event.string(&mut at)));
+            }
+        })?;
+        let find = |interface: &str| {
+            globals
+                .iter()
+                .find(|(_, global)| global == interface)
+                .map(|(name, _)| *name)
+                .ok_or_else(|| {
+                    format!("The compositor does not support {interface}, the Wayland backend needs a wlroots compositor like Sway or Hyprland")
+                })
+        };
+
+        let seat = connection
+            .bind(registry, find("wl_seat")?, "wl_seat", 1)
+            .map_err(write_error)?;
+
+        let manager = connection
+            .bind(registry, find(POINTER_MANAGER)?, POINTER_MANAGER, 1)
+            .map_err(write_error)?;
+        let pointer = connection.new_id();
+        // create_virtual_pointer
+        connection
+            .send(manager, 0, &[Arg::Uint(seat), Arg::Uint(pointer)])
+            .map_err(write_error)?;
+
+        let keyboard = if keyboard {
+            let manager = connection
+                .bind(registry, find(KEYBOARD_MANAGER)?, KEYBOARD_MANAGER, 1)
+                .map_err(write_error)?;
+            let keyboard = connection.new_id();
+            // create_virtual_keyboard
+            connection
+                .send(manager, 0, &[Arg::Uint(seat), Arg::Uint(keyboard)])
+                .map_err(write_error)?;
+
+            let keymap = keymap_file().map_err(|err| {
+                format!("Cannot create the keymap for the virtual keyboard: {err}")
+            })?;
+            // keymap, format 1 is xkb_v1 and the size includes the NUL
+            connection
+                .send_with_fd(
+                    keyboard,
+                    0,
+                    &[Arg::Uint(1), Arg::Uint(KEYMAP.len() as u32 + 1)],
+                    keymap.as_raw_fd(),
+                )
+                .map_err(write_error)?;
+            Some(keyboard)
+        } else {
+            None
+        };
+
+        // The compositor answers with an error when it refuses the virtual devices
+        connection.roundtrip(|_| {})?;
+
+        let mut events = connection
+            .stream
+            .try_clone()
+            .map_err(|err| format!("Cannot read from the Wayland compositor: {err}"))?;
+        thread::spawn(move || loop {
+            match read_event(&mut events) {
+                Ok(event) if event.is_error() => {
+                    eprintln!("{}", event.error());
+                    std::process::exit(1);
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    eprintln!("Lost the connection to the Wayland compositor: {err}");
+                    std::process::exit(1);
+                }
+            }
+        });
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            pointer,
+            keyboard,
+            screen,
+            start: Instant::now(),
+            pending: Mutex::new(Pending::default()),
+        })
+    }
+
+    /// Milliseconds for the event timestamps
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    fn send(&self, object: u32, opcode: u16, args: &[Arg]) -> io::Result<()> {
+        self.connection.lock().unwrap().send(object, opcode, args)
+    }
+
+    fn frame(&self) -> io::Result<()> {
+        self.send(self.pointer, 4, &[])
+    }
+
+    /// Sends the key without the pointer frame, returns if it was a mouse button
+    fn key(&self, code: u16, state: i32) -> io::Result<bool> {
+        // Autorepeat is done by the compositor
+        if state == 2 {
+            return Ok(false);
+        }
+        let time = Arg::Uint(self.time());
+        if (input_linux::sys::BTN_MOUSE..=input_linux::sys::BTN_TASK).contains(&(code as i32)) {
+            self.send(
+                self.pointer,
+                2,
+                &[time, Arg::Uint(code as u32), Arg::Uint(state as u32)],
+            )?;
+            Ok(true)
+        } else {
+            if let Some(keyboard) = self.keyboard {
+                self.send(
+                    keyboard,
+                    1,
+                    &[time, Arg::Uint(code as u32), Arg::Uint(state as u32)],
+                )?;
+            }
+            Ok(false)
+        }
+    }
+
+    fn motion(&self, x: i32, y: i32) -> io::Result<()> {
+        if x == 0 && y == 0 {
+            return Ok(());
+        }
+        self.send(
+            self.pointer,
+            0,
+            &[
+                Arg::Uint(self.time()),
+                Arg::Fixed(x as f64),
+                Arg::Fixed(y as f64),
+            ],
+        )
+    }
+
+    /// `axis` 0 is vertical and 1 horizontal, positive `notches` is down or right
+    fn axis(&self, axis: u32, notches: i32) -> io::Result<()> {
+        if notches == 0 {
+            return Ok(());
+        }
+        // axis_source wheel
+        self.send(self.pointer, 5, &[Arg::Uint(0)])?;
+        self.send(
+            self.pointer,
+            7,
+            &[
+                Arg::Uint(self.time()),
+                Arg::Uint(axis),
+                Arg::Fixed(notches as f64 * 15.0),
+                Arg::Int(notches),
+            ],
+        )
+    }
+}
+
+/// The clicks cannot go anywhere once the compositor is gone, the `send_` methods have no error
+/// to return so they exit
+fn or_exit(result: io::Result<()>) {
+    if let Err(err) = result {
+        eprintln!("\x1b[0KCannot write to the Wayland compositor, exiting: {err}");
+        std::process::exit(1);
+    }
+}
+
+impl OutputBackend for WaylandOutput {
+    fn write(&self, events: &[input_event]) -> io::Result<usize> {
+        let mut pending = self.pending.lock().unwrap();
+        for event in events {
+            match event.type_ as i32 {
+                input_linux::sys::EV_KEY => {
+                    self.key(event.code, event.value)?;
+                }
+                input_linux::sys::EV_REL => match event.code as i32 {
+                    input_linux::sys::REL_X => pending.x += event.value,
+                    input_linux::sys::REL_Y => pending.y += event.value,
+                    input_linux::sys::REL_WHEEL => pending.wheel += event.value,
+                    input_linux::sys::REL_HWHEEL => pending.hwheel += event.value,
+                    _ => {}
+                },
+                input_linux::sys::EV_SYN if event.code as i32 == input_linux::sys::SYN_REPORT => {
+                    let Pending {
+                        x,
+                        y,
+                        wheel,
+                        hwheel,
+                    } = mem::take(&mut *pending);
+                    self.motion(x, y)?;
+                    self.axis(0, -wheel)?;
+                    self.axis(1, hwheel)?;
+                    self.frame()?;
+                }
+                _ => {}
+            }
+        }
+        Ok(events.len())
+    }
+
+    fn send_key(&self, key: Key, state: KeyState) {
+        or_exit(match self.key(key as u16, state.into()) {
+            Ok(true) => self.frame(),
+            result => result.map(|_| ()),
+        });
+    }
+
+    fn send_relative(&self, axes: &[(RelativeAxis, i32)]) {
+        let (mut x, mut y) = (0, 0);
+        for (axis, value) in axes {
+            match axis {
+                RelativeAxis::X => x += value,
+                RelativeAxis::Y => y += value,
+                _ => {}
+            }
+        }
+        or_exit(self.motion(x, y).and_then(|_| self.frame()));
+    }
+
+    fn send_position(&self, x: i32, y: i32) {
+        let Some(screen) = self.screen else {
+            return;
+        };
+        let position = self.send(
+            self.pointer,
+            1,
+            &[
+                Arg::Uint(self.time()),
+                Arg::Uint(x.max(0) as u32),
+                Arg::Uint(y.max(0) as u32),
+                Arg::Uint(screen.width as u32),
+                Arg::Uint(screen.height as u32),
+            ],
+        );
+        or_exit(position.and_then(|_| self.frame()));
+    }
+
+    fn send_scroll(&self, notches: i32) {
+        // Wayland scrolls down for positive values
+        or_exit(self.axis(0, -notches).and_then(|_| self.frame()));
+    }
+}
+
+/// Keymap in a memfd, for sending to the compositor
+fn keymap_file() -> io::Result<File> {
+    let fd = unsafe { libc::memfd_create(c"theclicker-keymap".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(KEYMAP.as_bytes())?;
+    file.write_all(&[0])?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::{fd::OwnedFd, unix::fs::FileExt};
+
+    use super::*;
+
+    fn words(bytes: &[u8]) -> Vec<u32> {
+        bytes
+            .chunks(4)
+            .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn messages_have_the_size_and_opcode_in_the_header() {
+        let message = Connection::message(7, 3, &[Arg::Uint(5), Arg::Int(-1), Arg::Fixed(1.5)]);
+        assert_eq!(words(&message), [7, (20 << 16) | 3, 5, u32::MAX, 384]);
+    }
+
+    #[test]
+    fn strings_are_terminated_and_padded() {
+        let message = Connection::message(2, 0, &[Arg::Str("wl_seat"), Arg::Uint(1)]);
+        assert_eq!(message.len(), 8 + 4 + 8 + 4);
+        assert_eq!(&message[8..12], 8u32.to_ne_bytes());
+        assert_eq!(&message[12..20], b"wl_seat\0");
+        assert_eq!(&message[20..], 1u32.to_ne_bytes());
+
+        // With the terminator the length is a multiple of 4, a whole word of padding is not added
+        let message = Connection::message(2, 0, &[Arg::Str("abc")]);
+        assert_eq!(message.len(), 8 + 4 + 4);
+
+        let event = read_event(&mut &message[..]).unwrap();
+        assert_eq!((event.object, event.opcode), (2, 0));
+        assert_eq!(event.string(&mut 0), "abc");
+    }
+
+    #[test]
+    fn the_fd_is_sent_with_the_message() {
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        let mut connection = Connection { stream, next_id: 2 };
+        let keymap = keymap_file().unwrap();
+        connection
+            .send_with_fd(4, 0, &[Arg::Uint(1)], keymap.as_raw_fd())
+            .unwrap();
+
+        let mut message = [0u8; 12];
+        let mut iov = libc::iovec {
+            iov_base: message.as_mut_ptr() as *mut _,
+            iov_len: message.len(),
+        };
+        let space = unsafe { libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) } as usize;
+        let mut control = vec![0u64; space.div_ceil(8)];
+        let fd = unsafe {
+            let mut msg: libc::msghdr = mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr() as *mut _;
+            msg.msg_controllen = space as _;
+            assert_eq!(libc::recvmsg(peer.as_raw_fd(), &mut msg, 0), 12);
+
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            assert!(!cmsg.is_null());
+            assert_eq!((*cmsg).cmsg_type, libc::SCM_RIGHTS);
+            OwnedFd::from_raw_fd(std::ptr::read_unaligned(
+                libc::CMSG_DATA(cmsg) as *const RawFd
+            ))
+        };
+        assert_eq!(words(&message), [4, (12 << 16), 1]);
+
+        // The received fd is another handle on the same keymap
+        let mut keymap = vec![0; KEYMAP.len()];
+        File::from(fd).read_exact_at(&mut keymap, 0).unwrap();
+        assert_eq!(keymap, KEYMAP.as_bytes());
+
+        // Nothing else was written
+        peer.set_nonblocking(true).unwrap();
+        assert_eq!(
+            peer.read(&mut [0; 1]).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+    }
+}
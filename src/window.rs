@@ -0,0 +1,249 @@
+use std::{
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
+
+use serde_json::Value;
+
+use crate::{
+    args::{ClickOptions, RuleAction, WindowRule},
+    PauseSource,
+};
+
+/// The focused window
+#[derive(Default, Debug, PartialEq)]
+pub struct Focus {
+    pub class: String,
+    pub title: String,
+}
+
+impl WindowRule {
+    fn matches(&self, focus: &Focus) -> bool {
+        let contains =
+            |value: &str, pattern: &str| value.to_lowercase().contains(&pattern.to_lowercase());
+        self.class
+            .as_ref()
+            .is_none_or(|class| contains(&focus.class, class))
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|title| contains(&focus.title, title))
+    }
+}
+
+/// Pauses the autoclicker depending on the focused window
+pub struct WindowRules {
+    rules: Vec<WindowRule>,
+    debug: bool,
+}
+
+impl WindowRules {
+    pub fn from_options(options: &ClickOptions, debug: bool) -> Option<Self> {
+        (!options.window_rules.is_empty()).then(|| Self {
+            rules: options.window_rules.clone(),
+            debug,
+        })
+    }
+
+    /// Paused when a `pause` rule matches, or when there are `only` rules and none matches
+    pub fn paused(&self, focus: &Focus) -> bool {
+        let mut only = self
+            .rules
+            .iter()
+            .filter(|rule| rule.action == RuleAction::Only)
+            .peekable();
+        let outside_only = only.peek().is_some() && !only.any(|rule| rule.matches(focus));
+
+        outside_only
+            || self
+                .rules
+                .iter()
+                .any(|rule| rule.action == RuleAction::Pause && rule.matches(focus))
+    }
+
+    /// Follows the focused window with the IPC of Hyprland or Sway, or with X11
+    pub fn spawn(self, mut pause: PauseSource) {
+        let watch = if let Some(signature) = env::var_os("HYPRLAND_INSTANCE_SIGNATURE") {
+            Watch::Hyprland(signature.into())
+        } else if let Some(socket) = env::var_os("SWAYSOCK") {
+            Watch::Sway(socket.into())
+        } else if env::var_os("DISPLAY").is_some() {
+            Watch::X11
+        } else {
+            eprintln!("The window rules need Hyprland, Sway or an X11 session");
+            std::process::exit(1);
+        };
+
+        thread::spawn(move || {
+            let on_focus = |focus: Focus| {
+                if self.debug {
+                    println!("Focused window: {focus:?}");
+                }
+                pause.set(self.paused(&focus));
+            };
+            let result = match watch {
+                Watch::Hyprland(signature) => watch_hyprland(&signature, on_focus),
+                Watch::Sway(socket) => watch_sway(&socket, on_focus),
+                Watch::X11 => watch_x11(on_focus),
+            };
+            if let Err(err) = result {
+                eprintln!("Cannot watch the focused window: {err}");
+            }
+        });
+    }
+}
+
+enum Watch {
+    Hyprland(PathBuf),
+    Sway(PathBuf),
+    X11,
+}
+
+/// The sockets are in `$XDG_RUNTIME_DIR/hypr`, or in `/tmp/hypr` for older versions
+fn hyprland_socket(signature: &Path, name: &str) -> PathBuf {
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("hypr"));
+    runtime_dir
+        .into_iter()
+        .chain([PathBuf::from("/tmp/hypr")])
+        .map(|dir| dir.join(signature).join(name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("/tmp/hypr").join(signature).join(name))
+}
+
+/// Follows the `activewindow` events of the Hyprland event socket
+fn watch_hyprland(signature: &Path, mut on_focus: impl FnMut(Focus)) -> io::Result<()> {
+    let mut request = UnixStream::connect(hyprland_socket(signature, ".socket.sock"))?;
+    request.write_all(b"j/activewindow")?;
+    let mut response = String::new();
+    request.read_to_string(&mut response)?;
+    if let Ok(window) = serde_json::from_str::<Value>(&response) {
+        on_focus(Focus {
+            class: window["class"].as_str().unwrap_or_default().to_owned(),
+            title: window["title"].as_str().unwrap_or_default().to_owned(),
+        });
+    }
+
+    let events = UnixStream::connect(hyprland_socket(signature, ".socket2.sock"))?;
+    for line in BufReader::new(events).lines() {
+        // activewindow>>firefox,Title
+        let line = line?;
+        let Some(window) = line.strip_prefix("activewindow>>") else {
+            continue;
+        };
+        let (class, title) = window.split_once(',').unwrap_or((window, ""));
+        on_focus(Focus {
+            class: class.to_owned(),
+            title: title.to_owned(),
+        });
+    }
+
+    Err(io::Error::other("Hyprland closed the event socket"))
+}
+
+const SWAY_GET_TREE: u32 = 4;
+const SWAY_SUBSCRIBE: u32 = 2;
+
+fn sway_send(stream: &mut UnixStream, kind: u32, payload: &str) -> io::Result<()> {
+    let mut message = b"i3-ipc".to_vec();
+    message.extend((payload.len() as u32).to_ne_bytes());
+    message.extend(kind.to_ne_bytes());
+    message.extend(payload.as_bytes());
+    stream.write_all(&message)
+}
+
+fn sway_read(stream: &mut UnixStream) -> io::Result<Value> {
+    let mut header = [0; 14];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload)?;
+    serde_json::from_slice(&payload).map_err(io::Error::other)
+}
+
+/// Native windows have an `app_id`, Xwayland windows a class in `window_properties`
+fn sway_focus(node: &Value) -> Focus {
+    let class = node["app_id"]
+        .as_str()
+        .or(node["window_properties"]["class"].as_str())
+        .unwrap_or_default();
+    Focus {
+        class: class.to_owned(),
+        title: node["name"].as_str().unwrap_or_default().to_owned(),
+    }
+}
+
+fn sway_find_focused(node: &Value) -> Option<&Value> {
+    if node["focused"].as_bool() == Some(true) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|children| node[children].as_array())
+        .flatten()
+        .find_map(sway_find_focused)
+}
+
+/// Follows the `window` events of the Sway IPC
+fn watch_sway(socket: &Path, mut on_focus: impl FnMut(Focus)) -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket)?;
+
+    sway_send(&mut stream, SWAY_GET_TREE, "")?;
+    if let Some(node) = sway_find_focused(&sway_read(&mut stream)?) {
+        on_focus(sway_focus(node));
+    }
+
+    sway_send(&mut stream, SWAY_SUBSCRIBE, r#"["window"]"#)?;
+    loop {
+        let event = sway_read(&mut stream)?;
+        let container = &event["container"];
+        let focused = container["focused"].as_bool() == Some(true);
+        if matches!(event["change"].as_str(), Some("focus" | "title")) && focused {
+            on_focus(sway_focus(container));
+        }
+    }
+}
+
+/// Follows the focused window with `xprop`, calls `on_focus` on every change
+fn watch_x11(mut on_focus: impl FnMut(Focus)) -> io::Result<()> {
+    let mut spy = Command::new("xprop")
+        .args(["-root", "-spy", "_NET_ACTIVE_WINDOW"])
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let lines = BufReader::new(spy.stdout.take().unwrap()).lines();
+
+    for line in lines {
+        // _NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007
+        let line = line?;
+        let Some(id) = line.rsplit(' ').next().filter(|id| id.starts_with("0x")) else {
+            continue;
+        };
+        on_focus(x11_focus(id).unwrap_or_default());
+    }
+
+    Err(io::Error::other("xprop stopped"))
+}
+
+fn x11_focus(id: &str) -> io::Result<Focus> {
+    let output = Command::new("xprop")
+        .args(["-id", id, "WM_CLASS", "_NET_WM_NAME"])
+        .output()?;
+
+    let mut focus = Focus::default();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        // WM_CLASS(STRING) = "Navigator", "firefox"
+        let Some((name, value)) = line.split_once(" = ") else {
+            continue;
+        };
+        let value = value.trim_matches('"');
+        if name.starts_with("WM_CLASS") {
+            focus.class = value.replace("\", \"", " ");
+        } else if name.starts_with("_NET_WM_NAME") {
+            focus.title = value.to_owned();
+        }
+    }
+    Ok(focus)
+}
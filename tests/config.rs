@@ -0,0 +1,274 @@
+use std::path::PathBuf;
+
+use clap::{CommandFactory, FromArgMatches, Parser};
+use serde_json::{json, Value};
+use theclicker::{Args, Config, ConfigCommand};
+
+/// A file in the temporary directory, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("theclicker-{}-{name}", std::process::id())))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn config_from_args(args: &[&str]) -> Config {
+    let args = Args::parse_from(std::iter::once("theclicker").chain(args.iter().copied()));
+    let command = ConfigCommand::try_from(args.command.unwrap()).unwrap();
+    serde_json::from_value(json!({ "debug": true, "beep": false, "command": command })).unwrap()
+}
+
+/// The command the clicker runs with these arguments, after the config is loaded
+fn loaded_command(args: &[&str]) -> Result<Value, String> {
+    let matches =
+        Args::command().get_matches_from(std::iter::once("theclicker").chain(args.iter().copied()));
+    let args = Args::from_arg_matches(&matches)
+        .unwrap()
+        .load_from_config_or_default(&matches)
+        .map_err(|err| err.to_string())?;
+    let command = ConfigCommand::try_from(args.command.unwrap())?;
+    Ok(serde_json::to_value(command).unwrap())
+}
+
+fn round_trip(config: &Config, file: &TempFile) -> Config {
+    config.save_to_file(&file.0).unwrap();
+    Config::load(&file.0).unwrap()
+}
+
+fn value(config: &Config) -> Value {
+    serde_json::to_value(config).unwrap()
+}
+
+#[test]
+fn run_command_round_trips_in_json_and_toml() {
+    let config = config_from_args(&[
+        "run",
+        "-d",
+        "/dev/input/event3",
+        "-l",
+        "BTN_SIDE",
+        "-r",
+        "29+64",
+        "-T",
+        "274",
+        "--grab",
+        "-c",
+        "40",
+        "--left-key",
+        "KEY_F6",
+        "--bind",
+        "KEY_F7:macro:clicks.json",
+        "--position",
+        "960,540",
+        "--screen",
+        "1920x1080",
+        "--jiggle",
+        "30s",
+    ]);
+    for name in ["round-trip.json", "round-trip.toml"] {
+        let file = TempFile::new(name);
+        assert_eq!(value(&round_trip(&config, &file)), value(&config), "{name}");
+    }
+}
+
+#[test]
+fn legacy_command_round_trips_in_json_and_toml() {
+    let config = config_from_args(&["run-legacy", "-d", "/dev/input/mouse0", "-C", "10"]);
+    for name in ["legacy.json", "legacy.toml"] {
+        let file = TempFile::new(name);
+        assert_eq!(value(&round_trip(&config, &file)), value(&config), "{name}");
+    }
+}
+
+#[test]
+fn profiles_round_trip() {
+    let mut config = config_from_args(&["run", "-d", "mouse", "-l", "275", "--profile", "fast"]);
+    config.profiles.insert(
+        "fast".to_string(),
+        config_from_args(&["run", "-d", "mouse", "-l", "276", "-c", "5"]).command,
+    );
+    for name in ["profiles.json", "profiles.toml"] {
+        let file = TempFile::new(name);
+        let loaded = round_trip(&config, &file);
+        assert_eq!(value(&loaded), value(&config), "{name}");
+        assert!(loaded.profiles.contains_key("fast"));
+    }
+}
+
+#[test]
+fn saved_configs_have_the_version_and_the_key_names() {
+    let config = config_from_args(&["run", "-d", "mouse", "-l", "275", "-T", "29+64"]);
+    let file = TempFile::new("names.json");
+    round_trip(&config, &file);
+    let saved: Value = serde_json::from_str(&std::fs::read_to_string(&file.0).unwrap()).unwrap();
+    assert!(saved["version"]
+        .as_u64()
+        .is_some_and(|version| version >= 2));
+    assert_eq!(saved["command"]["left_bind"], "BTN_SIDE");
+    assert_eq!(saved["command"]["lock_unlock_bind"], "KEY_LEFTCTRL+KEY_F6");
+}
+
+#[test]
+fn configs_without_a_version_are_migrated() {
+    let file = TempFile::new("old.json");
+    let old = json!({
+        "debug": false,
+        "beep": false,
+        "command": {
+            "type": "RunLegacy",
+            "device_query": "/dev/input/mouse0",
+            "cooldown": 100,
+            "cooldown_press_release": 25,
+            "position": [10, 20],
+            "screen": "1920x1080",
+        },
+    });
+    std::fs::write(&file.0, old.to_string()).unwrap();
+
+    let config = value(&Config::load(&file.0).unwrap());
+    assert_eq!(config["command"]["positions"], json!([[10, 20]]));
+    assert_eq!(config["command"]["cooldown"], 100);
+    assert!(config["command"].get("position").is_none());
+}
+
+#[test]
+fn configs_of_newer_versions_are_refused() {
+    let file = TempFile::new("new.toml");
+    std::fs::write(
+        &file.0,
+        "version = 1000\ndebug = false\nbeep = false\n\n[command]\ntype = \"RunLegacy\"\ndevice_query = \"mouse\"\n",
+    )
+    .unwrap();
+    let err = Config::load(&file.0).unwrap_err().to_string();
+    assert!(err.contains("version 1000"), "{err}");
+}
+
+#[test]
+fn profiles_inherit_from_their_base() {
+    let file = TempFile::new("inherits.toml");
+    std::fs::write(
+        &file.0,
+        r#"debug = false
+beep = false
+
+[command]
+type = "Run"
+device_query = "auto"
+left_bind = "BTN_SIDE"
+hold = false
+grab = false
+
+[profiles.minecraft]
+inherits = "default"
+cooldown = 40
+
+[profiles.fast]
+inherits = "minecraft"
+hold = true
+"#,
+    )
+    .unwrap();
+
+    let config = value(&Config::load(&file.0).unwrap());
+    let fast = &config["profiles"]["fast"];
+    assert_eq!(fast["device_query"], "auto");
+    assert_eq!(fast["left_bind"], "BTN_SIDE");
+    assert_eq!(fast["cooldown"], 40);
+    assert_eq!(fast["hold"], true);
+    assert_eq!(config["profiles"]["minecraft"]["hold"], false);
+}
+
+#[test]
+fn inheriting_in_a_loop_is_refused() {
+    let file = TempFile::new("loop.toml");
+    std::fs::write(
+        &file.0,
+        "debug = false\nbeep = false\n\n[command]\ntype = \"RunLegacy\"\ndevice_query = \"mouse\"\n\n[profiles.a]\ninherits = \"b\"\n\n[profiles.b]\ninherits = \"a\"\n",
+    )
+    .unwrap();
+    let err = Config::load(&file.0).unwrap_err().to_string();
+    assert!(err.contains("loop"), "{err}");
+}
+
+#[test]
+fn options_given_with_a_profile_are_kept() {
+    let file = TempFile::new("given.toml");
+    std::fs::write(
+        &file.0,
+        r#"debug = false
+beep = false
+
+[command]
+type = "RunLegacy"
+device_query = "mouse"
+
+[profiles.fast]
+type = "Run"
+device_query = "auto"
+left_bind = "BTN_SIDE"
+hold = true
+grab = false
+cooldown = 5
+
+[profiles.legacy]
+type = "RunLegacy"
+device_query = "mouse0"
+"#,
+    )
+    .unwrap();
+    let config = file.0.to_str().unwrap();
+
+    let command = loaded_command(&[
+        "--config",
+        config,
+        "run",
+        "--profile",
+        "fast",
+        "-C",
+        "3",
+        "--grab",
+    ])
+    .unwrap();
+    assert_eq!(command["device_query"], "auto");
+    assert_eq!(command["left_bind"], "BTN_SIDE");
+    assert_eq!(command["hold"], true);
+    assert_eq!(command["grab"], true);
+    assert_eq!(command["cooldown_press_release"], 3);
+
+    let command = loaded_command(&["--config", config, "run", "--profile", "fast"]).unwrap();
+    assert_eq!(command["grab"], false);
+
+    let err =
+        loaded_command(&["--config", config, "run", "--profile", "legacy", "--grab"]).unwrap_err();
+    assert!(err.contains("grab"), "{err}");
+}
+
+#[test]
+fn patterns_with_invalid_keys_are_refused() {
+    let file = TempFile::new("pattern.json");
+    let pattern = |key: u16| {
+        json!({
+            "debug": false,
+            "beep": false,
+            "command": {
+                "type": "Run",
+                "device_query": "auto",
+                "left_bind": "BTN_SIDE",
+                "hold": false,
+                "grab": false,
+                "left_pattern": [{ "key": key, "hold": 10 }, { "wait": 20 }],
+            },
+        })
+    };
+    std::fs::write(&file.0, pattern(30).to_string()).unwrap();
+    Config::load(&file.0).unwrap();
+    std::fs::write(&file.0, pattern(9999).to_string()).unwrap();
+    assert!(Config::load(&file.0).is_err());
+}